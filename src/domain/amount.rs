@@ -0,0 +1,220 @@
+use crate::error::amount_error::AmountError;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Amount(i64);
+
+impl Amount {
+    pub const DECIMALS: u32 = 4;
+    pub const SCALE: i64 = 10_i64.pow(Self::DECIMALS);
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn from_raw(raw: i64) -> Self {
+        Self(raw)
+    }
+
+    pub fn raw(&self) -> i64 {
+        self.0
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    // Amounts of records; balances and stored values can be zero or negative.
+    pub fn positive(self) -> Result<Amount, AmountError> {
+        if self.0 <= 0 {
+            return Err(AmountError::NotPositive { amount: self });
+        }
+        Ok(self)
+    }
+
+    pub fn checked_add(self, other: Amount) -> Result<Amount, AmountError> {
+        self.0
+            .checked_add(other.0)
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Result<Amount, AmountError> {
+        self.0
+            .checked_sub(other.0)
+            .map(Amount)
+            .ok_or(AmountError::Overflow)
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AmountError::InvalidFormat {
+            value: s.to_string(),
+        };
+
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+
+        let (int_part, frac_part) = match unsigned.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, frac_part),
+            None => (unsigned, ""),
+        };
+
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if int_part.is_empty()
+            || !all_digits(int_part)
+            || !all_digits(frac_part)
+            || (unsigned.contains('.') && frac_part.is_empty())
+        {
+            return Err(invalid());
+        }
+
+        if frac_part.len() > Self::DECIMALS as usize {
+            return Err(AmountError::TooManyDecimals {
+                value: s.to_string(),
+            });
+        }
+
+        let int_value: i64 = int_part.parse().map_err(|_| AmountError::Overflow)?;
+        let frac_value: i64 = if frac_part.is_empty() {
+            0
+        } else {
+            frac_part.parse::<i64>().map_err(|_| invalid())?
+                * 10_i64.pow(Self::DECIMALS - frac_part.len() as u32)
+        };
+
+        let raw = int_value
+            .checked_mul(Self::SCALE)
+            .and_then(|v| v.checked_add(frac_value))
+            .ok_or(AmountError::Overflow)?;
+
+        Ok(Amount(if negative { -raw } else { raw }))
+    }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl TryFrom<String> for Amount {
+    type Error = AmountError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.is_negative() { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let scale = Self::SCALE as u64;
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            abs / scale,
+            abs % scale,
+            width = Self::DECIMALS as usize
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_integer() {
+        let amount: Amount = "12".parse().unwrap();
+        assert_eq!(amount.raw(), 120_000);
+    }
+
+    #[test]
+    fn test_parse_decimals() {
+        let amount: Amount = "1.1021".parse().unwrap();
+        assert_eq!(amount.raw(), 11_021);
+
+        let amount: Amount = "0.5".parse().unwrap();
+        assert_eq!(amount.raw(), 5_000);
+    }
+
+    #[test]
+    fn test_negative_and_zero_are_not_positive() {
+        for value in ["-2.25", "0", "0.0000"] {
+            let result = value.parse::<Amount>().unwrap().positive();
+            assert!(
+                matches!(result, Err(AmountError::NotPositive { .. })),
+                "{value} should be rejected"
+            );
+        }
+        assert_eq!(
+            "0.0001"
+                .parse::<Amount>()
+                .unwrap()
+                .positive()
+                .unwrap()
+                .raw(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_parse_too_many_decimals() {
+        let result = "1.00001".parse::<Amount>();
+        assert!(matches!(result, Err(AmountError::TooManyDecimals { .. })));
+    }
+
+    #[test]
+    fn test_parse_invalid_format() {
+        for value in ["", "abc", "1.", ".5", "1.2.3", "1e5", "+1", "- 1"] {
+            let result = value.parse::<Amount>();
+            assert!(
+                matches!(result, Err(AmountError::InvalidFormat { .. })),
+                "{value} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_overflow() {
+        let result = "922337203685478".parse::<Amount>();
+        assert!(matches!(result, Err(AmountError::Overflow)));
+    }
+
+    #[test]
+    fn test_checked_add_is_exact() {
+        let mut total = Amount::ZERO;
+        let tenth: Amount = "0.1".parse().unwrap();
+        for _ in 0..10 {
+            total = total.checked_add(tenth).unwrap();
+        }
+        assert_eq!(total, "1".parse().unwrap());
+    }
+
+    #[test]
+    fn test_checked_add_overflow() {
+        let result = Amount::from_raw(i64::MAX).checked_add(Amount::from_raw(1));
+        assert!(matches!(result, Err(AmountError::Overflow)));
+    }
+
+    #[test]
+    fn test_checked_sub_overflow() {
+        let result = Amount::from_raw(i64::MIN).checked_sub(Amount::from_raw(1));
+        assert!(matches!(result, Err(AmountError::Overflow)));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Amount::from_raw(15_000).to_string(), "1.5000");
+        assert_eq!(Amount::from_raw(-5).to_string(), "-0.0005");
+        assert_eq!(Amount::ZERO.to_string(), "0.0000");
+    }
+}
@@ -1,50 +1,87 @@
-#[derive(Debug, Clone, Copy)]
-pub struct AvailableAmount(f64);
+use crate::domain::amount::Amount;
+use crate::error::amount_error::AmountError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct AvailableAmount(Amount);
 
 impl AvailableAmount {
-    pub fn new(amount: f64) -> Self {
+    pub fn new(amount: Amount) -> Self {
         Self(amount)
     }
 
-    pub fn get(&self) -> f64 {
+    pub fn get(&self) -> Amount {
         self.0
     }
 
-    pub fn add(&mut self, amount: f64) {
-        self.0 += amount;
+    pub fn checked_add(&self, amount: Amount) -> Result<Self, AmountError> {
+        Ok(Self(self.0.checked_add(amount)?))
+    }
+
+    pub fn checked_sub(&self, amount: Amount) -> Result<Self, AmountError> {
+        Ok(Self(self.0.checked_sub(amount)?))
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct HeldAmount(f64);
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct HeldAmount(Amount);
 
 impl HeldAmount {
-    pub fn new(amount: f64) -> Self {
+    pub fn new(amount: Amount) -> Self {
         Self(amount)
     }
 
-    pub fn add(&mut self, amount: f64) {
-        self.0 += amount;
+    pub fn checked_add(&self, amount: Amount) -> Result<Self, AmountError> {
+        Ok(Self(self.0.checked_add(amount)?))
     }
 
-    pub fn get(&self) -> f64 {
+    pub fn checked_sub(&self, amount: Amount) -> Result<Self, AmountError> {
+        Ok(Self(self.0.checked_sub(amount)?))
+    }
+
+    pub fn get(&self) -> Amount {
         self.0
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct TotalAmount(f64);
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TotalAmount(Amount);
 
 impl TotalAmount {
-    pub fn new(amount: f64) -> Self {
+    pub fn new(amount: Amount) -> Self {
         Self(amount)
     }
 
-    pub fn add(&mut self, amount: f64) {
-        self.0 += amount;
+    pub fn checked_add(&self, amount: Amount) -> Result<Self, AmountError> {
+        Ok(Self(self.0.checked_add(amount)?))
+    }
+
+    pub fn checked_sub(&self, amount: Amount) -> Result<Self, AmountError> {
+        Ok(Self(self.0.checked_sub(amount)?))
     }
 
-    pub fn get(&self) -> f64 {
+    pub fn get(&self) -> Amount {
         self.0
     }
 }
+
+// Balances of a client in one currency.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Balance {
+    pub available: AvailableAmount,
+    pub held: HeldAmount,
+    pub total: TotalAmount,
+}
+
+impl Default for Balance {
+    fn default() -> Self {
+        Self {
+            available: AvailableAmount::new(Amount::ZERO),
+            held: HeldAmount::new(Amount::ZERO),
+            total: TotalAmount::new(Amount::ZERO),
+        }
+    }
+}
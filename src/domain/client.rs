@@ -1,14 +1,18 @@
-use crate::domain::amounts::{AvailableAmount, HeldAmount, TotalAmount};
+use crate::domain::amount::Amount;
+use crate::domain::amounts::Balance;
 use crate::domain::client_id::ClientId;
 use crate::domain::client_status::ClientStatus;
+use crate::domain::currency::Currency;
+use crate::domain::dispute_policy::DisputePolicy;
 use crate::error::client_error::ClientError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Client {
     pub id: ClientId,
-    pub available: AvailableAmount,
-    pub held: HeldAmount,
-    pub total: TotalAmount,
+    // One bucket per currency the client has moved funds in.
+    pub balances: BTreeMap<Currency, Balance>,
     pub status: ClientStatus,
 }
 
@@ -16,66 +20,283 @@ impl Client {
     pub fn new(id: ClientId) -> Self {
         Self {
             id,
-            available: AvailableAmount::new(0.0),
-            held: HeldAmount::new(0.0),
-            total: TotalAmount::new(0.0),
+            balances: BTreeMap::new(),
             status: ClientStatus::Active,
         }
     }
 
+    pub fn balance(&self, currency: Currency) -> Balance {
+        self.balances.get(&currency).copied().unwrap_or_default()
+    }
+
     pub fn is_locked(&self) -> bool {
         self.status == ClientStatus::Locked
     }
 
-    pub fn deposit(&mut self, amount: f64) -> Result<(), ClientError> {
-        self.available.add(amount);
-        self.total.add(amount);
+    pub fn ensure_not_locked(&self) -> Result<(), ClientError> {
+        if self.is_locked() {
+            return Err(ClientError::Locked { id: self.id });
+        }
         Ok(())
     }
 
-    pub fn withdraw(&mut self, amount: f64) -> Result<(), ClientError> {
-        if self.available.get() < amount {
-            return Err(ClientError::FundsUpdateError {
-                id: self.id.clone(),
-                tx_type: "withdraw".to_string(),
-            });
+    pub fn ensure_open(&self) -> Result<(), ClientError> {
+        if self.status == ClientStatus::Closed {
+            return Err(ClientError::Closed { id: self.id });
         }
-
-        self.available.add(-amount);
-        self.total.add(-amount);
         Ok(())
     }
 
-    pub fn dispute(&mut self, amount: f64) -> Result<(), ClientError> {
-        if self.available.get() < amount {
-            return Err(ClientError::FundsUpdateError {
-                id: self.id.clone(),
-                tx_type: "dispute".to_string(),
-            });
+    // Withdrawals, outgoing transfers, exchanges, authorizations and their captures, and refunds.
+    pub fn ensure_can_debit(&self) -> Result<(), ClientError> {
+        match self.status {
+            ClientStatus::Locked => Err(ClientError::Locked { id: self.id }),
+            ClientStatus::Frozen => Err(ClientError::Frozen { id: self.id }),
+            _ => self.ensure_open(),
         }
+    }
 
-        self.available.add(-amount);
-        self.held.add(amount);
+    // Operators can lift a lock or a freeze and freeze an active account. Closing is final.
+    pub fn change_status(&mut self, to: ClientStatus) -> Result<(), ClientError> {
+        let allowed = match (self.status, to) {
+            (ClientStatus::Locked | ClientStatus::Frozen, ClientStatus::Active) => true,
+            (ClientStatus::Active, ClientStatus::Frozen) => true,
+            (from, ClientStatus::Closed) => from != ClientStatus::Closed,
+            _ => false,
+        };
+        if !allowed {
+            return Err(ClientError::InvalidStatusChange {
+                id: self.id,
+                from: self.status,
+                to,
+            });
+        }
+        self.status = to;
         Ok(())
     }
 
-    pub fn resolve(&mut self, amount: f64) -> Result<(), ClientError> {
-        if self.held.get() < amount {
-            return Err(ClientError::FundsUpdateError {
-                id: self.id.clone(),
-                tx_type: "resolve".to_string(),
-            });
+    pub fn deposit(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.update(currency, |_, balance| {
+            balance.available = balance.available.checked_add(amount)?;
+            balance.total = balance.total.checked_add(amount)?;
+            Ok(())
+        })
+    }
+
+    pub fn withdraw(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.debit(currency, amount, "withdraw")
+    }
+
+    pub fn transfer_out(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.debit(currency, amount, "transfer")
+    }
+
+    pub fn refund(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.debit(currency, amount, "refund")
+    }
+
+    pub fn pay_fee(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.debit(currency, amount, "fee")
+    }
+
+    // Fees are given back on chargebacks even if the house account already spent them.
+    pub fn return_fee(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.update(currency, |_, balance| {
+            balance.available = balance.available.checked_sub(amount)?;
+            balance.total = balance.total.checked_sub(amount)?;
+            Ok(())
+        })
+    }
+
+    pub fn reverse_deposit(
+        &mut self,
+        currency: Currency,
+        amount: Amount,
+    ) -> Result<(), ClientError> {
+        self.debit(currency, amount, "reversal")
+    }
+
+    pub fn exchange(
+        &mut self,
+        currency: Currency,
+        amount: Amount,
+        to_currency: Currency,
+        to_amount: Amount,
+    ) -> Result<(), ClientError> {
+        self.debit(currency, amount, "exchange")?;
+        self.deposit(to_currency, to_amount)
+    }
+
+    pub fn authorize(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.update(currency, |id, balance| {
+            if balance.available.get() < amount {
+                return Err(ClientError::FundsUpdateError {
+                    id,
+                    tx_type: "authorize".to_string(),
+                });
+            }
+
+            balance.available = balance.available.checked_sub(amount)?;
+            balance.held = balance.held.checked_add(amount)?;
+            Ok(())
+        })
+    }
+
+    // The whole authorization leaves `held`: the captured part is withdrawn and the rest is
+    // available again.
+    pub fn capture(
+        &mut self,
+        currency: Currency,
+        authorized: Amount,
+        captured: Amount,
+    ) -> Result<(), ClientError> {
+        self.update(currency, |_, balance| {
+            balance.held = balance.held.checked_sub(authorized)?;
+            balance.available = balance
+                .available
+                .checked_add(authorized.checked_sub(captured)?)?;
+            balance.total = balance.total.checked_sub(captured)?;
+            Ok(())
+        })
+    }
+
+    pub fn void(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.update(currency, |_, balance| {
+            balance.held = balance.held.checked_sub(amount)?;
+            balance.available = balance.available.checked_add(amount)?;
+            Ok(())
+        })
+    }
+
+    fn debit(
+        &mut self,
+        currency: Currency,
+        amount: Amount,
+        tx_type: &str,
+    ) -> Result<(), ClientError> {
+        self.update(currency, |id, balance| {
+            if balance.available.get() < amount {
+                return Err(ClientError::FundsUpdateError {
+                    id,
+                    tx_type: tx_type.to_string(),
+                });
+            }
+
+            balance.available = balance.available.checked_sub(amount)?;
+            balance.total = balance.total.checked_sub(amount)?;
+            Ok(())
+        })
+    }
+
+    pub fn dispute(
+        &mut self,
+        currency: Currency,
+        amount: Amount,
+        policy: &DisputePolicy,
+    ) -> Result<(), ClientError> {
+        self.update(currency, |id, balance| {
+            if !policy.allow_negative_available && balance.available.get() < amount {
+                return Err(ClientError::FundsUpdateError {
+                    id,
+                    tx_type: "dispute".to_string(),
+                });
+            }
+
+            balance.available = balance.available.checked_sub(amount)?;
+            balance.held = balance.held.checked_add(amount)?;
+            Ok(())
+        })
+    }
+
+    pub fn resolve(&mut self, currency: Currency, amount: Amount) -> Result<(), ClientError> {
+        self.update(currency, |id, balance| {
+            if balance.held.get() < amount {
+                return Err(ClientError::FundsUpdateError {
+                    id,
+                    tx_type: "resolve".to_string(),
+                });
+            }
+
+            balance.held = balance.held.checked_sub(amount)?;
+            balance.available = balance.available.checked_add(amount)?;
+            Ok(())
+        })
+    }
+
+    pub fn chargeback(
+        &mut self,
+        currency: Currency,
+        amount: Amount,
+        policy: &DisputePolicy,
+    ) -> Result<(), ClientError> {
+        self.update(currency, |_, balance| {
+            balance.held = balance.held.checked_sub(amount)?;
+            balance.total = balance.total.checked_sub(amount)?;
+            Ok(())
+        })?;
+        if policy.chargeback_locks_account {
+            self.status = ClientStatus::Locked;
         }
+        Ok(())
+    }
+
+    pub fn dispute_withdrawal(
+        &mut self,
+        currency: Currency,
+        amount: Amount,
+    ) -> Result<(), ClientError> {
+        self.update(currency, |_, balance| {
+            balance.held = balance.held.checked_add(amount)?;
+            balance.total = balance.total.checked_add(amount)?;
+            Ok(())
+        })
+    }
 
-        self.held.add(-amount);
-        self.available.add(amount);
+    pub fn resolve_withdrawal(
+        &mut self,
+        currency: Currency,
+        amount: Amount,
+    ) -> Result<(), ClientError> {
+        self.update(currency, |id, balance| {
+            if balance.held.get() < amount {
+                return Err(ClientError::FundsUpdateError {
+                    id,
+                    tx_type: "resolve".to_string(),
+                });
+            }
+
+            balance.held = balance.held.checked_sub(amount)?;
+            balance.total = balance.total.checked_sub(amount)?;
+            Ok(())
+        })
+    }
+
+    pub fn chargeback_withdrawal(
+        &mut self,
+        currency: Currency,
+        amount: Amount,
+        policy: &DisputePolicy,
+    ) -> Result<(), ClientError> {
+        self.update(currency, |_, balance| {
+            balance.held = balance.held.checked_sub(amount)?;
+            balance.available = balance.available.checked_add(amount)?;
+            Ok(())
+        })?;
+        if policy.chargeback_locks_account {
+            self.status = ClientStatus::Locked;
+        }
         Ok(())
     }
 
-    pub fn chargeback(&mut self, amount: f64) -> Result<(), ClientError> {
-        self.held.add(-amount);
-        self.total.add(-amount);
-        self.status = ClientStatus::Locked;
+    // Works on a copy of the currency's balance, kept only if every step succeeds.
+    fn update(
+        &mut self,
+        currency: Currency,
+        f: impl FnOnce(ClientId, &mut Balance) -> Result<(), ClientError>,
+    ) -> Result<(), ClientError> {
+        let mut balance = self.balance(currency);
+        f(self.id, &mut balance)?;
+        self.balances.insert(currency, balance);
         Ok(())
     }
 }
@@ -84,6 +305,12 @@ impl Client {
 mod client_tests {
     use super::*;
 
+    const EUR: Currency = Currency::EUR;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
     fn setup_client() -> Client {
         Client::new(ClientId::try_from("1".to_string()).unwrap())
     }
@@ -92,9 +319,9 @@ mod client_tests {
     fn test_new_client() {
         let client = setup_client();
 
-        assert_eq!(client.available.get(), 0.0);
-        assert_eq!(client.held.get(), 0.0);
-        assert_eq!(client.total.get(), 0.0);
+        assert_eq!(client.balance(EUR).available.get(), Amount::ZERO);
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
+        assert_eq!(client.balance(EUR).total.get(), Amount::ZERO);
         assert_eq!(client.status, ClientStatus::Active);
     }
 
@@ -102,104 +329,220 @@ mod client_tests {
     fn test_deposit() {
         let mut client = setup_client();
 
-        let result = client.deposit(100.0);
+        let result = client.deposit(EUR, amount("100"));
         assert!(result.is_ok());
-        assert_eq!(client.available.get(), 100.0);
-        assert_eq!(client.total.get(), 100.0);
-        assert_eq!(client.held.get(), 0.0);
+        assert_eq!(client.balance(EUR).available.get(), amount("100"));
+        assert_eq!(client.balance(EUR).total.get(), amount("100"));
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
     }
 
     #[test]
     fn test_withdraw_success() {
         let mut client = setup_client();
 
-        client.deposit(100.0).unwrap();
-        let result = client.withdraw(50.0);
+        client.deposit(EUR, amount("100")).unwrap();
+        let result = client.withdraw(EUR, amount("50"));
 
         assert!(result.is_ok());
-        assert_eq!(client.available.get(), 50.0);
-        assert_eq!(client.total.get(), 50.0);
-        assert_eq!(client.held.get(), 0.0);
+        assert_eq!(client.balance(EUR).available.get(), amount("50"));
+        assert_eq!(client.balance(EUR).total.get(), amount("50"));
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_transfer_out_insufficient_funds() {
+        let mut client = setup_client();
+
+        client.deposit(EUR, amount("50")).unwrap();
+        let result = client.transfer_out(EUR, amount("100"));
+
+        assert!(matches!(
+            result,
+            Err(ClientError::FundsUpdateError { ref tx_type, .. }) if tx_type == "transfer"
+        ));
+        assert_eq!(client.balance(EUR).available.get(), amount("50"));
+    }
+
+    #[test]
+    fn test_ensure_not_locked() {
+        let mut client = setup_client();
+        assert!(client.ensure_not_locked().is_ok());
+
+        client.status = ClientStatus::Locked;
+
+        assert!(matches!(
+            client.ensure_not_locked(),
+            Err(ClientError::Locked { .. })
+        ));
+    }
+
+    #[test]
+    fn test_locked_account_cannot_debit() {
+        let mut client = setup_client();
+        client.status = ClientStatus::Locked;
+
+        assert!(client.ensure_open().is_ok());
+        assert!(matches!(
+            client.ensure_can_debit(),
+            Err(ClientError::Locked { .. })
+        ));
+    }
+
+    #[test]
+    fn test_frozen_and_closed_accounts() {
+        let mut client = setup_client();
+        client.change_status(ClientStatus::Frozen).unwrap();
+
+        assert!(client.ensure_open().is_ok());
+        assert!(matches!(
+            client.ensure_can_debit(),
+            Err(ClientError::Frozen { .. })
+        ));
+
+        client.change_status(ClientStatus::Closed).unwrap();
+
+        assert!(matches!(
+            client.ensure_open(),
+            Err(ClientError::Closed { .. })
+        ));
+        assert!(matches!(
+            client.ensure_can_debit(),
+            Err(ClientError::Closed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_status_changes() {
+        use ClientStatus::*;
+        for (from, to, allowed) in [
+            (Locked, Active, true),
+            (Frozen, Active, true),
+            (Active, Frozen, true),
+            (Locked, Closed, true),
+            (Active, Active, false),
+            (Locked, Frozen, false),
+            (Closed, Active, false),
+            (Closed, Closed, false),
+        ] {
+            let mut client = setup_client();
+            client.status = from;
+
+            let result = client.change_status(to);
+
+            assert_eq!(result.is_ok(), allowed, "{from} -> {to}");
+            assert_eq!(client.status, if allowed { to } else { from });
+        }
+    }
+
+    #[test]
+    fn test_authorize_capture_and_void() {
+        let mut client = setup_client();
+        client.deposit(EUR, amount("10")).unwrap();
+
+        client.authorize(EUR, amount("4")).unwrap();
+        assert_eq!(client.balance(EUR).available.get(), amount("6"));
+        assert_eq!(client.balance(EUR).held.get(), amount("4"));
+        assert!(matches!(
+            client.authorize(EUR, amount("7")),
+            Err(ClientError::FundsUpdateError { ref tx_type, .. }) if tx_type == "authorize"
+        ));
+
+        client.capture(EUR, amount("4"), amount("2.5")).unwrap();
+        let balance = client.balance(EUR);
+        assert_eq!(balance.available.get(), amount("7.5"));
+        assert_eq!(balance.held.get(), Amount::ZERO);
+        assert_eq!(balance.total.get(), amount("7.5"));
+
+        client.authorize(EUR, amount("1")).unwrap();
+        client.void(EUR, amount("1")).unwrap();
+        assert_eq!(client.balance(EUR).available.get(), amount("7.5"));
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
     }
 
     #[test]
     fn test_withdraw_insufficient_funds() {
         let mut client = setup_client();
 
-        client.deposit(50.0).unwrap();
-        let result = client.withdraw(100.0);
+        client.deposit(EUR, amount("50")).unwrap();
+        let result = client.withdraw(EUR, amount("100"));
 
         assert!(matches!(result, Err(ClientError::FundsUpdateError { .. })));
-        assert_eq!(client.available.get(), 50.0);
-        assert_eq!(client.total.get(), 50.0);
+        assert_eq!(client.balance(EUR).available.get(), amount("50"));
+        assert_eq!(client.balance(EUR).total.get(), amount("50"));
     }
 
     #[test]
     fn test_dispute_success() {
         let mut client = setup_client();
 
-        client.deposit(100.0).unwrap();
-        let result = client.dispute(30.0);
+        client.deposit(EUR, amount("100")).unwrap();
+        let result = client.dispute(EUR, amount("30"), &DisputePolicy::default());
 
         assert!(result.is_ok());
-        assert_eq!(client.available.get(), 70.0);
-        assert_eq!(client.held.get(), 30.0);
-        assert_eq!(client.total.get(), 100.0);
+        assert_eq!(client.balance(EUR).available.get(), amount("70"));
+        assert_eq!(client.balance(EUR).held.get(), amount("30"));
+        assert_eq!(client.balance(EUR).total.get(), amount("100"));
     }
 
     #[test]
     fn test_dispute_insufficient_available() {
         let mut client = setup_client();
 
-        client.deposit(20.0).unwrap();
-        let result = client.dispute(30.0);
+        client.deposit(EUR, amount("20")).unwrap();
+        let result = client.dispute(EUR, amount("30"), &DisputePolicy::default());
 
         assert!(matches!(result, Err(ClientError::FundsUpdateError { .. })));
-        assert_eq!(client.available.get(), 20.0);
-        assert_eq!(client.held.get(), 0.0);
-        assert_eq!(client.total.get(), 20.0);
+        assert_eq!(client.balance(EUR).available.get(), amount("20"));
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
+        assert_eq!(client.balance(EUR).total.get(), amount("20"));
     }
 
     #[test]
     fn test_resolve_success() {
         let mut client = setup_client();
 
-        client.deposit(100.0).unwrap();
-        client.dispute(30.0).unwrap();
-        let result = client.resolve(20.0);
+        client.deposit(EUR, amount("100")).unwrap();
+        client
+            .dispute(EUR, amount("30"), &DisputePolicy::default())
+            .unwrap();
+        let result = client.resolve(EUR, amount("20"));
 
         assert!(result.is_ok());
-        assert_eq!(client.available.get(), 90.0);
-        assert_eq!(client.held.get(), 10.0);
-        assert_eq!(client.total.get(), 100.0);
+        assert_eq!(client.balance(EUR).available.get(), amount("90"));
+        assert_eq!(client.balance(EUR).held.get(), amount("10"));
+        assert_eq!(client.balance(EUR).total.get(), amount("100"));
     }
 
     #[test]
     fn test_resolve_insufficient_held() {
         let mut client = setup_client();
 
-        client.deposit(100.0).unwrap();
-        client.dispute(20.0).unwrap();
-        let result = client.resolve(30.0);
+        client.deposit(EUR, amount("100")).unwrap();
+        client
+            .dispute(EUR, amount("20"), &DisputePolicy::default())
+            .unwrap();
+        let result = client.resolve(EUR, amount("30"));
 
         assert!(matches!(result, Err(ClientError::FundsUpdateError { .. })));
-        assert_eq!(client.available.get(), 80.0);
-        assert_eq!(client.held.get(), 20.0);
-        assert_eq!(client.total.get(), 100.0);
+        assert_eq!(client.balance(EUR).available.get(), amount("80"));
+        assert_eq!(client.balance(EUR).held.get(), amount("20"));
+        assert_eq!(client.balance(EUR).total.get(), amount("100"));
     }
 
     #[test]
     fn test_chargeback() {
         let mut client = setup_client();
 
-        client.deposit(100.0).unwrap();
-        client.dispute(30.0).unwrap();
-        let result = client.chargeback(30.0);
+        client.deposit(EUR, amount("100")).unwrap();
+        client
+            .dispute(EUR, amount("30"), &DisputePolicy::default())
+            .unwrap();
+        let result = client.chargeback(EUR, amount("30"), &DisputePolicy::default());
 
         assert!(result.is_ok());
-        assert_eq!(client.available.get(), 70.0);
-        assert_eq!(client.held.get(), 0.0);
-        assert_eq!(client.total.get(), 70.0);
+        assert_eq!(client.balance(EUR).available.get(), amount("70"));
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
+        assert_eq!(client.balance(EUR).total.get(), amount("70"));
         assert_eq!(client.status, ClientStatus::Locked);
         assert!(client.is_locked());
     }
@@ -210,10 +553,113 @@ mod client_tests {
 
         assert!(!client.is_locked());
 
-        client.deposit(100.0).unwrap();
-        client.dispute(30.0).unwrap();
-        client.chargeback(30.0).unwrap();
+        client.deposit(EUR, amount("100")).unwrap();
+        client
+            .dispute(EUR, amount("30"), &DisputePolicy::default())
+            .unwrap();
+        client
+            .chargeback(EUR, amount("30"), &DisputePolicy::default())
+            .unwrap();
 
         assert!(client.is_locked());
     }
+
+    #[test]
+    fn test_dispute_with_negative_available_allowed() {
+        let mut client = setup_client();
+        let policy = DisputePolicy {
+            allow_negative_available: true,
+            ..DisputePolicy::default()
+        };
+
+        client.deposit(EUR, amount("100")).unwrap();
+        client.withdraw(EUR, amount("80")).unwrap();
+        let result = client.dispute(EUR, amount("100"), &policy);
+
+        assert!(result.is_ok());
+        assert_eq!(client.balance(EUR).available.get(), amount("-80"));
+        assert_eq!(client.balance(EUR).held.get(), amount("100"));
+        assert_eq!(client.balance(EUR).total.get(), amount("20"));
+    }
+
+    #[test]
+    fn test_chargeback_without_lock() {
+        let mut client = setup_client();
+        let policy = DisputePolicy {
+            chargeback_locks_account: false,
+            ..DisputePolicy::default()
+        };
+
+        client.deposit(EUR, amount("100")).unwrap();
+        client.dispute(EUR, amount("30"), &policy).unwrap();
+        client.chargeback(EUR, amount("30"), &policy).unwrap();
+
+        assert_eq!(client.balance(EUR).total.get(), amount("70"));
+        assert!(!client.is_locked());
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_resolved() {
+        let mut client = setup_client();
+
+        client.deposit(EUR, amount("100")).unwrap();
+        client.withdraw(EUR, amount("40")).unwrap();
+        client.dispute_withdrawal(EUR, amount("40")).unwrap();
+
+        assert_eq!(client.balance(EUR).available.get(), amount("60"));
+        assert_eq!(client.balance(EUR).held.get(), amount("40"));
+        assert_eq!(client.balance(EUR).total.get(), amount("100"));
+
+        client.resolve_withdrawal(EUR, amount("40")).unwrap();
+
+        assert_eq!(client.balance(EUR).available.get(), amount("60"));
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
+        assert_eq!(client.balance(EUR).total.get(), amount("60"));
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_charged_back() {
+        let mut client = setup_client();
+
+        client.deposit(EUR, amount("100")).unwrap();
+        client.withdraw(EUR, amount("40")).unwrap();
+        client.dispute_withdrawal(EUR, amount("40")).unwrap();
+        client
+            .chargeback_withdrawal(EUR, amount("40"), &DisputePolicy::default())
+            .unwrap();
+
+        assert_eq!(client.balance(EUR).available.get(), amount("100"));
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
+        assert_eq!(client.balance(EUR).total.get(), amount("100"));
+        assert!(client.is_locked());
+    }
+
+    #[test]
+    fn test_currencies_have_separate_balances() {
+        let mut client = setup_client();
+        let usd: Currency = "USD".parse().unwrap();
+
+        client.deposit(EUR, amount("100")).unwrap();
+        client.deposit(usd, amount("10")).unwrap();
+        let result = client.withdraw(usd, amount("50"));
+
+        assert!(matches!(result, Err(ClientError::FundsUpdateError { .. })));
+        assert_eq!(client.balance(usd).available.get(), amount("10"));
+        client
+            .dispute(usd, amount("10"), &DisputePolicy::default())
+            .unwrap();
+        assert_eq!(client.balance(usd).held.get(), amount("10"));
+        assert_eq!(client.balance(EUR).held.get(), Amount::ZERO);
+        assert_eq!(client.balance(EUR).available.get(), amount("100"));
+        assert_eq!(client.balances.len(), 2);
+    }
+
+    #[test]
+    fn test_failed_operation_adds_no_balance() {
+        let mut client = setup_client();
+
+        assert!(client.withdraw(EUR, amount("1")).is_err());
+
+        assert!(client.balances.is_empty());
+    }
 }
@@ -1,17 +1,33 @@
 use fmt::Display;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize, Serializer};
 use std::fmt;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Hash)]
 #[serde(try_from = "String")]
 pub struct ClientId(u16);
 
+impl ClientId {
+    pub fn new(id: u16) -> Self {
+        Self(id)
+    }
+
+    pub fn get(&self) -> u16 {
+        self.0
+    }
+}
+
 impl Display for ClientId {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
     }
 }
 
+impl Serialize for ClientId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl TryFrom<String> for ClientId {
     type Error = anyhow::Error;
 
@@ -1,5 +1,46 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
 pub enum ClientStatus {
     Active,
+    // Set by a chargeback.
     Locked,
+    // Set by an operator: deposits still come in, nothing goes out.
+    Frozen,
+    // Set by an operator: nothing is allowed anymore.
+    Closed,
+}
+
+impl fmt::Display for ClientStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Active => "active",
+            Self::Locked => "locked",
+            Self::Frozen => "frozen",
+            Self::Closed => "closed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Serialize for ClientStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl TryFrom<String> for ClientStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Ok(match s.as_str() {
+            "active" => Self::Active,
+            "locked" => Self::Locked,
+            "frozen" => Self::Frozen,
+            "closed" => Self::Closed,
+            _ => return Err(anyhow::anyhow!("Invalid client status: {}", s)),
+        })
+    }
 }
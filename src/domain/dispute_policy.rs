@@ -0,0 +1,29 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalDisputes {
+    #[default]
+    Deny,
+    Reverse,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisputePolicy {
+    pub allow_redispute_after_resolve: bool,
+    pub withdrawal_disputes: WithdrawalDisputes,
+    pub allow_negative_available: bool,
+    pub chargeback_locks_account: bool,
+}
+
+impl Default for DisputePolicy {
+    fn default() -> Self {
+        Self {
+            allow_redispute_after_resolve: false,
+            withdrawal_disputes: WithdrawalDisputes::Deny,
+            allow_negative_available: false,
+            chargeback_locks_account: true,
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::domain::amount::Amount;
+use crate::domain::client_id::ClientId;
+use crate::domain::client_status::ClientStatus;
+use crate::domain::currency::Currency;
+use crate::domain::rate::Rate;
+use crate::domain::tx_id::TxId;
+use crate::domain::tx_type::TxType;
+use serde::{Deserialize, Serialize};
+
+// A missing `currency` is read as EUR, the only currency of logs written before there were others.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum Event {
+    Deposited {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+        #[serde(default)]
+        currency: Currency,
+    },
+    Withdrawn {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+        #[serde(default)]
+        currency: Currency,
+    },
+    Transferred {
+        client: ClientId,
+        to: ClientId,
+        tx: TxId,
+        amount: Amount,
+        #[serde(default)]
+        currency: Currency,
+    },
+    Exchanged {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+        currency: Currency,
+        to_amount: Amount,
+        to_currency: Currency,
+        rate: Rate,
+    },
+    Authorized {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+        currency: Currency,
+    },
+    // `amount` is the captured part of the authorization.
+    Captured {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+    },
+    Voided {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+        // Released by the service because the hold expired, not by a `void` record.
+        #[serde(default)]
+        expired: bool,
+    },
+    FundsHeld {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+    },
+    FundsReleased {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+    },
+    ChargedBack {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+    },
+    Refunded {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+    },
+    // `amount` is what was left of the deposit or withdrawal.
+    Reversed {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+        reason: String,
+        operator: String,
+    },
+    // Paid by `client` into the house account `to` on the transaction `parent`. Fees have no
+    // transaction ID of their own.
+    FeeCharged {
+        client: ClientId,
+        to: ClientId,
+        parent: TxId,
+        amount: Amount,
+        currency: Currency,
+    },
+    // Always logged right after the chargeback that caused it, and the lock it set. `tx` is the
+    // transaction charged back.
+    FeeRefunded {
+        client: ClientId,
+        tx: TxId,
+        amount: Amount,
+    },
+    AccountLocked {
+        client: ClientId,
+    },
+    StatusChanged {
+        client: ClientId,
+        tx: TxId,
+        from: ClientStatus,
+        to: ClientStatus,
+        reason: String,
+        operator: String,
+    },
+    Rejected {
+        #[serde(rename = "type")]
+        tx_type: TxType,
+        client: ClientId,
+        tx: TxId,
+        reason: String,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let events = vec![
+            Event::Deposited {
+                client: ClientId::new(1),
+                tx: TxId::new(1),
+                amount: "1.5".parse().unwrap(),
+                currency: Currency::EUR,
+            },
+            Event::Transferred {
+                client: ClientId::new(1),
+                to: ClientId::new(2),
+                tx: TxId::new(2),
+                amount: "0.5".parse().unwrap(),
+                currency: "GBP".parse().unwrap(),
+            },
+            Event::Exchanged {
+                client: ClientId::new(1),
+                tx: TxId::new(3),
+                amount: "2".parse().unwrap(),
+                currency: Currency::EUR,
+                to_amount: "2.17".parse().unwrap(),
+                to_currency: "USD".parse().unwrap(),
+                rate: "1.085".parse().unwrap(),
+            },
+            Event::Authorized {
+                client: ClientId::new(1),
+                tx: TxId::new(5),
+                amount: "3".parse().unwrap(),
+                currency: Currency::EUR,
+            },
+            Event::Voided {
+                client: ClientId::new(1),
+                tx: TxId::new(5),
+                amount: "3".parse().unwrap(),
+                expired: true,
+            },
+            Event::AccountLocked {
+                client: ClientId::new(1),
+            },
+            Event::StatusChanged {
+                client: ClientId::new(1),
+                tx: TxId::new(4),
+                from: ClientStatus::Locked,
+                to: ClientStatus::Active,
+                reason: "chargeback refunded".to_string(),
+                operator: "alice".to_string(),
+            },
+            Event::Rejected {
+                tx_type: TxType::Withdrawal,
+                client: ClientId::new(2),
+                tx: TxId::new(3),
+                reason: "client with id 2 not found".to_string(),
+            },
+        ];
+
+        for event in events {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<Event>(&json).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn test_json_format() {
+        let event = Event::FundsHeld {
+            client: ClientId::new(1),
+            tx: TxId::new(2),
+            amount: "3".parse().unwrap(),
+        };
+
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"FundsHeld","client":"1","tx":"2","amount":"3.0000"}"#
+        );
+    }
+
+    #[test]
+    fn test_missing_currency_is_eur() {
+        let event: Event =
+            serde_json::from_str(r#"{"event":"Deposited","client":"1","tx":"2","amount":"3"}"#)
+                .unwrap();
+
+        assert!(matches!(
+            event,
+            Event::Deposited { currency, .. } if currency == Currency::EUR
+        ));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::domain::amount::Amount;
+use crate::domain::client_id::ClientId;
+use crate::domain::percent::Percent;
+use crate::domain::rounding::Rounding;
+use crate::domain::tx_type::TxType;
+use crate::error::amount_error::AmountError;
+use serde::Deserialize;
+
+// Fees on deposits and withdrawals, paid into `house_account`. Transactions without a fee
+// configured for their type are free.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeeSchedule {
+    pub house_account: Option<ClientId>,
+    pub deposit: Option<Fee>,
+    pub withdrawal: Option<Fee>,
+}
+
+// A flat part plus a percentage of the amount, kept between `min` and `max`. Tiers replace
+// the flat part and percentage for amounts from their `from` up to the next tier's.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Fee {
+    pub flat: Amount,
+    pub percent: Option<Percent>,
+    pub min: Option<Amount>,
+    pub max: Option<Amount>,
+    pub tiers: Vec<FeeTier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeTier {
+    pub from: Amount,
+    #[serde(default)]
+    pub flat: Amount,
+    #[serde(default)]
+    pub percent: Option<Percent>,
+}
+
+impl FeeSchedule {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.house_account.is_none() && (self.deposit.is_some() || self.withdrawal.is_some()) {
+            anyhow::bail!("Fees need a house_account to be paid into");
+        }
+        for fee in self.deposit.iter().chain(&self.withdrawal) {
+            let amounts = std::iter::once(fee.flat)
+                .chain(fee.min)
+                .chain(fee.max)
+                .chain(fee.tiers.iter().flat_map(|tier| [tier.from, tier.flat]));
+            if amounts.into_iter().any(|amount| amount.is_negative()) {
+                anyhow::bail!("Fee amounts can't be negative");
+            }
+            if fee.min.zip(fee.max).is_some_and(|(min, max)| min > max) {
+                anyhow::bail!("Fee min is above its max");
+            }
+        }
+        Ok(())
+    }
+
+    // A deposit's fee never exceeds the deposit.
+    pub fn fee(
+        &self,
+        tx_type: TxType,
+        amount: Amount,
+        rounding: Rounding,
+    ) -> Result<Amount, AmountError> {
+        match tx_type {
+            TxType::Deposit => match &self.deposit {
+                Some(fee) => Ok(fee.of(amount, rounding)?.min(amount)),
+                None => Ok(Amount::ZERO),
+            },
+            TxType::Withdrawal => match &self.withdrawal {
+                Some(fee) => fee.of(amount, rounding),
+                None => Ok(Amount::ZERO),
+            },
+            _ => Ok(Amount::ZERO),
+        }
+    }
+}
+
+impl Fee {
+    pub fn of(&self, amount: Amount, rounding: Rounding) -> Result<Amount, AmountError> {
+        let (flat, percent) = self
+            .tiers
+            .iter()
+            .filter(|tier| tier.from <= amount)
+            .max_by_key(|tier| tier.from)
+            .map_or((self.flat, self.percent), |tier| (tier.flat, tier.percent));
+        let mut fee = match percent {
+            Some(percent) => flat.checked_add(percent.of(amount, rounding)?)?,
+            None => flat,
+        };
+        if let Some(min) = self.min {
+            fee = fee.max(min);
+        }
+        if let Some(max) = self.max {
+            fee = fee.min(max);
+        }
+        Ok(fee)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    fn schedule(toml: &str) -> FeeSchedule {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_flat_and_percentage_with_caps() {
+        let fees = schedule(
+            r#"
+            house_account = "900"
+            [withdrawal]
+            flat = "0.5"
+            percent = "1.5"
+            min = "1"
+            max = "10"
+            "#,
+        );
+
+        let fee = |value: &str| {
+            fees.fee(TxType::Withdrawal, amount(value), Rounding::HalfEven)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(fee("10"), "1.0000");
+        assert_eq!(fee("100"), "2.0000");
+        assert_eq!(fee("0.0333"), "1.0000");
+        assert_eq!(fee("1000"), "10.0000");
+        assert_eq!(
+            fees.fee(TxType::Deposit, amount("100"), Rounding::HalfEven)
+                .unwrap(),
+            Amount::ZERO
+        );
+    }
+
+    #[test]
+    fn test_tiers() {
+        let fees = schedule(
+            r#"
+            house_account = "900"
+            [deposit]
+            flat = "2"
+            tiers = [
+                { from = "1000", percent = "0.1" },
+                { from = "100", flat = "1" },
+                { from = "5000", flat = "3", percent = "0" },
+            ]
+            "#,
+        );
+
+        let fee = |value: &str| {
+            fees.fee(TxType::Deposit, amount(value), Rounding::HalfEven)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(fee("50"), "2.0000");
+        assert_eq!(fee("1.5"), "1.5000");
+        assert_eq!(fee("100"), "1.0000");
+        assert_eq!(fee("2000.5"), "2.0005");
+        assert_eq!(fee("6000"), "3.0000");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(FeeSchedule::default().validate().is_ok());
+        assert!(schedule("[deposit]\nflat = \"1\"").validate().is_err());
+        assert!(
+            schedule("house_account = \"1\"\n[deposit]\nmin = \"2\"\nmax = \"1\"")
+                .validate()
+                .is_err()
+        );
+        assert!(
+            schedule("house_account = \"1\"\n[withdrawal]\nflat = \"-1\"")
+                .validate()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,11 @@
+use serde::Deserialize;
+
+// When authorizations that were neither captured nor voided are released. Either limit can
+// be left out; without both, holds never expire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HoldExpiry {
+    // Number of records processed after the authorization.
+    pub after_records: Option<u64>,
+    pub after_seconds: Option<u64>,
+}
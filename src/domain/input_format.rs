@@ -0,0 +1,261 @@
+use crate::domain::input_record::{CsvRecord, CsvRecords, InputRecord};
+use crate::error::input_error::InputError;
+use csv::StringRecord;
+use serde_json::{Map, Value};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    Csv,
+    Jsonl,
+}
+
+impl InputFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "jsonl" | "ndjson" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+
+    pub fn records<R: Read + Send + 'static>(
+        self,
+        reader: R,
+    ) -> Result<Box<dyn RecordReader + Send>, InputError> {
+        match self {
+            Self::Csv => Ok(Box::new(InputRecord::records_from_csv(reader)?)),
+            Self::Jsonl => Ok(Box::new(JsonlRecords::new(reader))),
+        }
+    }
+
+    pub fn records_at<R: Read + Seek + Send + 'static>(
+        self,
+        reader: R,
+        position: csv::Position,
+    ) -> Result<Box<dyn RecordReader + Send>, InputError> {
+        match self {
+            Self::Csv => Ok(Box::new(InputRecord::records_from_csv_at(
+                reader, position,
+            )?)),
+            Self::Jsonl => Ok(Box::new(JsonlRecords::at(reader, position)?)),
+        }
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(anyhow::anyhow!(
+                "Invalid input format '{}', expected 'csv' or 'jsonl'",
+                s
+            )),
+        }
+    }
+}
+
+// Rows of every format come out as CSV fields in `headers` order, so they go through the
+// same validation and rejected rows can be written to the same rejects file.
+pub trait RecordReader: Iterator<Item = Result<CsvRecord, InputError>> {
+    fn headers(&self) -> &StringRecord;
+
+    // Position right after the last record returned, usable with `InputFormat::records_at`.
+    fn next_position(&self) -> &csv::Position;
+}
+
+impl<T: RecordReader + ?Sized> RecordReader for Box<T> {
+    fn headers(&self) -> &StringRecord {
+        (**self).headers()
+    }
+
+    fn next_position(&self) -> &csv::Position {
+        (**self).next_position()
+    }
+}
+
+impl<R: Read> RecordReader for CsvRecords<R> {
+    fn headers(&self) -> &StringRecord {
+        CsvRecords::headers(self)
+    }
+
+    fn next_position(&self) -> &csv::Position {
+        self.position()
+    }
+}
+
+// One JSON object per line with the same keys as the CSV columns. IDs and amounts can be
+// given as strings or numbers, a missing or null `amount` is an empty one.
+pub struct JsonlRecords<R> {
+    reader: BufReader<R>,
+    headers: StringRecord,
+    position: csv::Position,
+}
+
+impl<R: Read> JsonlRecords<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_position(reader, csv::Position::new())
+    }
+
+    fn with_position(reader: R, position: csv::Position) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            headers: StringRecord::from(InputRecord::COLUMNS.to_vec()),
+            position,
+        }
+    }
+}
+
+impl<R: Read + Seek> JsonlRecords<R> {
+    pub fn at(mut reader: R, position: csv::Position) -> Result<Self, InputError> {
+        reader.seek(SeekFrom::Start(position.byte()))?;
+        Ok(Self::with_position(reader, position))
+    }
+}
+
+impl<R: Read> Iterator for JsonlRecords<R> {
+    type Item = Result<CsvRecord, InputError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut line = Vec::new();
+            let read = match self.reader.read_until(b'\n', &mut line) {
+                Ok(0) => return None,
+                Ok(read) => read as u64,
+                Err(err) => return Some(Err(err.into())),
+            };
+
+            let number = self.position.line();
+            let byte = self.position.byte();
+            self.position.set_byte(byte + read).set_line(number + 1);
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let record = self.position.record();
+            self.position.set_record(record + 1);
+
+            let object: Map<String, Value> = match serde_json::from_slice(&line) {
+                Ok(object) => object,
+                Err(source) => {
+                    return Some(Err(InputError::Json {
+                        line: number,
+                        source,
+                    }));
+                }
+            };
+            let fields: StringRecord = InputRecord::COLUMNS
+                .iter()
+                .map(|column| field(object.get(*column)))
+                .collect();
+
+            return Some(Ok(CsvRecord {
+                line: number,
+                record: fields.deserialize(Some(&self.headers)),
+                fields,
+            }));
+        }
+    }
+}
+
+impl<R: Read> RecordReader for JsonlRecords<R> {
+    fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    fn next_position(&self) -> &csv::Position {
+        &self.position
+    }
+}
+
+fn field(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.trim().to_string(),
+        Some(value) => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::tx_type::TxType;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            InputFormat::from_path(Path::new("in.JSONL")),
+            Some(InputFormat::Jsonl)
+        );
+        assert_eq!(
+            InputFormat::from_path(Path::new("in.csv")),
+            Some(InputFormat::Csv)
+        );
+        assert_eq!(InputFormat::from_path(Path::new("-")), None);
+    }
+
+    #[test]
+    fn test_jsonl_records_are_validated_like_csv() {
+        let data = r#"{"type":"deposit","client":1,"tx":"1","amount":1.5}
+
+{"type":"dispute","client":"1","tx":1,"amount":null}
+{"type":"deposit","client":70000,"tx":2,"amount":"1"}
+{"type":"deposit","client":1,"tx":3,"amount":"1.00001"}
+not json
+"#;
+        let mut records = InputFormat::Jsonl
+            .records(Cursor::new(data.as_bytes().to_vec()))
+            .unwrap();
+
+        let first = records.next().unwrap().unwrap();
+        assert_eq!(first.line, 1);
+        assert_eq!(
+            first.fields,
+            vec!["deposit", "1", "1", "1.5", "", "", "", "", ""]
+        );
+        assert_eq!(first.record.unwrap().amount, Some("1.5".parse().unwrap()));
+
+        let dispute = records.next().unwrap().unwrap();
+        assert_eq!(dispute.line, 3);
+        let dispute = dispute.record.unwrap();
+        assert_eq!(dispute.tx_type, TxType::Dispute);
+        assert_eq!(dispute.amount, None);
+
+        assert!(records.next().unwrap().unwrap().record.is_err());
+        assert!(records.next().unwrap().unwrap().record.is_err());
+
+        let err = records.next().unwrap().unwrap_err();
+        assert_eq!(err.line(), 6);
+        assert_eq!(err.code(), "invalid_json");
+        assert!(records.next().is_none());
+    }
+
+    #[test]
+    fn test_jsonl_records_resume_from_position() {
+        let data = "\
+{\"type\":\"deposit\",\"client\":1,\"tx\":1,\"amount\":1}
+{\"type\":\"deposit\",\"client\":1,\"tx\":2,\"amount\":2}
+{\"type\":\"withdrawal\",\"client\":1,\"tx\":3,\"amount\":1}
+";
+        let mut records = InputFormat::Jsonl
+            .records(Cursor::new(data.as_bytes().to_vec()))
+            .unwrap();
+        records.next().unwrap().unwrap();
+        records.next().unwrap().unwrap();
+        let position = records.next_position().clone();
+
+        let mut resumed = InputFormat::Jsonl
+            .records_at(Cursor::new(data.as_bytes().to_vec()), position)
+            .unwrap();
+
+        let row = resumed.next().unwrap().unwrap();
+        assert_eq!(row.line, 3);
+        assert_eq!(row.record.unwrap().tx_type, TxType::Withdrawal);
+        assert!(resumed.next().is_none());
+    }
+}
@@ -1,9 +1,12 @@
+use crate::domain::amount::Amount;
 use crate::domain::client_id::ClientId;
+use crate::domain::currency::Currency;
 use crate::domain::tx_id::TxId;
 use crate::domain::tx_type::TxType;
-use csv::ReaderBuilder;
+use crate::error::input_error::InputError;
+use csv::{ReaderBuilder, StringRecord};
 use serde::Deserialize;
-use std::io::Read;
+use std::io::{Read, Seek};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct InputRecord {
@@ -11,18 +14,132 @@ pub struct InputRecord {
     pub tx_type: TxType,
     pub client: ClientId,
     pub tx: TxId,
-    pub amount: Option<f64>,
+    pub amount: Option<Amount>,
+    // Destination client of a transfer; the column can be left out of inputs without transfers.
+    #[serde(default)]
+    pub to: Option<ClientId>,
+    // Left out or empty for the default currency.
+    #[serde(default)]
+    pub currency: Option<Currency>,
+    // Currency an exchange converts `amount` into.
+    #[serde(default)]
+    pub to_currency: Option<Currency>,
+    // Why an operator unlocked, froze or closed the account or reversed a transaction, and
+    // who did it.
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub operator: Option<String>,
 }
 
 impl InputRecord {
+    pub const COLUMNS: [&'static str; 9] = [
+        "type",
+        "client",
+        "tx",
+        "amount",
+        "to",
+        "currency",
+        "to_currency",
+        "reason",
+        "operator",
+    ];
+    const REQUIRED_COLUMNS: usize = 4;
+
+    // The optional columns after `amount` can be left out from the end.
+    pub fn is_header(fields: &StringRecord) -> bool {
+        fields.len() >= Self::REQUIRED_COLUMNS
+            && fields.len() <= Self::COLUMNS.len()
+            && fields == Self::COLUMNS[..fields.len()]
+    }
+
     pub fn from_csv<R: Read>(reader: R) -> csv::DeserializeRecordsIntoIter<R, InputRecord> {
-        let rdr = ReaderBuilder::new()
-            .flexible(true)
-            .has_headers(true)
-            .trim(csv::Trim::All)
-            .from_reader(reader);
+        Self::csv_reader(reader).into_deserialize()
+    }
+
+    pub fn records_from_csv<R: Read>(reader: R) -> csv::Result<CsvRecords<R>> {
+        CsvRecords::new(Self::csv_reader(reader))
+    }
+
+    // The headers are read before seeking, so `position` must point at the start of a record.
+    pub fn records_from_csv_at<R: Read + Seek>(
+        reader: R,
+        position: csv::Position,
+    ) -> csv::Result<CsvRecords<R>> {
+        let mut records = CsvRecords::new(Self::csv_reader(reader))?;
+        records.reader.seek(position)?;
+
+        Ok(records)
+    }
+
+    // Parses a single row without a header line, with the fields in `COLUMNS` order.
+    pub fn from_line(line: &str) -> csv::Result<CsvRecord> {
+        let mut reader = Self::reader_builder()
+            .has_headers(false)
+            .from_reader(line.as_bytes());
+        let mut fields = StringRecord::new();
+        reader.read_record(&mut fields)?;
+
+        Ok(CsvRecord {
+            line: 1,
+            record: fields.deserialize(Some(&StringRecord::from(Self::COLUMNS.to_vec()))),
+            fields,
+        })
+    }
 
-        rdr.into_deserialize()
+    fn csv_reader<R: Read>(reader: R) -> csv::Reader<R> {
+        Self::reader_builder().has_headers(true).from_reader(reader)
+    }
+
+    fn reader_builder() -> ReaderBuilder {
+        let mut builder = ReaderBuilder::new();
+        builder.flexible(true).trim(csv::Trim::All);
+        builder
+    }
+}
+
+// A CSV row kept together with its parsed form, so rejected rows can be written back as read.
+#[derive(Debug)]
+pub struct CsvRecord {
+    pub line: u64,
+    pub fields: StringRecord,
+    pub record: csv::Result<InputRecord>,
+}
+
+pub struct CsvRecords<R> {
+    reader: csv::Reader<R>,
+    headers: StringRecord,
+}
+
+impl<R: Read> CsvRecords<R> {
+    fn new(mut reader: csv::Reader<R>) -> csv::Result<Self> {
+        let headers = reader.headers()?.clone();
+        Ok(Self { reader, headers })
+    }
+
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+
+    pub fn position(&self) -> &csv::Position {
+        self.reader.position()
+    }
+}
+
+impl<R: Read> Iterator for CsvRecords<R> {
+    type Item = Result<CsvRecord, InputError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut fields = StringRecord::new();
+        match self.reader.read_record(&mut fields) {
+            Ok(false) => None,
+            Ok(true) => Some(Ok(CsvRecord {
+                line: fields.position().map_or(0, |position| position.line()),
+                record: fields.deserialize(Some(&self.headers)),
+                fields,
+            })),
+            Err(err) => Some(Err(err.into())),
+        }
     }
 }
 
@@ -46,11 +163,49 @@ deposit,1,1,1.0
         assert_eq!(record.tx_type, TxType::Deposit);
         assert_eq!(record.client.to_string(), "1");
         assert_eq!(record.tx.to_string(), "1");
-        assert_eq!(record.amount, Some(1.0));
+        assert_eq!(record.amount, Some("1.0".parse().unwrap()));
+        assert_eq!(record.to, None);
 
         assert!(records.next().is_none());
     }
 
+    #[test]
+    fn test_read_transfer() {
+        let csv_data = "\
+type,client,tx,amount,to
+transfer,1,2,1.5,3
+deposit,1,3,1.0,
+";
+        let mut records = InputRecord::from_csv(Cursor::new(csv_data));
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.tx_type, TxType::Transfer);
+        assert_eq!(record.to, Some(ClientId::new(3)));
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.to, None);
+    }
+
+    #[test]
+    fn test_read_admin_record() {
+        let csv_data = "\
+type,client,tx,amount,to,currency,to_currency,reason,operator
+freeze,1,9,,,,,\"fraud review, case 12\",alice
+unlock,1,10,,,,,,
+";
+        let mut records = InputRecord::from_csv(Cursor::new(csv_data));
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.tx_type, TxType::Freeze);
+        assert_eq!(record.amount, None);
+        assert_eq!(record.reason.as_deref(), Some("fraud review, case 12"));
+        assert_eq!(record.operator.as_deref(), Some("alice"));
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.reason, None);
+        assert_eq!(record.operator, None);
+    }
+
     #[test]
     fn test_decimals() {
         let csv_data = "\
@@ -65,11 +220,40 @@ deposit,1,1,1.1021
         assert_eq!(record.tx_type, TxType::Deposit);
         assert_eq!(record.client.to_string(), "1");
         assert_eq!(record.tx.to_string(), "1");
-        assert_eq!(record.amount, Some(1.1021));
+        assert_eq!(record.amount, Some("1.1021".parse().unwrap()));
 
         assert!(records.next().is_none());
     }
 
+    #[test]
+    fn test_fail_on_too_many_decimals() {
+        let csv_data = "\
+type,client,tx,amount
+deposit,1,1,1.10215
+";
+        let cursor = Cursor::new(csv_data);
+
+        let mut records = InputRecord::from_csv(cursor);
+
+        let result = records.next().unwrap();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_missing_amount() {
+        let csv_data = "\
+type,client,tx,amount
+dispute,1,1,
+";
+        let cursor = Cursor::new(csv_data);
+
+        let mut records = InputRecord::from_csv(cursor);
+
+        let record = records.next().unwrap().unwrap();
+        assert_eq!(record.tx_type, TxType::Dispute);
+        assert_eq!(record.amount, None);
+    }
+
     #[test]
     fn test_fail_on_wrong_type() {
         let csv_data = "\
@@ -97,6 +281,87 @@ deposit,  1 ,   1    ,    1.0
         assert_eq!(record.tx_type, TxType::Deposit);
         assert_eq!(record.client.to_string(), "1");
         assert_eq!(record.tx.to_string(), "1");
-        assert_eq!(record.amount, Some(1.0));
+        assert_eq!(record.amount, Some("1.0".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_read_csv_from_position() {
+        let csv_data = "\
+type,client,tx,amount
+deposit,1,1,1.0
+deposit,1,2,2.0
+withdrawal,1,3,0.5
+";
+        let mut records = InputRecord::records_from_csv(Cursor::new(csv_data)).unwrap();
+        records.next().unwrap().unwrap();
+        records.next().unwrap().unwrap();
+        let position = records.position().clone();
+
+        let mut resumed =
+            InputRecord::records_from_csv_at(Cursor::new(csv_data), position).unwrap();
+
+        let row = resumed.next().unwrap().unwrap();
+        assert_eq!(row.line, 4);
+        let record = row.record.unwrap();
+        assert_eq!(record.tx_type, TxType::Withdrawal);
+        assert_eq!(record.tx.to_string(), "3");
+        assert!(resumed.next().is_none());
+    }
+
+    #[test]
+    fn test_csv_records_keep_rejected_fields() {
+        let csv_data = "\
+type,client,tx,amount
+deposit, 1,1,1.00001
+";
+        let mut records = InputRecord::records_from_csv(Cursor::new(csv_data)).unwrap();
+
+        let row = records.next().unwrap().unwrap();
+
+        assert_eq!(row.line, 2);
+        assert_eq!(row.fields, vec!["deposit", "1", "1", "1.00001"]);
+        assert!(row.record.is_err());
+    }
+
+    #[test]
+    fn test_record_from_line() {
+        let row = InputRecord::from_line(" withdrawal, 2 ,5, 1.5").unwrap();
+
+        assert_eq!(row.fields, vec!["withdrawal", "2", "5", "1.5"]);
+        let record = row.record.unwrap();
+        assert_eq!(record.tx_type, TxType::Withdrawal);
+        assert_eq!(record.client.to_string(), "2");
+        assert_eq!(record.amount, Some("1.5".parse().unwrap()));
+
+        let transfer = InputRecord::from_line("transfer,1,6,2,3")
+            .unwrap()
+            .record
+            .unwrap();
+        assert_eq!(transfer.tx_type, TxType::Transfer);
+        assert_eq!(transfer.to, Some(ClientId::new(3)));
+
+        let deposit = InputRecord::from_line("deposit,1,7,2,,usd")
+            .unwrap()
+            .record
+            .unwrap();
+        assert_eq!(deposit.currency, Some("USD".parse().unwrap()));
+        let exchange = InputRecord::from_line("exchange,1,8,2,,EUR,GBP")
+            .unwrap()
+            .record
+            .unwrap();
+        assert_eq!(exchange.to_currency, Some("GBP".parse().unwrap()));
+        assert!(InputRecord::is_header(&StringRecord::from(vec![
+            "type", "client", "tx", "amount"
+        ])));
+        assert!(!InputRecord::is_header(&StringRecord::from(vec![
+            "type", "client"
+        ])));
+
+        assert!(
+            InputRecord::from_line("deposit,x,1,1")
+                .unwrap()
+                .record
+                .is_err()
+        );
     }
 }
@@ -1,20 +1,42 @@
+pub mod amount;
 pub mod amounts;
 pub mod client;
 pub mod client_id;
 pub mod client_status;
+pub mod currency;
+pub mod dispute_policy;
+pub mod event;
+pub mod fee_schedule;
+pub mod hold_expiry;
+pub mod input_format;
 pub mod input_record;
+pub mod output_format;
 pub mod output_record;
+pub mod percent;
+pub mod rate;
+pub mod rounding;
 pub mod transaction;
 pub mod transaction_status;
 pub mod tx_id;
 pub mod tx_type;
 
+pub use amount::*;
 pub use amounts::*;
 pub use client::*;
 pub use client_id::*;
 pub use client_status::*;
+pub use currency::*;
+pub use dispute_policy::*;
+pub use event::*;
+pub use fee_schedule::*;
+pub use hold_expiry::*;
+pub use input_format::*;
 pub use input_record::*;
+pub use output_format::*;
 pub use output_record::*;
+pub use percent::*;
+pub use rate::*;
+pub use rounding::*;
 pub use transaction::*;
 pub use transaction_status::*;
 pub use tx_id::*;
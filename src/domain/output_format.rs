@@ -0,0 +1,156 @@
+use crate::domain::output_record::OutputRecord;
+use csv::WriterBuilder;
+use std::io::Write;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl OutputFormat {
+    pub fn writer<'a, W: Write + 'a>(
+        self,
+        writer: W,
+        with_currency: bool,
+    ) -> anyhow::Result<Box<dyn OutputWriter + 'a>> {
+        let columns: &[&str] = if with_currency {
+            &OutputRecord::CURRENCY_COLUMNS
+        } else {
+            &OutputRecord::COLUMNS
+        };
+        match self {
+            Self::Csv => Ok(Box::new(CsvOutputWriter::new(writer, columns)?)),
+            Self::Jsonl => Ok(Box::new(JsonlOutputWriter::new(writer))),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "jsonl" => Ok(Self::Jsonl),
+            _ => Err(anyhow::anyhow!(
+                "Invalid output format '{}', expected 'csv' or 'jsonl'",
+                s
+            )),
+        }
+    }
+}
+
+pub trait OutputWriter {
+    fn write(&mut self, record: &OutputRecord) -> anyhow::Result<()>;
+
+    fn flush(&mut self) -> anyhow::Result<()>;
+}
+
+// The header is written up front, so an empty client list still gives a valid CSV.
+pub struct CsvOutputWriter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> CsvOutputWriter<W> {
+    pub fn new(writer: W, columns: &[&str]) -> anyhow::Result<Self> {
+        let mut writer = WriterBuilder::new().has_headers(false).from_writer(writer);
+        writer.write_record(columns)?;
+        Ok(Self { writer })
+    }
+}
+
+impl<W: Write> OutputWriter for CsvOutputWriter<W> {
+    fn write(&mut self, record: &OutputRecord) -> anyhow::Result<()> {
+        self.writer.serialize(record)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub struct JsonlOutputWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonlOutputWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> OutputWriter for JsonlOutputWriter<W> {
+    fn write(&mut self, record: &OutputRecord) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::client::Client;
+    use crate::domain::client_id::ClientId;
+    use crate::domain::currency::Currency;
+
+    fn write(format: OutputFormat, per_currency: bool) -> String {
+        let mut client = Client::new(ClientId::new(3));
+        client
+            .deposit(Currency::EUR, "1.5".parse().unwrap())
+            .unwrap();
+        let records = if per_currency {
+            OutputRecord::per_currency(&client, Currency::EUR)
+        } else {
+            vec![OutputRecord::new(&client, Currency::EUR)]
+        };
+        let mut output = Vec::new();
+
+        let mut writer = format.writer(&mut output, per_currency).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_csv_output() {
+        assert_eq!(
+            write(OutputFormat::Csv, false),
+            "client,available,held,total,locked\n3,1.5000,0.0000,1.5000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_jsonl_output() {
+        assert_eq!(
+            write(OutputFormat::Jsonl, false),
+            "{\"client\":\"3\",\"available\":\"1.5000\",\"held\":\"0.0000\",\"total\":\"1.5000\",\"locked\":false}\n"
+        );
+    }
+
+    #[test]
+    fn test_per_currency_output() {
+        assert_eq!(
+            write(OutputFormat::Csv, true),
+            "client,currency,available,held,total,locked\n3,EUR,1.5000,0.0000,1.5000,false\n"
+        );
+        assert_eq!(
+            write(OutputFormat::Jsonl, true),
+            "{\"client\":\"3\",\"currency\":\"EUR\",\"available\":\"1.5000\",\"held\":\"0.0000\",\"total\":\"1.5000\",\"locked\":false}\n"
+        );
+    }
+}
@@ -1,24 +1,150 @@
 use crate::domain::amounts::{AvailableAmount, HeldAmount, TotalAmount};
 use crate::domain::client::Client;
 use crate::domain::client_id::ClientId;
+use crate::domain::currency::Currency;
+use crate::domain::rate::RateTable;
+use crate::domain::rounding::Rounding;
+use serde::Serialize;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
 pub struct OutputRecord {
     pub client: ClientId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<Currency>,
     pub available: AvailableAmount,
     pub held: HeldAmount,
     pub total: TotalAmount,
     pub locked: bool,
 }
 
-impl From<&Client> for OutputRecord {
-    fn from(client: &Client) -> Self {
+impl OutputRecord {
+    pub const COLUMNS: [&'static str; 5] = ["client", "available", "held", "total", "locked"];
+    pub const CURRENCY_COLUMNS: [&'static str; 6] =
+        ["client", "currency", "available", "held", "total", "locked"];
+
+    // The classic row: the client's balances in a single currency, without naming it.
+    pub fn new(client: &Client, currency: Currency) -> Self {
+        let balance = client.balance(currency);
         Self {
             client: client.id,
-            available: client.available,
-            held: client.held,
-            total: client.total,
+            currency: None,
+            available: balance.available,
+            held: balance.held,
+            total: balance.total,
             locked: client.is_locked(),
         }
     }
+
+    // One row per currency the client holds, for `CURRENCY_COLUMNS` output. A client that
+    // never moved funds still gets a row in the default currency.
+    pub fn per_currency(client: &Client, default_currency: Currency) -> Vec<Self> {
+        if client.balances.is_empty() {
+            return vec![Self {
+                currency: Some(default_currency),
+                ..Self::new(client, default_currency)
+            }];
+        }
+        client
+            .balances
+            .keys()
+            .map(|&currency| Self {
+                currency: Some(currency),
+                ..Self::new(client, currency)
+            })
+            .collect()
+    }
+
+    // Every balance of the client converted into `currency` at the rate in effect now and
+    // summed, each converted amount rounded on its own.
+    pub fn consolidated(
+        client: &Client,
+        currency: Currency,
+        rates: &RateTable,
+        rounding: Rounding,
+    ) -> anyhow::Result<Self> {
+        let mut record = Self {
+            currency: Some(currency),
+            ..Self::new(&Client::new(client.id), currency)
+        };
+        record.locked = client.is_locked();
+
+        for (&from, balance) in &client.balances {
+            let convert = |amount| -> anyhow::Result<_> {
+                if from == currency {
+                    return Ok(amount);
+                }
+                let rate = rates
+                    .current(from, currency)
+                    .ok_or_else(|| anyhow::anyhow!("No rate from {} to {}", from, currency))?;
+                Ok(rate.convert(amount, rounding)?)
+            };
+            let available = convert(balance.available.get())?;
+            let held = convert(balance.held.get())?;
+            record.available = record.available.checked_add(available)?;
+            record.held = record.held.checked_add(held)?;
+            record.total = record.total.checked_add(available.checked_add(held)?)?;
+        }
+
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_currency_rows() {
+        let usd: Currency = "USD".parse().unwrap();
+        let mut client = Client::new(ClientId::new(1));
+        assert_eq!(
+            OutputRecord::per_currency(&client, usd)[0].currency,
+            Some(usd)
+        );
+
+        client.deposit(usd, "2".parse().unwrap()).unwrap();
+        client.deposit(Currency::EUR, "1".parse().unwrap()).unwrap();
+
+        let rows: Vec<_> = OutputRecord::per_currency(&client, usd)
+            .iter()
+            .map(|row| {
+                (
+                    row.currency.unwrap().to_string(),
+                    row.total.get().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("EUR".to_string(), "1.0000".to_string()),
+                ("USD".to_string(), "2.0000".to_string())
+            ]
+        );
+        assert_eq!(OutputRecord::new(&client, usd).currency, None);
+    }
+
+    #[test]
+    fn test_consolidated_balances() {
+        let (usd, gbp): (Currency, Currency) = ("USD".parse().unwrap(), "GBP".parse().unwrap());
+        let rates = RateTable::from_csv(
+            "pair,rate,effective_from\nUSD/EUR,0.5,\nUSD/EUR,0.25,10\n".as_bytes(),
+        )
+        .unwrap();
+        let mut client = Client::new(ClientId::new(1));
+        client.deposit(Currency::EUR, "1".parse().unwrap()).unwrap();
+        client.deposit(usd, "0.0003".parse().unwrap()).unwrap();
+
+        let record =
+            OutputRecord::consolidated(&client, Currency::EUR, &rates, Rounding::HalfEven).unwrap();
+        assert_eq!(record.currency, Some(Currency::EUR));
+        // 0.0003 USD at 0.25 is 0.000075 EUR, rounded to 0.0001.
+        assert_eq!(record.available.get().to_string(), "1.0001");
+        assert_eq!(record.total.get().to_string(), "1.0001");
+
+        client.deposit(gbp, "1".parse().unwrap()).unwrap();
+        assert!(
+            OutputRecord::consolidated(&client, Currency::EUR, &rates, Rounding::HalfEven).is_err()
+        );
+    }
 }
@@ -0,0 +1,61 @@
+use crate::domain::amount::Amount;
+use crate::domain::rate::{multiply, parse_unsigned};
+use crate::domain::rounding::Rounding;
+use crate::error::amount_error::AmountError;
+use serde::Deserialize;
+use std::str::FromStr;
+
+// A percentage of an amount, as an exact decimal. Unlike a rate it can be zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Percent(i128);
+
+impl Percent {
+    pub const DECIMALS: u32 = 12;
+    const SCALE: i128 = 100 * 10_i128.pow(Self::DECIMALS);
+
+    pub fn of(&self, amount: Amount, rounding: Rounding) -> Result<Amount, AmountError> {
+        multiply(amount, self.0, Self::SCALE, rounding)
+    }
+}
+
+impl FromStr for Percent {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_unsigned(s, Self::DECIMALS)
+            .map(Percent)
+            .ok_or_else(|| anyhow::anyhow!("Invalid percentage '{}'", s))
+    }
+}
+
+impl TryFrom<String> for Percent {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_allows_zero() {
+        let amount: Amount = "200".parse().unwrap();
+        let percent = |value: &str| value.parse::<Percent>().unwrap();
+
+        assert_eq!(
+            percent("0").of(amount, Rounding::HalfEven).unwrap(),
+            Amount::ZERO
+        );
+        assert_eq!(
+            percent("1.5").of(amount, Rounding::HalfEven).unwrap(),
+            "3".parse().unwrap()
+        );
+        for invalid in ["", "-1", "1.", ".5", "abc"] {
+            assert!(invalid.parse::<Percent>().is_err(), "{invalid}");
+        }
+    }
+}
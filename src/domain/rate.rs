@@ -0,0 +1,248 @@
+use crate::domain::amount::Amount;
+use crate::domain::currency::Currency;
+use crate::domain::rounding::Rounding;
+use crate::error::amount_error::AmountError;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Units of the quote currency for one unit of the base currency, as an exact decimal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Rate(i128);
+
+impl Rate {
+    pub const DECIMALS: u32 = 12;
+    const SCALE: i128 = 10_i128.pow(Self::DECIMALS);
+
+    pub fn convert(&self, amount: Amount, rounding: Rounding) -> Result<Amount, AmountError> {
+        multiply(amount, self.0, Self::SCALE, rounding)
+    }
+}
+
+// `amount * factor / scale`, rounded back to four decimals.
+pub(crate) fn multiply(
+    amount: Amount,
+    factor: i128,
+    scale: i128,
+    rounding: Rounding,
+) -> Result<Amount, AmountError> {
+    let product = (amount.raw() as i128)
+        .checked_mul(factor)
+        .ok_or(AmountError::Overflow)?;
+    i64::try_from(rounding.divide(product, scale))
+        .map(Amount::from_raw)
+        .map_err(|_| AmountError::Overflow)
+}
+
+// An unsigned decimal with up to `decimals` places, scaled to an integer.
+pub(crate) fn parse_unsigned(s: &str, decimals: u32) -> Option<i128> {
+    let (int_part, frac_part) = s.split_once('.').unwrap_or((s, ""));
+    let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if int_part.is_empty()
+        || !all_digits(int_part)
+        || !all_digits(frac_part)
+        || (s.contains('.') && frac_part.is_empty())
+        || frac_part.len() > decimals as usize
+    {
+        return None;
+    }
+
+    format!("{int_part}{frac_part:0<width$}", width = decimals as usize)
+        .parse()
+        .ok()
+}
+
+impl FromStr for Rate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_unsigned(s, Self::DECIMALS) {
+            Some(raw) if raw > 0 => Ok(Rate(raw)),
+            _ => Err(anyhow::anyhow!("Invalid rate '{}'", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for Rate {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+// Shortest exact form: `1.085`, not `1.085000000000`.
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let int_part = self.0 / Self::SCALE;
+        let frac_part = self.0 % Self::SCALE;
+        if frac_part == 0 {
+            return write!(f, "{int_part}");
+        }
+        let frac = format!("{:0width$}", frac_part, width = Self::DECIMALS as usize);
+        write!(f, "{}.{}", int_part, frac.trim_end_matches('0'))
+    }
+}
+
+impl Serialize for Rate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RateRecord {
+    pair: String,
+    rate: Rate,
+    effective_from: Option<u64>,
+}
+
+// Rates by currency pair, each effective from a Unix time in seconds until the next one of
+// the same pair. Pairs are directional: `EUR/USD` only converts EUR into USD, an inverted rate
+// wouldn't be exact.
+#[derive(Debug, Clone, Default)]
+pub struct RateTable {
+    rates: HashMap<(Currency, Currency), BTreeMap<u64, Rate>>,
+}
+
+impl RateTable {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .map_err(|err| anyhow::anyhow!("Error opening rates '{}': {}", path.display(), err))?;
+        Self::from_csv(file)
+            .map_err(|err| anyhow::anyhow!("Error reading rates '{}': {}", path.display(), err))
+    }
+
+    pub fn from_csv<R: Read>(reader: R) -> anyhow::Result<Self> {
+        let mut table = Self::default();
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+
+        for row in reader.deserialize() {
+            let row: RateRecord = row?;
+            let (base, quote) = row
+                .pair
+                .split_once('/')
+                .ok_or_else(|| anyhow::anyhow!("Invalid pair '{}'", row.pair))?;
+            let pair = (base.parse()?, quote.parse()?);
+            let effective_from = row.effective_from.unwrap_or(0);
+            if table
+                .rates
+                .entry(pair)
+                .or_default()
+                .insert(effective_from, row.rate)
+                .is_some()
+            {
+                anyhow::bail!(
+                    "Duplicate rate for {} effective from {}",
+                    row.pair,
+                    effective_from
+                );
+            }
+        }
+
+        Ok(table)
+    }
+
+    // Rate in effect at Unix time `at`.
+    pub fn rate(&self, from: Currency, to: Currency, at: u64) -> Option<Rate> {
+        let rates = self.rates.get(&(from, to))?;
+        rates.range(..=at).next_back().map(|(_, rate)| *rate)
+    }
+
+    pub fn current(&self, from: Currency, to: Currency) -> Option<Rate> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.rate(from, to, now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn currency(code: &str) -> Currency {
+        code.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display_rate() {
+        assert_eq!("1.0850".parse::<Rate>().unwrap().to_string(), "1.085");
+        assert_eq!("2".parse::<Rate>().unwrap().to_string(), "2");
+        assert_eq!(
+            "0.000000000001".parse::<Rate>().unwrap().to_string(),
+            "0.000000000001"
+        );
+        for invalid in ["", "0", "-1", "1.", ".5", "1e3", "0.0000000000001"] {
+            assert!(invalid.parse::<Rate>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_convert_is_exact_and_rounded() {
+        let rate: Rate = "1.08505".parse().unwrap();
+        let amount: Amount = "10.0001".parse().unwrap();
+
+        // 10.0001 * 1.08505 = 10.850608505
+        assert_eq!(
+            rate.convert(amount, Rounding::HalfEven)
+                .unwrap()
+                .to_string(),
+            "10.8506"
+        );
+        assert_eq!(
+            rate.convert(amount, Rounding::Down).unwrap().to_string(),
+            "10.8506"
+        );
+        let half: Amount = "0.0001".parse().unwrap();
+        let rate: Rate = "0.5".parse().unwrap();
+        assert_eq!(
+            rate.convert(half, Rounding::HalfEven).unwrap(),
+            Amount::ZERO
+        );
+        assert_eq!(rate.convert(half, Rounding::HalfUp).unwrap(), half);
+    }
+
+    #[test]
+    fn test_rate_in_effect() {
+        let rates = "\
+pair,rate,effective_from
+EUR/USD,1.10,
+EUR/USD,1.20,1700000000
+EUR/USD,1.30,4102444800
+USD/EUR,0.9,
+";
+        let table = RateTable::from_csv(Cursor::new(rates)).unwrap();
+        let (eur, usd) = (currency("EUR"), currency("USD"));
+
+        let at = |time| table.rate(eur, usd, time).unwrap().to_string();
+        assert_eq!(at(1_699_999_999), "1.1");
+        assert_eq!(at(1_700_000_000), "1.2");
+        assert_eq!(at(u64::MAX), "1.3");
+        // A rate only takes over once its time has come.
+        assert_eq!(table.current(eur, usd).unwrap().to_string(), "1.2");
+        assert_eq!(table.current(usd, eur).unwrap().to_string(), "0.9");
+        assert_eq!(table.current(eur, currency("GBP")), None);
+    }
+
+    #[test]
+    fn test_invalid_rate_files() {
+        for rates in [
+            "pair,rate,effective_from\nEURUSD,1.1,\n",
+            "pair,rate,effective_from\nEUR/USD,abc,\n",
+            "pair,rate,effective_from\nEUR/USD,1.1,5\nEUR/USD,1.2,5\n",
+            "pair,rate,effective_from\nEUR/USD,1.1,2024-01-01\n",
+        ] {
+            assert!(RateTable::from_csv(Cursor::new(rates)).is_err(), "{rates}");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+
+// How amounts that don't fit in four decimals are rounded, e.g. after a currency conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    // Ties go to the even neighbour.
+    #[default]
+    HalfEven,
+    // Ties go away from zero.
+    HalfUp,
+    // Truncates towards zero.
+    Down,
+}
+
+impl Rounding {
+    pub fn divide(self, numerator: i128, denominator: i128) -> i128 {
+        let quotient = numerator / denominator;
+        let remainder = numerator % denominator;
+        if remainder == 0 {
+            return quotient;
+        }
+
+        let away = if (numerator < 0) == (denominator < 0) {
+            quotient + 1
+        } else {
+            quotient - 1
+        };
+        let twice = remainder.unsigned_abs() * 2;
+        match (self, twice.cmp(&denominator.unsigned_abs())) {
+            (Self::Down, _) | (_, std::cmp::Ordering::Less) => quotient,
+            (_, std::cmp::Ordering::Greater) | (Self::HalfUp, _) => away,
+            (Self::HalfEven, _) if quotient % 2 == 0 => quotient,
+            (Self::HalfEven, _) => away,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_divide() {
+        let cases = [
+            (25, 10, [2, 3, 2]),
+            (35, 10, [4, 4, 3]),
+            (26, 10, [3, 3, 2]),
+            (-25, 10, [-2, -3, -2]),
+            (-26, 10, [-3, -3, -2]),
+            (20, 10, [2, 2, 2]),
+        ];
+        for (numerator, denominator, expected) in cases {
+            let rounded = [Rounding::HalfEven, Rounding::HalfUp, Rounding::Down]
+                .map(|rounding| rounding.divide(numerator, denominator));
+            assert_eq!(rounded, expected, "{numerator}/{denominator}");
+        }
+    }
+}
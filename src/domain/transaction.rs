@@ -1,46 +1,750 @@
+use crate::domain::amount::Amount;
 use crate::domain::client_id::ClientId;
+use crate::domain::currency::Currency;
+use crate::domain::dispute_policy::{DisputePolicy, WithdrawalDisputes};
 use crate::domain::input_record::InputRecord;
+use crate::domain::rate::Rate;
 use crate::domain::transaction_status::TransactionStatus;
 use crate::domain::tx_id::TxId;
 use crate::domain::tx_type::TxType;
 use crate::error::transaction_error::TransactionError;
-#[derive(Debug, Clone)]
+use serde::{Deserialize, Serialize};
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub id: TxId,
     pub client_id: ClientId,
-    pub amount: f64,
+    pub amount: Amount,
+    #[serde(default)]
+    pub currency: Currency,
     pub tx_type: TxType,
     pub status: TransactionStatus,
+    // Client credited by a transfer, or the house account paid a fee; `client_id` is the one
+    // debited.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination_id: Option<ClientId>,
+    // Currency an exchange credits, and the amount credited at `rate`; `amount` is debited
+    // in `currency`. The amount and rate are set once the exchange is priced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination_currency: Option<Currency>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination_amount: Option<Amount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<Rate>,
+    // Part of an authorization's `amount` that was captured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub captured_amount: Option<Amount>,
+    // Part of `amount` under dispute now, and part of it charged back so far. Transactions
+    // stored before partial disputes have neither, and derive them from `status`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disputed_amount: Option<Amount>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub charged_back_amount: Option<Amount>,
+    // Part of `amount` disputed and then resolved, which only a policy allowing re-disputes
+    // lets be disputed again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_amount: Option<Amount>,
+    // Part of a deposit returned by refunds so far.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refunded_amount: Option<Amount>,
+    // Deadline of an authorization under `HoldExpiry`: the record count and the Unix time at
+    // which it expires.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_after_record: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
 }
 
 impl Transaction {
-    pub fn new(id: TxId, client_id: ClientId, amount: f64, tx_type: TxType) -> Self {
+    pub fn new(id: TxId, client_id: ClientId, amount: Amount, tx_type: TxType) -> Self {
         Self {
             id,
             client_id,
             amount,
+            currency: Currency::default(),
             tx_type,
             status: TransactionStatus::Confirmed,
+            destination_id: None,
+            destination_currency: None,
+            destination_amount: None,
+            rate: None,
+            captured_amount: None,
+            disputed_amount: None,
+            charged_back_amount: None,
+            resolved_amount: None,
+            refunded_amount: None,
+            expires_after_record: None,
+            expires_at: None,
         }
     }
 
+    pub fn transfer(
+        id: TxId,
+        client_id: ClientId,
+        destination_id: ClientId,
+        amount: Amount,
+    ) -> Self {
+        Self {
+            destination_id: Some(destination_id),
+            ..Self::new(id, client_id, amount, TxType::Transfer)
+        }
+    }
+
+    pub fn authorization(id: TxId, client_id: ClientId, amount: Amount) -> Self {
+        Self {
+            status: TransactionStatus::Authorized,
+            ..Self::new(id, client_id, amount, TxType::Authorize)
+        }
+    }
+
+    // Fees are stored apart from the transactions, under the ID of the one they were charged on.
+    pub fn fee(parent: &Transaction, house: ClientId, amount: Amount) -> Self {
+        Self {
+            currency: parent.currency,
+            destination_id: Some(house),
+            ..Self::new(parent.id, parent.client_id, amount, TxType::Fee)
+        }
+    }
+
+    pub fn exchange(
+        id: TxId,
+        client_id: ClientId,
+        amount: Amount,
+        destination_currency: Currency,
+    ) -> Self {
+        Self {
+            destination_currency: Some(destination_currency),
+            ..Self::new(id, client_id, amount, TxType::Exchange)
+        }
+    }
+
+    // Records without a currency are in `default_currency`.
+    pub fn from_record(
+        record: &InputRecord,
+        default_currency: Currency,
+    ) -> Result<Self, TransactionError> {
+        let currency = record.currency.unwrap_or(default_currency);
+        let transaction = match (record.tx_type, record.amount, record.to, record.to_currency) {
+            (TxType::Transfer, Some(amount), Some(to), None) if to != record.client => {
+                Transaction::transfer(record.tx, record.client, to, amount)
+            }
+            (TxType::Deposit | TxType::Withdrawal, Some(amount), None, None) => {
+                Transaction::new(record.tx, record.client, amount, record.tx_type)
+            }
+            (TxType::Authorize, Some(amount), None, None) => {
+                Transaction::authorization(record.tx, record.client, amount)
+            }
+            (TxType::Exchange, Some(amount), None, Some(to_currency))
+                if to_currency != currency =>
+            {
+                Transaction::exchange(record.tx, record.client, amount, to_currency)
+            }
+            _ => return Err(TransactionError::InvalidTransaction { id: record.tx }),
+        };
+
+        Ok(Self {
+            currency,
+            ..transaction
+        })
+    }
+
     pub fn is_under_dispute(&self) -> bool {
         self.status == TransactionStatus::Disputed
     }
+
+    pub fn is_expiring(&self) -> bool {
+        self.status == TransactionStatus::Authorized
+            && (self.expires_after_record.is_some() || self.expires_at.is_some())
+    }
+
+    pub fn disputed(&self) -> Amount {
+        self.disputed_amount.unwrap_or(match self.status {
+            TransactionStatus::Disputed => self.amount,
+            _ => Amount::ZERO,
+        })
+    }
+
+    pub fn charged_back(&self) -> Amount {
+        self.charged_back_amount.unwrap_or(match self.status {
+            TransactionStatus::ChargedBack => self.amount,
+            _ => Amount::ZERO,
+        })
+    }
+
+    pub fn resolved(&self) -> Amount {
+        self.resolved_amount.unwrap_or(match self.status {
+            TransactionStatus::Resolved => self.amount,
+            _ => Amount::ZERO,
+        })
+    }
+
+    pub fn refunded(&self) -> Amount {
+        self.refunded_amount.unwrap_or(Amount::ZERO)
+    }
+
+    // Part of `amount` not disputed, charged back or refunded.
+    fn outstanding(&self) -> Result<Amount, TransactionError> {
+        Ok(self
+            .amount
+            .checked_sub(self.disputed())?
+            .checked_sub(self.charged_back())?
+            .checked_sub(self.refunded())?)
+    }
+
+    pub fn is_replay_of(&self, other: &Transaction) -> bool {
+        self.id == other.id
+            && self.client_id == other.client_id
+            && self.amount == other.amount
+            && self.currency == other.currency
+            && self.tx_type == other.tx_type
+            && self.destination_id == other.destination_id
+            && self.destination_currency == other.destination_currency
+            && self.destination_amount == other.destination_amount
+    }
+
+    // Disputes, resolves and chargebacks cover `amount`, or the part of it given. A dispute
+    // can be added to while the transaction is disputed and can cover whatever was never
+    // disputed, plus what was resolved if the policy allows re-disputes; resolves and
+    // chargebacks take from what is disputed. Returns the amount affected.
+    pub fn transition(
+        &mut self,
+        tx_type: TxType,
+        amount: Option<Amount>,
+        policy: &DisputePolicy,
+    ) -> Result<Amount, TransactionError> {
+        use TransactionStatus::*;
+
+        let id = self.id;
+        let (disputed, charged_back, resolved) =
+            (self.disputed(), self.charged_back(), self.resolved());
+        let undisputed = self.outstanding()?;
+        let never_disputed = undisputed.checked_sub(resolved)?.max(Amount::ZERO);
+        let disputable = if policy.allow_redispute_after_resolve {
+            undisputed
+        } else {
+            never_disputed
+        };
+        match (self.status, tx_type) {
+            (_, TxType::Dispute | TxType::Resolve | TxType::Chargeback)
+                if matches!(
+                    self.tx_type,
+                    TxType::Exchange | TxType::Authorize | TxType::Fee
+                ) =>
+            {
+                return Err(TransactionError::DisputeNotAllowed { id });
+            }
+            (Confirmed | Resolved, TxType::Dispute)
+                if self.tx_type == TxType::Withdrawal
+                    && policy.withdrawal_disputes == WithdrawalDisputes::Deny =>
+            {
+                return Err(TransactionError::DisputeNotAllowed { id });
+            }
+            (Confirmed, TxType::Dispute) => {}
+            (Disputed, TxType::Dispute) if amount.is_some() => {}
+            (Resolved | ChargedBack, TxType::Dispute) if disputable > Amount::ZERO => {}
+            (Disputed, TxType::Resolve | TxType::Chargeback) => {}
+            (Confirmed, TxType::Resolve | TxType::Chargeback) => {
+                return Err(TransactionError::NotUnderDispute { id });
+            }
+            (Disputed, TxType::Dispute) => return Err(TransactionError::AlreadyDisputed { id }),
+            (Resolved, _) => return Err(TransactionError::AlreadyResolved { id }),
+            (ChargedBack, _) => return Err(TransactionError::AlreadyChargedBack { id }),
+            (Reversed, _) => return Err(TransactionError::AlreadyReversed { id }),
+            _ => return Err(TransactionError::InvalidTransaction { id }),
+        }
+
+        let limit = match tx_type {
+            TxType::Dispute => disputable,
+            _ => disputed,
+        };
+        let amount = amount.unwrap_or(limit);
+        if amount > limit || limit == Amount::ZERO {
+            return Err(match tx_type {
+                TxType::Dispute => TransactionError::DisputeExceedsAmount { id },
+                _ => TransactionError::ExceedsDisputedAmount { id },
+            });
+        }
+        if amount <= Amount::ZERO {
+            return Err(TransactionError::InvalidTransaction { id });
+        }
+
+        let (disputed, charged_back, resolved) = match tx_type {
+            // What was never disputed is taken first, the rest from what was resolved.
+            TxType::Dispute => (
+                disputed.checked_add(amount)?,
+                charged_back,
+                resolved.checked_sub(amount.checked_sub(never_disputed)?.max(Amount::ZERO))?,
+            ),
+            TxType::Resolve => (
+                disputed.checked_sub(amount)?,
+                charged_back,
+                resolved.checked_add(amount)?,
+            ),
+            _ => (
+                disputed.checked_sub(amount)?,
+                charged_back.checked_add(amount)?,
+                resolved,
+            ),
+        };
+        self.status = if disputed > Amount::ZERO {
+            Disputed
+        } else if charged_back > Amount::ZERO {
+            ChargedBack
+        } else {
+            Resolved
+        };
+        self.disputed_amount = Some(disputed);
+        self.charged_back_amount = Some(charged_back);
+        self.resolved_amount = Some(resolved);
+        Ok(amount)
+    }
+
+    // Refunds return a deposit, or the part given, as long as it isn't disputed, charged back
+    // or refunded already. Returns the amount refunded.
+    pub fn refund(&mut self, amount: Option<Amount>) -> Result<Amount, TransactionError> {
+        let id = self.id;
+        if self.tx_type != TxType::Deposit {
+            return Err(TransactionError::NotRefundable { id });
+        }
+        if self.status == TransactionStatus::Reversed {
+            return Err(TransactionError::AlreadyReversed { id });
+        }
+        let refundable = self.outstanding()?;
+        let amount = amount.unwrap_or(refundable);
+        if amount > refundable || refundable == Amount::ZERO {
+            return Err(TransactionError::RefundExceedsAmount { id });
+        }
+        if amount <= Amount::ZERO {
+            return Err(TransactionError::InvalidTransaction { id });
+        }
+        self.refunded_amount = Some(self.refunded().checked_add(amount)?);
+        Ok(amount)
+    }
+
+    // A reversal cancels whatever is left of a deposit or withdrawal that isn't under dispute,
+    // after what was charged back or refunded. Returns the amount reversed.
+    pub fn reverse(&mut self) -> Result<Amount, TransactionError> {
+        use TransactionStatus::*;
+
+        let id = self.id;
+        if !matches!(self.tx_type, TxType::Deposit | TxType::Withdrawal) {
+            return Err(TransactionError::NotReversible { id });
+        }
+        match self.status {
+            Confirmed | Resolved | ChargedBack => {}
+            Disputed => return Err(TransactionError::AlreadyDisputed { id }),
+            Reversed => return Err(TransactionError::AlreadyReversed { id }),
+            _ => return Err(TransactionError::InvalidTransaction { id }),
+        }
+        let amount = self.outstanding()?;
+        match (self.status, amount == Amount::ZERO) {
+            (ChargedBack, true) => return Err(TransactionError::AlreadyChargedBack { id }),
+            (_, true) => return Err(TransactionError::NotReversible { id }),
+            _ => {}
+        }
+        self.status = Reversed;
+        Ok(amount)
+    }
+
+    // Captures, voids and expiries end an authorization; none of them can be undone.
+    pub fn settle(&mut self, status: TransactionStatus) -> Result<(), TransactionError> {
+        if self.tx_type != TxType::Authorize {
+            return Err(TransactionError::NotAnAuthorization { id: self.id });
+        }
+        if self.status != TransactionStatus::Authorized {
+            return Err(TransactionError::AuthorizationClosed {
+                id: self.id,
+                status: self.status,
+            });
+        }
+        self.status = status;
+        Ok(())
+    }
 }
 
-impl TryFrom<&InputRecord> for Transaction {
-    type Error = TransactionError;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_transaction(status: TransactionStatus) -> Transaction {
+        let mut tx = Transaction::new(
+            TxId::new(1),
+            ClientId::new(1),
+            "10".parse().unwrap(),
+            TxType::Deposit,
+        );
+        tx.status = status;
+        tx
+    }
+
+    #[test]
+    fn test_full_dispute_lifecycle() {
+        let policy = DisputePolicy::default();
+        let mut tx = setup_transaction(TransactionStatus::Confirmed);
+
+        tx.transition(TxType::Dispute, None, &policy).unwrap();
+        assert_eq!(tx.status, TransactionStatus::Disputed);
+
+        tx.transition(TxType::Resolve, None, &policy).unwrap();
+        assert_eq!(tx.status, TransactionStatus::Resolved);
+
+        let mut tx = setup_transaction(TransactionStatus::Disputed);
+        tx.transition(TxType::Chargeback, None, &policy).unwrap();
+        assert_eq!(tx.status, TransactionStatus::ChargedBack);
+    }
+
+    #[test]
+    fn test_resolve_or_chargeback_without_dispute() {
+        let policy = DisputePolicy::default();
 
-    fn try_from(record: &InputRecord) -> Result<Self, Self::Error> {
-        match record.amount {
-            Some(amount) => Ok(Transaction::new(
-                record.tx,
-                record.client,
-                amount,
-                record.tx_type,
-            )),
-            None => Err(TransactionError::InvalidTransaction { id: record.tx }),
+        for tx_type in [TxType::Resolve, TxType::Chargeback] {
+            let mut tx = setup_transaction(TransactionStatus::Confirmed);
+            let result = tx.transition(tx_type, None, &policy);
+
+            assert!(matches!(
+                result,
+                Err(TransactionError::NotUnderDispute { .. })
+            ));
+            assert_eq!(tx.status, TransactionStatus::Confirmed);
+        }
+    }
+
+    #[test]
+    fn test_dispute_twice() {
+        let mut tx = setup_transaction(TransactionStatus::Disputed);
+
+        let result = tx.transition(TxType::Dispute, None, &DisputePolicy::default());
+
+        assert!(matches!(
+            result,
+            Err(TransactionError::AlreadyDisputed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_partial_disputes() {
+        let policy = DisputePolicy::default();
+        let amount = |value: &str| -> Option<Amount> { Some(value.parse().unwrap()) };
+        let mut tx = setup_transaction(TransactionStatus::Confirmed);
+
+        assert_eq!(
+            tx.transition(TxType::Dispute, amount("3"), &policy)
+                .unwrap(),
+            "3".parse().unwrap()
+        );
+        tx.transition(TxType::Dispute, amount("4"), &policy)
+            .unwrap();
+        assert!(matches!(
+            tx.transition(TxType::Dispute, amount("4"), &policy),
+            Err(TransactionError::DisputeExceedsAmount { .. })
+        ));
+        tx.transition(TxType::Resolve, amount("2"), &policy)
+            .unwrap();
+        assert!(matches!(
+            tx.transition(TxType::Chargeback, amount("6"), &policy),
+            Err(TransactionError::ExceedsDisputedAmount { .. })
+        ));
+        assert!(matches!(
+            tx.transition(TxType::Chargeback, amount("0"), &policy),
+            Err(TransactionError::InvalidTransaction { .. })
+        ));
+        assert_eq!(tx.status, TransactionStatus::Disputed);
+        assert_eq!(tx.disputed(), "5".parse().unwrap());
+
+        tx.transition(TxType::Chargeback, None, &policy).unwrap();
+        assert_eq!(tx.status, TransactionStatus::ChargedBack);
+        assert_eq!(tx.disputed(), Amount::ZERO);
+        assert_eq!(tx.charged_back(), "5".parse().unwrap());
+
+        // The part never disputed can still be disputed, the resolved part can't.
+        assert_eq!(
+            tx.transition(TxType::Dispute, None, &policy).unwrap(),
+            "3".parse().unwrap()
+        );
+        tx.transition(TxType::Chargeback, None, &policy).unwrap();
+        assert!(matches!(
+            tx.transition(TxType::Dispute, None, &policy),
+            Err(TransactionError::AlreadyChargedBack { .. })
+        ));
+    }
+
+    #[test]
+    fn test_amounts_derived_from_status() {
+        let tx = setup_transaction(TransactionStatus::Disputed);
+        assert_eq!(tx.disputed(), "10".parse().unwrap());
+        assert_eq!(tx.charged_back(), Amount::ZERO);
+
+        let tx = setup_transaction(TransactionStatus::ChargedBack);
+        assert_eq!(tx.disputed(), Amount::ZERO);
+        assert_eq!(tx.charged_back(), "10".parse().unwrap());
+    }
+
+    #[test]
+    fn test_no_transition_after_resolve() {
+        let policy = DisputePolicy::default();
+
+        for tx_type in [TxType::Dispute, TxType::Resolve, TxType::Chargeback] {
+            let mut tx = setup_transaction(TransactionStatus::Resolved);
+            let result = tx.transition(tx_type, None, &policy);
+
+            assert!(matches!(
+                result,
+                Err(TransactionError::AlreadyResolved { .. })
+            ));
+            assert_eq!(tx.status, TransactionStatus::Resolved);
+        }
+    }
+
+    #[test]
+    fn test_undisputed_part_can_be_disputed_after_resolve() {
+        let policy = DisputePolicy::default();
+        let amount = |value: &str| -> Option<Amount> { Some(value.parse().unwrap()) };
+        let mut tx = setup_transaction(TransactionStatus::Confirmed);
+
+        tx.transition(TxType::Dispute, amount("4"), &policy)
+            .unwrap();
+        tx.transition(TxType::Resolve, None, &policy).unwrap();
+        assert_eq!(tx.status, TransactionStatus::Resolved);
+        assert_eq!(tx.resolved(), "4".parse().unwrap());
+
+        assert!(matches!(
+            tx.transition(TxType::Dispute, amount("6.0001"), &policy),
+            Err(TransactionError::DisputeExceedsAmount { .. })
+        ));
+        assert_eq!(
+            tx.transition(TxType::Dispute, amount("6"), &policy)
+                .unwrap(),
+            "6".parse().unwrap()
+        );
+        assert_eq!(tx.status, TransactionStatus::Disputed);
+        tx.transition(TxType::Resolve, None, &policy).unwrap();
+        assert!(matches!(
+            tx.transition(TxType::Dispute, None, &policy),
+            Err(TransactionError::AlreadyResolved { .. })
+        ));
+
+        let redispute = DisputePolicy {
+            allow_redispute_after_resolve: true,
+            ..DisputePolicy::default()
+        };
+        assert_eq!(
+            tx.transition(TxType::Dispute, amount("3"), &redispute)
+                .unwrap(),
+            "3".parse().unwrap()
+        );
+        assert_eq!(tx.resolved(), "7".parse().unwrap());
+    }
+
+    #[test]
+    fn test_redispute_after_resolve_when_allowed() {
+        let policy = DisputePolicy {
+            allow_redispute_after_resolve: true,
+            ..DisputePolicy::default()
+        };
+        let mut tx = setup_transaction(TransactionStatus::Resolved);
+
+        tx.transition(TxType::Dispute, None, &policy).unwrap();
+
+        assert_eq!(tx.status, TransactionStatus::Disputed);
+    }
+
+    #[test]
+    fn test_no_transition_after_chargeback() {
+        let policy = DisputePolicy {
+            allow_redispute_after_resolve: true,
+            ..DisputePolicy::default()
+        };
+
+        for tx_type in [TxType::Dispute, TxType::Resolve, TxType::Chargeback] {
+            let mut tx = setup_transaction(TransactionStatus::ChargedBack);
+            let result = tx.transition(tx_type, None, &policy);
+
+            assert!(matches!(
+                result,
+                Err(TransactionError::AlreadyChargedBack { .. })
+            ));
+            assert_eq!(tx.status, TransactionStatus::ChargedBack);
         }
     }
+
+    #[test]
+    fn test_withdrawal_dispute_denied_by_default() {
+        let mut tx = setup_transaction(TransactionStatus::Confirmed);
+        tx.tx_type = TxType::Withdrawal;
+
+        let result = tx.transition(TxType::Dispute, None, &DisputePolicy::default());
+
+        assert!(matches!(
+            result,
+            Err(TransactionError::DisputeNotAllowed { .. })
+        ));
+        assert_eq!(tx.status, TransactionStatus::Confirmed);
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_allowed_by_policy() {
+        let mut tx = setup_transaction(TransactionStatus::Confirmed);
+        tx.tx_type = TxType::Withdrawal;
+        let policy = DisputePolicy {
+            withdrawal_disputes: WithdrawalDisputes::Reverse,
+            ..DisputePolicy::default()
+        };
+
+        tx.transition(TxType::Dispute, None, &policy).unwrap();
+
+        assert_eq!(tx.status, TransactionStatus::Disputed);
+    }
+
+    #[test]
+    fn test_transfer_from_record() {
+        let mut record = InputRecord {
+            tx_type: TxType::Transfer,
+            client: ClientId::new(1),
+            tx: TxId::new(1),
+            amount: Some("5".parse().unwrap()),
+            to: Some(ClientId::new(2)),
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
+        };
+
+        let tx = Transaction::from_record(&record, Currency::EUR).unwrap();
+        assert_eq!(tx.tx_type, TxType::Transfer);
+        assert_eq!(tx.client_id, ClientId::new(1));
+        assert_eq!(tx.destination_id, Some(ClientId::new(2)));
+        assert_eq!(tx.currency, Currency::EUR);
+
+        record.currency = Some("GBP".parse().unwrap());
+        assert_eq!(
+            Transaction::from_record(&record, Currency::EUR)
+                .unwrap()
+                .currency
+                .to_string(),
+            "GBP"
+        );
+
+        record.to = Some(ClientId::new(1));
+        assert!(matches!(
+            Transaction::from_record(&record, Currency::EUR),
+            Err(TransactionError::InvalidTransaction { .. })
+        ));
+
+        record.to = None;
+        assert!(matches!(
+            Transaction::from_record(&record, Currency::EUR),
+            Err(TransactionError::InvalidTransaction { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_transition_type() {
+        let mut tx = setup_transaction(TransactionStatus::Confirmed);
+
+        let result = tx.transition(TxType::Deposit, None, &DisputePolicy::default());
+
+        assert!(matches!(
+            result,
+            Err(TransactionError::InvalidTransaction { .. })
+        ));
+    }
+
+    #[test]
+    fn test_refund_and_reverse() {
+        let mut tx = setup_transaction(TransactionStatus::Confirmed);
+
+        assert_eq!(
+            tx.refund(Some("4".parse().unwrap())).unwrap(),
+            "4".parse().unwrap()
+        );
+        assert!(matches!(
+            tx.refund(Some("7".parse().unwrap())),
+            Err(TransactionError::RefundExceedsAmount { .. })
+        ));
+        assert_eq!(tx.reverse().unwrap(), "6".parse().unwrap());
+        assert_eq!(tx.status, TransactionStatus::Reversed);
+        assert!(matches!(
+            tx.refund(None),
+            Err(TransactionError::AlreadyReversed { .. })
+        ));
+
+        let mut tx = setup_transaction(TransactionStatus::Confirmed);
+        let policy = DisputePolicy::default();
+        tx.transition(TxType::Dispute, Some("3".parse().unwrap()), &policy)
+            .unwrap();
+        tx.transition(TxType::Chargeback, None, &policy).unwrap();
+        tx.refund(Some("2".parse().unwrap())).unwrap();
+        assert_eq!(tx.reverse().unwrap(), "5".parse().unwrap());
+
+        let mut tx = setup_transaction(TransactionStatus::ChargedBack);
+        assert!(matches!(
+            tx.reverse(),
+            Err(TransactionError::AlreadyChargedBack { .. })
+        ));
+
+        let mut tx = setup_transaction(TransactionStatus::Disputed);
+        assert!(matches!(
+            tx.reverse(),
+            Err(TransactionError::AlreadyDisputed { .. })
+        ));
+        tx.tx_type = TxType::Withdrawal;
+        assert!(matches!(
+            tx.refund(None),
+            Err(TransactionError::NotRefundable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_settle_authorization() {
+        let mut tx =
+            Transaction::authorization(TxId::new(1), ClientId::new(1), "2".parse().unwrap());
+        assert!(matches!(
+            tx.transition(TxType::Dispute, None, &DisputePolicy::default()),
+            Err(TransactionError::DisputeNotAllowed { .. })
+        ));
+
+        tx.settle(TransactionStatus::Captured).unwrap();
+
+        assert_eq!(tx.status, TransactionStatus::Captured);
+        assert!(matches!(
+            tx.settle(TransactionStatus::Voided),
+            Err(TransactionError::AuthorizationClosed {
+                status: TransactionStatus::Captured,
+                ..
+            })
+        ));
+        let mut deposit = setup_transaction(TransactionStatus::Confirmed);
+        assert!(matches!(
+            deposit.settle(TransactionStatus::Voided),
+            Err(TransactionError::NotAnAuthorization { .. })
+        ));
+    }
+
+    #[test]
+    fn test_exchange_from_record() {
+        let mut record = InputRecord {
+            tx_type: TxType::Exchange,
+            client: ClientId::new(1),
+            tx: TxId::new(1),
+            amount: Some("5".parse().unwrap()),
+            to: None,
+            currency: Some("USD".parse().unwrap()),
+            to_currency: Some(Currency::EUR),
+            reason: None,
+            operator: None,
+        };
+
+        let mut tx = Transaction::from_record(&record, Currency::EUR).unwrap();
+        assert_eq!(tx.currency.to_string(), "USD");
+        assert_eq!(tx.destination_currency, Some(Currency::EUR));
+        assert!(matches!(
+            tx.transition(TxType::Dispute, None, &DisputePolicy::default()),
+            Err(TransactionError::DisputeNotAllowed { .. })
+        ));
+
+        record.currency = None;
+        assert!(matches!(
+            Transaction::from_record(&record, Currency::EUR),
+            Err(TransactionError::InvalidTransaction { .. })
+        ));
+    }
 }
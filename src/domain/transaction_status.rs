@@ -1,5 +1,60 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
 pub enum TransactionStatus {
     Confirmed,
     Disputed,
+    Resolved,
+    ChargedBack,
+    // Authorizations: funds held until captured, voided or expired.
+    Authorized,
+    Captured,
+    Voided,
+    Expired,
+    // Cancelled by an operator; final.
+    Reversed,
+}
+
+impl fmt::Display for TransactionStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Confirmed => "confirmed",
+            Self::Disputed => "disputed",
+            Self::Resolved => "resolved",
+            Self::ChargedBack => "charged_back",
+            Self::Authorized => "authorized",
+            Self::Captured => "captured",
+            Self::Voided => "voided",
+            Self::Expired => "expired",
+            Self::Reversed => "reversed",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Serialize for TransactionStatus {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl TryFrom<String> for TransactionStatus {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Ok(match s.as_str() {
+            "confirmed" => Self::Confirmed,
+            "disputed" => Self::Disputed,
+            "resolved" => Self::Resolved,
+            "charged_back" => Self::ChargedBack,
+            "authorized" => Self::Authorized,
+            "captured" => Self::Captured,
+            "voided" => Self::Voided,
+            "expired" => Self::Expired,
+            "reversed" => Self::Reversed,
+            _ => return Err(anyhow::anyhow!("Invalid transaction status: {}", s)),
+        })
+    }
 }
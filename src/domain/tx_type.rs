@@ -1,13 +1,69 @@
-use serde::Deserialize;
+use crate::domain::client_status::ClientStatus;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(try_from = "String")]
 pub enum TxType {
     Deposit,
     Withdrawal,
+    Transfer,
+    Exchange,
+    Authorize,
+    Capture,
+    Void,
     Dispute,
     Resolve,
     Chargeback,
+    Refund,
+    Reversal,
+    // Charged by the service on a deposit or withdrawal; never read from input.
+    Fee,
+    Unlock,
+    Freeze,
+    Close,
+}
+
+impl TxType {
+    // Status an admin record puts the account in.
+    pub fn target_status(self) -> Option<ClientStatus> {
+        match self {
+            Self::Unlock => Some(ClientStatus::Active),
+            Self::Freeze => Some(ClientStatus::Frozen),
+            Self::Close => Some(ClientStatus::Closed),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TxType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdrawal",
+            Self::Transfer => "transfer",
+            Self::Exchange => "exchange",
+            Self::Authorize => "authorize",
+            Self::Capture => "capture",
+            Self::Void => "void",
+            Self::Dispute => "dispute",
+            Self::Resolve => "resolve",
+            Self::Chargeback => "chargeback",
+            Self::Refund => "refund",
+            Self::Reversal => "reversal",
+            Self::Fee => "fee",
+            Self::Unlock => "unlock",
+            Self::Freeze => "freeze",
+            Self::Close => "close",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Serialize for TxType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
 }
 
 impl TryFrom<String> for TxType {
@@ -17,9 +73,20 @@ impl TryFrom<String> for TxType {
         Ok(match s.as_str() {
             "deposit" => Self::Deposit,
             "withdrawal" => Self::Withdrawal,
+            "transfer" => Self::Transfer,
+            "exchange" => Self::Exchange,
+            "authorize" => Self::Authorize,
+            "capture" => Self::Capture,
+            "void" => Self::Void,
             "dispute" => Self::Dispute,
             "resolve" => Self::Resolve,
             "chargeback" => Self::Chargeback,
+            "refund" => Self::Refund,
+            "reversal" => Self::Reversal,
+            "fee" => Self::Fee,
+            "unlock" => Self::Unlock,
+            "freeze" => Self::Freeze,
+            "close" => Self::Close,
             _ => return Err(anyhow::anyhow!("Invalid transaction type: {}", s)),
         })
     }
@@ -0,0 +1,25 @@
+use crate::domain::amount::Amount;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AmountError {
+    #[error("invalid amount '{value}'")]
+    InvalidFormat { value: String },
+    #[error("amount '{value}' has more than four decimal places")]
+    TooManyDecimals { value: String },
+    #[error("amount overflow")]
+    Overflow,
+    #[error("amount {amount} is not positive")]
+    NotPositive { amount: Amount },
+}
+
+impl AmountError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidFormat { .. } => "invalid_amount",
+            Self::TooManyDecimals { .. } => "too_many_decimals",
+            Self::Overflow => "amount_overflow",
+            Self::NotPositive { .. } => "non_positive_amount",
+        }
+    }
+}
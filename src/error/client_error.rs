@@ -1,4 +1,6 @@
 use crate::domain::client_id::ClientId;
+use crate::domain::client_status::ClientStatus;
+use crate::error::amount_error::AmountError;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -7,6 +9,35 @@ pub enum ClientError {
     NotFound { id: ClientId },
     #[error("error updating funds for client {id} with tx type {tx_type}")]
     FundsUpdateError { id: ClientId, tx_type: String },
+    #[error("account of client {id} is locked")]
+    Locked { id: ClientId },
+    #[error("account of client {id} is frozen")]
+    Frozen { id: ClientId },
+    #[error("account of client {id} is closed")]
+    Closed { id: ClientId },
+    #[error("account of client {id} cannot go from {from} to {to}")]
+    InvalidStatusChange {
+        id: ClientId,
+        from: ClientStatus,
+        to: ClientStatus,
+    },
+    #[error(transparent)]
+    AmountError(#[from] AmountError),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
+
+impl ClientError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound { .. } => "client_not_found",
+            Self::FundsUpdateError { .. } => "insufficient_funds",
+            Self::Locked { .. } => "account_locked",
+            Self::Frozen { .. } => "account_frozen",
+            Self::Closed { .. } => "account_closed",
+            Self::InvalidStatusChange { .. } => "invalid_status_change",
+            Self::AmountError(err) => err.code(),
+            Self::UnexpectedError(_) => "unexpected_error",
+        }
+    }
+}
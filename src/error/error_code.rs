@@ -0,0 +1,121 @@
+use crate::domain::amount::Amount;
+use crate::domain::client_id::ClientId;
+use crate::domain::currency::Currency;
+use crate::domain::tx_id::TxId;
+use crate::domain::tx_type::TxType;
+use crate::error::amount_error::AmountError;
+use crate::error::client_error::ClientError;
+use crate::error::transaction_error::TransactionError;
+use csv::{DeserializeErrorKind, ErrorKind, StringRecord};
+
+// Codes are part of the rejects file format, so existing ones must never change.
+pub fn error_code(err: &anyhow::Error) -> &'static str {
+    if let Some(err) = err.downcast_ref::<ClientError>() {
+        err.code()
+    } else if let Some(err) = err.downcast_ref::<TransactionError>() {
+        err.code()
+    } else if let Some(err) = err.downcast_ref::<AmountError>() {
+        err.code()
+    } else {
+        "unexpected_error"
+    }
+}
+
+// Type conversion errors reach csv as plain messages without the failing column, so the
+// fields are checked one by one against the column they were read from.
+pub fn csv_error_code(
+    err: &csv::Error,
+    headers: &StringRecord,
+    fields: &StringRecord,
+) -> &'static str {
+    match err.kind() {
+        ErrorKind::Deserialize { err, .. } => match err.kind() {
+            DeserializeErrorKind::UnexpectedEndOfRow => "missing_field",
+            _ => invalid_field_code(headers, fields).unwrap_or("invalid_record"),
+        },
+        ErrorKind::Utf8 { .. } => "invalid_encoding",
+        ErrorKind::Io(_) => "io_error",
+        _ => "invalid_record",
+    }
+}
+
+// The position is left out of deserialization messages, the rejects file has its own column.
+pub fn csv_error_message(err: &csv::Error) -> String {
+    match err.kind() {
+        ErrorKind::Deserialize { err, .. } => err.to_string(),
+        _ => err.to_string(),
+    }
+}
+
+fn invalid_field_code(headers: &StringRecord, fields: &StringRecord) -> Option<&'static str> {
+    headers
+        .iter()
+        .zip(fields.iter())
+        .find_map(|(header, field)| match header {
+            "type" => TxType::try_from(field.to_string())
+                .err()
+                .map(|_| "invalid_type"),
+            "client" => ClientId::try_from(field.to_string())
+                .err()
+                .map(|_| "invalid_client"),
+            "tx" => TxId::try_from(field.to_string())
+                .err()
+                .map(|_| "invalid_tx"),
+            "amount" if !field.is_empty() => field.parse::<Amount>().err().map(|err| err.code()),
+            "to" if !field.is_empty() => ClientId::try_from(field.to_string())
+                .err()
+                .map(|_| "invalid_client"),
+            "currency" | "to_currency" if !field.is_empty() => {
+                field.parse::<Currency>().err().map(|_| "invalid_currency")
+            }
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::InputRecord;
+
+    fn parse_error_code(row: &str) -> &'static str {
+        let headers = StringRecord::from(InputRecord::COLUMNS.to_vec());
+        let fields = StringRecord::from(row.split(',').collect::<Vec<_>>());
+        let err = fields
+            .deserialize::<InputRecord>(Some(&headers))
+            .unwrap_err();
+        csv_error_code(&err, &headers, &fields)
+    }
+
+    #[test]
+    fn test_domain_error_codes() {
+        let err = anyhow::Error::from(TransactionError::Duplicate { id: TxId::new(1) });
+        assert_eq!(error_code(&err), "duplicate_transaction");
+
+        let err = anyhow::Error::from(ClientError::NotFound {
+            id: ClientId::new(1),
+        });
+        assert_eq!(error_code(&err), "client_not_found");
+
+        let err = anyhow::Error::from(ClientError::AmountError(AmountError::Overflow));
+        assert_eq!(error_code(&err), "amount_overflow");
+
+        assert_eq!(error_code(&anyhow::anyhow!("boom")), "unexpected_error");
+    }
+
+    #[test]
+    fn test_csv_error_codes() {
+        for (row, code) in [
+            ("payout,1,1,1.0", "invalid_type"),
+            ("deposit,-1,1,1.0", "invalid_client"),
+            ("deposit,1,x,1.0", "invalid_tx"),
+            ("deposit,1,1,1.00001", "too_many_decimals"),
+            ("deposit,1,1,abc", "invalid_amount"),
+            ("deposit,1", "missing_field"),
+            ("transfer,1,1,1.0,x", "invalid_client"),
+            ("deposit,1,1,1.0,,EURO", "invalid_currency"),
+            ("exchange,1,1,1.0,,EUR,U5D", "invalid_currency"),
+        ] {
+            assert_eq!(parse_error_code(row), code, "{row}");
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use csv::ErrorKind;
+use thiserror::Error;
+
+// A row that couldn't be read at all, as opposed to a row read fine but holding invalid
+// fields, which is reported through `CsvRecord::record`.
+#[derive(Debug, Error)]
+pub enum InputError {
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error("invalid JSON: {source}")]
+    Json {
+        line: u64,
+        source: serde_json::Error,
+    },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+impl InputError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Csv(err) => match err.kind() {
+                ErrorKind::Utf8 { .. } => "invalid_encoding",
+                ErrorKind::Io(_) => "io_error",
+                _ => "invalid_record",
+            },
+            Self::Json { source, .. } if source.is_io() => "io_error",
+            Self::Json { .. } => "invalid_json",
+            Self::Io(err) if err.kind() == std::io::ErrorKind::InvalidData => "invalid_encoding",
+            Self::Io(_) => "io_error",
+        }
+    }
+
+    pub fn line(&self) -> u64 {
+        match self {
+            Self::Csv(err) => err.position().map_or(0, |position| position.line()),
+            Self::Json { line, .. } => *line,
+            Self::Io(_) => 0,
+        }
+    }
+}
@@ -1,5 +1,11 @@
+pub mod amount_error;
 pub mod client_error;
+pub mod error_code;
+pub mod input_error;
 pub mod transaction_error;
 
+pub use amount_error::*;
 pub use client_error::*;
+pub use error_code::*;
+pub use input_error::*;
 pub use transaction_error::*;
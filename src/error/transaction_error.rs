@@ -1,14 +1,90 @@
+use crate::domain::client_id::ClientId;
+use crate::domain::currency::Currency;
+use crate::domain::transaction_status::TransactionStatus;
 use crate::domain::tx_id::TxId;
+use crate::error::amount_error::AmountError;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum TransactionError {
     #[error("transaction with id {id} not found")]
     NotFound { id: TxId },
+    #[error("transaction with id {id} already exists")]
+    Duplicate { id: TxId },
+    #[error("transaction with id {id} does not belong to client {client}")]
+    ClientMismatch { id: TxId, client: ClientId },
     #[error("transaction with id {id} not under dispute")]
     NotUnderDispute { id: TxId },
+    #[error("transaction with id {id} is already under dispute")]
+    AlreadyDisputed { id: TxId },
+    #[error("transaction with id {id} has already been resolved")]
+    AlreadyResolved { id: TxId },
+    #[error("transaction with id {id} has already been charged back")]
+    AlreadyChargedBack { id: TxId },
+    #[error("transaction with id {id} cannot be disputed")]
+    DisputeNotAllowed { id: TxId },
+    #[error("transaction with id {id} is not in {currency}")]
+    CurrencyMismatch { id: TxId, currency: Currency },
+    #[error("no rate from {from} to {to} for transaction with id {id}")]
+    RateNotFound {
+        id: TxId,
+        from: Currency,
+        to: Currency,
+    },
+    #[error("exchange with id {id} converts to a zero amount")]
+    ExchangeRoundsToZero { id: TxId },
+    #[error("transaction with id {id} is not an authorization")]
+    NotAnAuthorization { id: TxId },
+    #[error("authorization with id {id} is already {status}")]
+    AuthorizationClosed { id: TxId, status: TransactionStatus },
+    #[error("capture exceeds the amount of authorization with id {id}")]
+    CaptureExceedsAuthorization { id: TxId },
+    #[error("dispute exceeds the undisputed amount of transaction with id {id}")]
+    DisputeExceedsAmount { id: TxId },
+    #[error("amount exceeds the disputed amount of transaction with id {id}")]
+    ExceedsDisputedAmount { id: TxId },
+    #[error("transaction with id {id} cannot be refunded")]
+    NotRefundable { id: TxId },
+    #[error("refund exceeds the refundable amount of transaction with id {id}")]
+    RefundExceedsAmount { id: TxId },
+    #[error("transaction with id {id} cannot be reversed")]
+    NotReversible { id: TxId },
+    #[error("transaction with id {id} has already been reversed")]
+    AlreadyReversed { id: TxId },
     #[error("transaction with id {id} is not a valid transaction")]
     InvalidTransaction { id: TxId },
     #[error(transparent)]
+    AmountError(#[from] AmountError),
+    #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
+
+impl TransactionError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound { .. } => "transaction_not_found",
+            Self::Duplicate { .. } => "duplicate_transaction",
+            Self::ClientMismatch { .. } => "client_mismatch",
+            Self::NotUnderDispute { .. } => "not_under_dispute",
+            Self::AlreadyDisputed { .. } => "already_disputed",
+            Self::AlreadyResolved { .. } => "already_resolved",
+            Self::AlreadyChargedBack { .. } => "already_charged_back",
+            Self::DisputeNotAllowed { .. } => "dispute_not_allowed",
+            Self::CurrencyMismatch { .. } => "currency_mismatch",
+            Self::RateNotFound { .. } => "rate_not_found",
+            Self::ExchangeRoundsToZero { .. } => "exchange_rounds_to_zero",
+            Self::NotAnAuthorization { .. } => "not_an_authorization",
+            Self::AuthorizationClosed { .. } => "authorization_closed",
+            Self::CaptureExceedsAuthorization { .. } => "capture_exceeds_authorization",
+            Self::DisputeExceedsAmount { .. } => "dispute_exceeds_amount",
+            Self::ExceedsDisputedAmount { .. } => "exceeds_disputed_amount",
+            Self::NotRefundable { .. } => "not_refundable",
+            Self::RefundExceedsAmount { .. } => "refund_exceeds_amount",
+            Self::NotReversible { .. } => "not_reversible",
+            Self::AlreadyReversed { .. } => "already_reversed",
+            Self::InvalidTransaction { .. } => "invalid_transaction",
+            Self::AmountError(err) => err.code(),
+            Self::UnexpectedError(_) => "unexpected_error",
+        }
+    }
+}
@@ -1,49 +1,1021 @@
-use rust_exercise::domain::input_record::InputRecord;
-use rust_exercise::repository::client_repository::ClientRepositoryImpl;
-use rust_exercise::repository::transaction_repository::TransactionRepositoryImpl;
+use clap::{Parser, Subcommand};
+use csv::{StringRecord, WriterBuilder};
+use rust_exercise::domain::client_id::ClientId;
+use rust_exercise::domain::currency::Currency;
+use rust_exercise::domain::event::Event;
+use rust_exercise::domain::fee_schedule::FeeSchedule;
+use rust_exercise::domain::hold_expiry::HoldExpiry;
+use rust_exercise::domain::input_format::{InputFormat, RecordReader};
+use rust_exercise::domain::input_record::CsvRecord;
+use rust_exercise::domain::output_format::OutputFormat;
+use rust_exercise::domain::output_record::OutputRecord;
+use rust_exercise::domain::rate::RateTable;
+use rust_exercise::error::error_code::{csv_error_code, csv_error_message, error_code};
+use rust_exercise::error::input_error::InputError;
+use rust_exercise::repository::client_repository::{ClientRepository, ClientRepositoryImpl};
+use rust_exercise::repository::snapshot::{Checkpoint, Snapshot};
+use rust_exercise::repository::sqlite_store::SqliteStore;
+use rust_exercise::repository::transaction_repository::{
+    TransactionRepository, TransactionRepositoryImpl,
+};
+use rust_exercise::service::audit_trail::{StatusChange, audit_trail};
+use rust_exercise::service::event_log::{JsonlEventLog, read_events};
+use rust_exercise::service::follow_reader::FollowReader;
+#[cfg(feature = "http")]
+use rust_exercise::service::http_api::HttpServer;
+use rust_exercise::service::rejects::RejectsWriter;
+use rust_exercise::service::reprocessing::{report_headers, reprocess};
+use rust_exercise::service::service_config::ServiceConfig;
+use rust_exercise::service::sharded_engine::ShardedEngine;
+use rust_exercise::service::socket_server::{Listener, SocketServer};
+use rust_exercise::service::statement::{StatementLine, statement};
+use rust_exercise::service::suspicious_activity::{
+    CsvReporter, NoopReporter, SuspiciousActivityReporter,
+};
 use rust_exercise::service::transaction_service::TransactionService;
-use std::env;
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, Stdin};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{RecvTimeoutError, sync_channel};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Parser)]
+#[command(
+    about = "Process a CSV of transactions and print the resulting client balances",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    args: Args,
+
+    /// Storage backend: `memory` or `sqlite:<path>` (`sqlite::memory:` for an in-memory database)
+    #[arg(long, global = true, default_value = "memory")]
+    store: Store,
+
+    /// CSV file of exchange rates (`pair,rate,effective_from`) used by exchanges and `--report-currency`
+    #[arg(long, global = true, value_name = "PATH")]
+    rates: Option<PathBuf>,
+
+    /// TOML file with the service configuration (dispute policy, default currency, fees, hold expiry)
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Rebuild client and transaction state from an event log and print the resulting client balances
+    Replay {
+        /// Event log written with `--event-log`
+        log: PathBuf,
+
+        /// Format of the printed client balances: `csv` or `jsonl`
+        #[arg(long, value_name = "FORMAT", default_value = "csv")]
+        output_format: OutputFormat,
+
+        /// Print one row per client and currency instead of the balances in the default currency
+        #[arg(long)]
+        per_currency: bool,
+
+        /// Print every client's balances converted into this currency with the rates given with `--rates`
+        #[arg(long, value_name = "CURRENCY", conflicts_with = "per_currency")]
+        report_currency: Option<Currency>,
+    },
+    /// Print every operation of an event log with the client's balances right after it
+    Statement {
+        /// Event log written with `--event-log`
+        log: PathBuf,
+
+        /// Only print the operations of this client
+        #[arg(long, value_name = "ID")]
+        client: Option<u16>,
+    },
+    /// Print every change of account status in an event log: operator unlocks, freezes and closes, and chargeback locks
+    Audit {
+        /// Event log written with `--event-log`
+        log: PathBuf,
+
+        /// Only print the changes of this client
+        #[arg(long, value_name = "ID")]
+        client: Option<u16>,
+    },
+    /// Apply corrected records to a saved state and report which were applied and how balances changed
+    Reprocess {
+        /// CSV (or `.jsonl`) file with the corrected records; a rejects file written with `--rejects` is accepted as is
+        corrections: PathBuf,
+
+        /// Snapshot saved with `--checkpoint` to load and update (in-memory store only)
+        #[arg(long, value_name = "PATH")]
+        state: Option<PathBuf>,
+    },
+    /// Accept transactions and balance queries over a socket, line by line or as HTTP/JSON
+    Serve {
+        /// Address to listen on: `tcp:<host>:<port>`, `unix:<path>` or `http:<host>:<port>` (needs the `http` feature)
+        #[arg(long, value_name = "ADDRESS")]
+        listen: ListenAddress,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+struct Args {
+    /// CSV or JSON Lines file with the transactions to process, or `-` to read from stdin
+    #[arg(required = true)]
+    filename: Option<PathBuf>,
+
+    /// Format of the input: `csv` or `jsonl` (by default from the file extension, CSV otherwise)
+    #[arg(long, value_name = "FORMAT")]
+    input_format: Option<InputFormat>,
+
+    /// Format of the printed client balances: `csv` or `jsonl`
+    #[arg(long, value_name = "FORMAT", default_value = "csv")]
+    output_format: OutputFormat,
+
+    /// Print one row per client and currency instead of the balances in the default currency
+    #[arg(long)]
+    per_currency: bool,
+
+    /// Print every client's balances converted into this currency with the rates given with `--rates`
+    #[arg(long, value_name = "CURRENCY", conflicts_with = "per_currency")]
+    report_currency: Option<Currency>,
+
+    /// Treat a record repeating an already applied deposit or withdrawal as a no-op
+    #[arg(long)]
+    idempotent_replays: bool,
+
+    /// Write disputes, resolves and chargebacks referencing another client's transaction to this CSV file
+    #[arg(long, value_name = "PATH")]
+    suspicious: Option<PathBuf>,
+
+    /// Write every rejected record to this CSV file with its line number, an error code and a message
+    #[arg(long, value_name = "PATH")]
+    rejects: Option<PathBuf>,
+
+    /// Append every accepted and rejected record as an event to this file
+    #[arg(long, value_name = "PATH")]
+    event_log: Option<PathBuf>,
+
+    /// Number of worker threads; clients are sharded across them by ID (in-memory store only)
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+
+    /// Keep reading the file as it grows, like `tail -f`
+    #[arg(long)]
+    follow: bool,
+
+    /// Print the client balances every SECONDS while records keep coming (5 by default with `--follow`)
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    emit_interval: Option<u64>,
+
+    /// Periodically save a snapshot of the state and the input position to this file (in-memory store only)
+    #[arg(long, value_name = "PATH")]
+    checkpoint: Option<PathBuf>,
+
+    /// Number of records between two snapshots
+    #[arg(long, value_name = "RECORDS", default_value_t = 100_000, value_parser = clap::value_parser!(u64).range(1..))]
+    checkpoint_every: u64,
+
+    /// Continue processing the input from a snapshot saved with `--checkpoint`
+    #[arg(long, value_name = "PATH")]
+    resume: Option<PathBuf>,
+}
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const DEFAULT_EMIT_INTERVAL: Duration = Duration::from_secs(5);
+const INPUT_QUEUE_SIZE: usize = 1024;
+
+enum Input {
+    Stdin(Stdin),
+    File(File),
+}
+
+trait SeekRead: Read + Seek + Send {}
+
+impl<R: Read + Seek + Send> SeekRead for R {}
+
+#[derive(Debug, Clone)]
+enum Store {
+    Memory,
+    Sqlite(PathBuf),
+}
+
+impl FromStr for Store {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "memory" => Ok(Store::Memory),
+            Some(("sqlite", path)) if !path.is_empty() => Ok(Store::Sqlite(PathBuf::from(path))),
+            _ => Err(anyhow::anyhow!(
+                "Invalid store '{}', expected 'memory' or 'sqlite:<path>'",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ListenAddress {
+    Tcp(String),
+    Unix(PathBuf),
+    Http(String),
+}
+
+impl FromStr for ListenAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("tcp", address)) if !address.is_empty() => {
+                Ok(ListenAddress::Tcp(address.to_string()))
+            }
+            Some(("unix", path)) if !path.is_empty() => {
+                Ok(ListenAddress::Unix(PathBuf::from(path)))
+            }
+            Some(("http", address)) if !address.is_empty() => {
+                Ok(ListenAddress::Http(address.to_string()))
+            }
+            _ => Err(anyhow::anyhow!(
+                "Invalid address '{}', expected 'tcp:<host>:<port>', 'unix:<path>' or 'http:<host>:<port>'",
+                s
+            )),
+        }
+    }
+}
 
 fn main() -> anyhow::Result<()> {
-    let filename = env::args().nth(1).ok_or_else(|| {
-        anyhow::anyhow!("Missing filename argument. Usage: cargo run -- <filename.csv>")
-    })?;
+    let cli = Cli::parse();
+    let args = &cli.args;
 
-    let file = File::open(&filename)
-        .map_err(|err| anyhow::anyhow!("Error opening file '{}': {}", filename, err))?;
+    let mut config = match &cli.config {
+        Some(path) => ServiceConfig::from_file(path)?,
+        None => ServiceConfig::default(),
+    };
+    config.idempotent_replays |= args.idempotent_replays;
+    if let Some(path) = &cli.rates {
+        config.rates = RateTable::from_file(path)?;
+    }
 
-    let client_repository = ClientRepositoryImpl::new();
-    let transaction_repository = TransactionRepositoryImpl::new();
-    let mut transaction_service =
-        TransactionService::new(client_repository, transaction_repository);
+    let snapshots = args.checkpoint.is_some() || args.resume.is_some();
+    // Records after the last snapshot are processed again on resume, so anything they wrote
+    // to these files would be written twice.
+    if args.resume.is_some() {
+        for (flag, path) in [
+            ("--event-log", &args.event_log),
+            ("--rejects", &args.rejects),
+            ("--suspicious", &args.suspicious),
+        ] {
+            if path.is_some() {
+                anyhow::bail!("{} is not supported together with --resume", flag);
+            }
+        }
+    }
 
-    let records = InputRecord::from_csv(file);
+    if cli.command.is_none() && args.threads > 1 {
+        if args.rejects.is_some() {
+            anyhow::bail!("--rejects is not supported together with --threads");
+        }
+        if args.follow || args.emit_interval.is_some() {
+            anyhow::bail!("--follow and --emit-interval are not supported together with --threads");
+        }
+        // Each shard would pay fees into a house account of its own.
+        if config.fees != FeeSchedule::default() {
+            anyhow::bail!("fees are not supported together with --threads");
+        }
+        // Each shard would count records and expire holds on its own clock.
+        if config.hold_expiry != HoldExpiry::default() {
+            anyhow::bail!("hold expiry is not supported together with --threads");
+        }
+        return match (&cli.store, &args.event_log) {
+            (Store::Memory, None) if !snapshots => run_sharded(config, args),
+            (Store::Memory, None) => Err(anyhow::anyhow!(
+                "--checkpoint and --resume are not supported together with --threads"
+            )),
+            (Store::Memory, Some(_)) => Err(anyhow::anyhow!(
+                "--event-log is not supported together with --threads"
+            )),
+            (Store::Sqlite(_), _) => Err(anyhow::anyhow!(
+                "--threads is only supported with the in-memory store"
+            )),
+        };
+    }
 
-    for r in records {
-        match r {
-            Ok(record) => {
-                if let Err(err) = transaction_service.process_transaction(&record) {
+    if let Some(Command::Statement { log, client }) = &cli.command {
+        return print_statement(log, client.map(ClientId::new));
+    }
+
+    if let Some(Command::Audit { log, client }) = &cli.command {
+        return print_audit_trail(log, client.map(ClientId::new));
+    }
+
+    if let Some(Command::Reprocess {
+        corrections, state, ..
+    }) = &cli.command
+    {
+        return match (&cli.store, state) {
+            (Store::Memory, Some(state)) => {
+                let snapshot = Snapshot::load(state)?;
+                let checkpoint = snapshot.checkpoint;
+                let (clients, transactions) = snapshot.restore();
+                let mut service = TransactionService::with_config(clients, transactions, config);
+                reprocess_corrections(&mut service, corrections)?;
+                Snapshot::capture(
+                    checkpoint,
+                    service.client_repository(),
+                    service.transaction_repository(),
+                )?
+                .save(state)
+            }
+            (Store::Memory, None) => Err(anyhow::anyhow!(
+                "reprocess needs --state with the in-memory store"
+            )),
+            (Store::Sqlite(path), None) => {
+                let store = open_sqlite(path)?;
+                reprocess_corrections(
+                    &mut TransactionService::with_config(
+                        store.client_repository(),
+                        store.transaction_repository(),
+                        config,
+                    ),
+                    corrections,
+                )
+            }
+            (Store::Sqlite(_), Some(_)) => Err(anyhow::anyhow!(
+                "--state is only supported with the in-memory store"
+            )),
+        };
+    }
+
+    if let Some(Command::Serve { listen, .. }) = &cli.command {
+        return match &cli.store {
+            Store::Memory => serve(
+                TransactionService::with_config(
+                    ClientRepositoryImpl::new(),
+                    TransactionRepositoryImpl::new(),
+                    config,
+                ),
+                listen,
+            ),
+            Store::Sqlite(path) => {
+                let store = open_sqlite(path)?;
+                serve(
+                    TransactionService::with_config(
+                        store.client_repository(),
+                        store.transaction_repository(),
+                        config,
+                    ),
+                    listen,
+                )
+            }
+        };
+    }
+
+    match &cli.store {
+        Store::Memory => {
+            let (clients, transactions, start) = match &args.resume {
+                Some(path) => {
+                    let snapshot = Snapshot::load(path)?;
+                    let checkpoint = snapshot.checkpoint;
+                    let (clients, transactions) = snapshot.restore();
+                    (clients, transactions, Some(checkpoint))
+                }
+                None => (
+                    ClientRepositoryImpl::new(),
+                    TransactionRepositoryImpl::new(),
+                    None,
+                ),
+            };
+
+            let mut since_snapshot = 0;
+            run(
+                TransactionService::with_config(clients, transactions, config),
+                &cli,
+                start,
+                |service, checkpoint, finished| {
+                    since_snapshot += 1;
+                    match &args.checkpoint {
+                        Some(path) if finished || since_snapshot >= args.checkpoint_every => {
+                            since_snapshot = 0;
+                            Snapshot::capture(
+                                checkpoint,
+                                service.client_repository(),
+                                service.transaction_repository(),
+                            )?
+                            .save(path)
+                        }
+                        _ => Ok(()),
+                    }
+                },
+            )
+        }
+        Store::Sqlite(_) if snapshots => Err(anyhow::anyhow!(
+            "--checkpoint and --resume are only supported with the in-memory store"
+        )),
+        Store::Sqlite(path) => {
+            let store = open_sqlite(path)?;
+            run(
+                TransactionService::with_config(
+                    store.client_repository(),
+                    store.transaction_repository(),
+                    config,
+                ),
+                &cli,
+                None,
+                |_, _, _| Ok(()),
+            )
+        }
+    }
+}
+
+fn run<T, V>(
+    transaction_service: TransactionService<T, V>,
+    cli: &Cli,
+    start: Option<Checkpoint>,
+    on_progress: impl FnMut(&TransactionService<T, V>, Checkpoint, bool) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    match &cli.command {
+        Some(Command::Replay {
+            log,
+            output_format,
+            per_currency,
+            report_currency,
+        }) => replay(
+            transaction_service,
+            log,
+            *output_format,
+            BalanceView::new(*per_currency, *report_currency),
+        ),
+        Some(
+            Command::Statement { .. }
+            | Command::Audit { .. }
+            | Command::Reprocess { .. }
+            | Command::Serve { .. },
+        ) => {
+            unreachable!("statement, audit, reprocess and serve are handled before run")
+        }
+        None => process(transaction_service, &cli.args, start, on_progress),
+    }
+}
+
+fn serve<T, V>(
+    transaction_service: TransactionService<T, V>,
+    address: &ListenAddress,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository + Send + 'static,
+    V: TransactionRepository + Send + 'static,
+{
+    match address {
+        ListenAddress::Tcp(address) => {
+            SocketServer::new(transaction_service).serve(Listener::Tcp(bind_tcp(address)?))
+        }
+        #[cfg(unix)]
+        ListenAddress::Unix(path) => {
+            let listener = UnixListener::bind(path).map_err(|err| {
+                anyhow::anyhow!("Error listening on '{}': {}", path.display(), err)
+            })?;
+            SocketServer::new(transaction_service).serve(Listener::Unix(listener))
+        }
+        #[cfg(not(unix))]
+        ListenAddress::Unix(_) => Err(anyhow::anyhow!(
+            "Unix domain sockets are not supported on this platform"
+        )),
+        #[cfg(feature = "http")]
+        ListenAddress::Http(address) => {
+            HttpServer::new(transaction_service).serve(bind_tcp(address)?)
+        }
+        #[cfg(not(feature = "http"))]
+        ListenAddress::Http(address) => Err(anyhow::anyhow!(
+            "Cannot listen on '{}': HTTP support is not enabled, rebuild with `--features http`",
+            address
+        )),
+    }
+}
+
+fn bind_tcp(address: &str) -> anyhow::Result<TcpListener> {
+    TcpListener::bind(address)
+        .map_err(|err| anyhow::anyhow!("Error listening on '{}': {}", address, err))
+}
+
+fn open_sqlite(path: &Path) -> anyhow::Result<SqliteStore> {
+    if path.as_os_str() == ":memory:" {
+        SqliteStore::open_in_memory()
+    } else {
+        SqliteStore::open(path)
+    }
+}
+
+fn process<T, V>(
+    mut transaction_service: TransactionService<T, V>,
+    args: &Args,
+    start: Option<Checkpoint>,
+    mut on_progress: impl FnMut(&TransactionService<T, V>, Checkpoint, bool) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    transaction_service.set_reporter(reporter(args)?);
+    if let Some(path) = &args.event_log {
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| anyhow::anyhow!("Error opening file '{}': {}", path.display(), err))?;
+        transaction_service.set_event_log(Box::new(JsonlEventLog::new(log)));
+    }
+
+    match open_input(args)? {
+        Input::Stdin(stdin) => {
+            if start.is_some() {
+                anyhow::bail!("--resume needs a file as input, not stdin");
+            }
+            if args.follow {
+                anyhow::bail!("--follow needs a file as input, not stdin");
+            }
+            let records = input_format(args).records(stdin)?;
+            consume(&mut transaction_service, records, args, &mut on_progress)?;
+        }
+        Input::File(file) => {
+            let reader: Box<dyn SeekRead> = if args.follow {
+                Box::new(FollowReader::new(file, FOLLOW_POLL_INTERVAL))
+            } else {
+                Box::new(file)
+            };
+            let records = match start {
+                Some(checkpoint) => input_format(args).records_at(reader, checkpoint.into())?,
+                None => input_format(args).records(reader)?,
+            };
+            consume(&mut transaction_service, records, args, &mut on_progress)?;
+        }
+    }
+
+    print_clients(
+        &transaction_service,
+        args.output_format,
+        args.balance_view(),
+    )
+}
+
+fn consume<T, V>(
+    transaction_service: &mut TransactionService<T, V>,
+    records: Box<dyn RecordReader + Send>,
+    args: &Args,
+    on_progress: &mut impl FnMut(&TransactionService<T, V>, Checkpoint, bool) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let headers = records.headers().clone();
+    let mut rejects = match &args.rejects {
+        Some(path) => {
+            let mut rejects = RejectsWriter::new(create_output(path)?);
+            rejects.write_header(&headers)?;
+            Some(rejects)
+        }
+        None => None,
+    };
+
+    let mut handle = |transaction_service: &mut TransactionService<T, V>,
+                      r: Result<CsvRecord, InputError>|
+     -> anyhow::Result<()> {
+        let rejected = match r {
+            Ok(CsvRecord {
+                line,
+                fields,
+                record: Ok(record),
+            }) => match transaction_service.process_transaction(&record) {
+                Ok(_) => None,
+                Err(err) => {
                     eprintln!("Skipping transaction {}: {}", record.tx, err);
+                    Some((line, fields, error_code(&err), err.to_string()))
                 }
+            },
+            Ok(CsvRecord {
+                line,
+                fields,
+                record: Err(err),
+            }) => {
+                eprintln!("Error parsing record: {}", err);
+                let code = csv_error_code(&err, &headers, &fields);
+                Some((line, fields, code, csv_error_message(&err)))
             }
             Err(err) => {
                 eprintln!("Error parsing record: {}", err);
+                Some((err.line(), StringRecord::new(), err.code(), err.to_string()))
+            }
+        };
+
+        if let (Some(rejects), Some((line, fields, code, message))) = (&mut rejects, rejected) {
+            rejects.reject(line, &fields, code, &message)?;
+        }
+        Ok(())
+    };
+
+    let mut last_checkpoint = None;
+    match emit_interval(args) {
+        None => {
+            let mut records = records;
+            while let Some(r) = records.next() {
+                handle(transaction_service, r)?;
+                let checkpoint = Checkpoint::from(records.next_position());
+                on_progress(transaction_service, checkpoint, false)?;
+                last_checkpoint = Some(checkpoint);
+            }
+        }
+        // Records are read on their own thread so that the client state can still be
+        // emitted on time while the input is waiting for more data.
+        Some(interval) => {
+            let (sender, receiver) = sync_channel(INPUT_QUEUE_SIZE);
+            thread::spawn(move || {
+                let mut records = records;
+                while let Some(r) = records.next() {
+                    let checkpoint = Checkpoint::from(records.next_position());
+                    if sender.send((r, checkpoint)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            let mut last_emit = Instant::now();
+            let mut changed = false;
+            loop {
+                match receiver.recv_timeout(interval.saturating_sub(last_emit.elapsed())) {
+                    Ok((r, checkpoint)) => {
+                        handle(transaction_service, r)?;
+                        on_progress(transaction_service, checkpoint, false)?;
+                        last_checkpoint = Some(checkpoint);
+                        changed = true;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                if last_emit.elapsed() >= interval {
+                    if changed {
+                        print_clients(
+                            transaction_service,
+                            args.output_format,
+                            args.balance_view(),
+                        )?;
+                        changed = false;
+                    }
+                    last_emit = Instant::now();
+                }
             }
         }
     }
 
-    println!("client,available,held,total,locked");
-    for c in transaction_service.get_all_clients() {
+    if let Some(checkpoint) = last_checkpoint {
+        on_progress(transaction_service, checkpoint, true)?;
+    }
+
+    Ok(())
+}
+
+fn replay<T, V>(
+    mut transaction_service: TransactionService<T, V>,
+    log: &Path,
+    output_format: OutputFormat,
+    view: BalanceView,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let file = File::open(log)
+        .map_err(|err| anyhow::anyhow!("Error opening file '{}': {}", log.display(), err))?;
+
+    for (line, event) in read_events(BufReader::new(file)).enumerate() {
+        let event = event.map_err(|err| {
+            anyhow::anyhow!(
+                "Error reading event {} of '{}': {}",
+                line + 1,
+                log.display(),
+                err
+            )
+        })?;
+        transaction_service.apply_event(&event).map_err(|err| {
+            anyhow::anyhow!(
+                "Error replaying event {} of '{}': {}",
+                line + 1,
+                log.display(),
+                err
+            )
+        })?;
+    }
+
+    print_clients(&transaction_service, output_format, view)
+}
+
+fn log_events(log: &Path) -> anyhow::Result<impl Iterator<Item = anyhow::Result<Event>> + '_> {
+    let file = File::open(log)
+        .map_err(|err| anyhow::anyhow!("Error opening file '{}': {}", log.display(), err))?;
+    Ok(read_events(BufReader::new(file))
+        .enumerate()
+        .map(move |(line, event)| {
+            event.map_err(|err| {
+                anyhow::anyhow!(
+                    "Error reading event {} of '{}': {}",
+                    line + 1,
+                    log.display(),
+                    err
+                )
+            })
+        }))
+}
+
+fn print_statement(log: &Path, client: Option<ClientId>) -> anyhow::Result<()> {
+    let lines = statement(log_events(log)?, client)?;
+
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(io::stdout().lock());
+    writer.write_record(StatementLine::COLUMNS)?;
+    for line in lines {
+        writer.serialize(line)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn print_audit_trail(log: &Path, client: Option<ClientId>) -> anyhow::Result<()> {
+    let changes = audit_trail(log_events(log)?, client)?;
+
+    let mut writer = WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(io::stdout().lock());
+    writer.write_record(StatusChange::COLUMNS)?;
+    for change in changes {
+        writer.serialize(change)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn reprocess_corrections<T, V>(
+    transaction_service: &mut TransactionService<T, V>,
+    corrections: &Path,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let file = File::open(corrections).map_err(|err| {
+        anyhow::anyhow!("Error opening file '{}': {}", corrections.display(), err)
+    })?;
+    let records = InputFormat::from_path(corrections)
+        .unwrap_or(InputFormat::Csv)
+        .records(file)?;
+    let headers = report_headers(records.headers());
+    let report = reprocess(transaction_service, records)?;
+
+    let mut writer = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(io::stdout());
+    writer.write_record(&headers)?;
+    for correction in &report.corrections {
+        let line = correction.line.to_string();
+        let (result, code, message) = match &correction.rejection {
+            Some(rejection) => ("rejected", rejection.code, rejection.message.as_str()),
+            None => ("applied", "", ""),
+        };
+        writer.write_record(
+            [line.as_str(), result, code, message]
+                .into_iter()
+                .chain(&correction.fields),
+        )?;
+    }
+    writer.flush()?;
+
+    println!();
+    println!("client,currency,available,held,total,locked");
+    for delta in &report.deltas {
         println!(
-            "{},{},{},{},{}",
-            c.client,
-            c.available.get(),
-            c.held.get(),
-            c.total.get(),
-            c.locked
+            "{},{},{},{},{},{}",
+            delta.client, delta.currency, delta.available, delta.held, delta.total, delta.locked
         );
     }
 
     Ok(())
 }
+
+fn open_input(args: &Args) -> anyhow::Result<Input> {
+    let filename = args
+        .filename
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Missing CSV file to process"))?;
+    if filename.as_os_str() == "-" {
+        return Ok(Input::Stdin(io::stdin()));
+    }
+    File::open(filename)
+        .map(Input::File)
+        .map_err(|err| anyhow::anyhow!("Error opening file '{}': {}", filename.display(), err))
+}
+
+// `--input-format` wins over the file extension; stdin and unknown extensions are read as CSV.
+fn input_format(args: &Args) -> InputFormat {
+    args.input_format
+        .or_else(|| args.filename.as_deref().and_then(InputFormat::from_path))
+        .unwrap_or(InputFormat::Csv)
+}
+
+fn emit_interval(args: &Args) -> Option<Duration> {
+    match (args.emit_interval, args.follow) {
+        (Some(seconds), _) => Some(Duration::from_secs(seconds)),
+        (None, true) => Some(DEFAULT_EMIT_INTERVAL),
+        (None, false) => None,
+    }
+}
+
+fn run_sharded(config: ServiceConfig, args: &Args) -> anyhow::Result<()> {
+    let config_currency = config.default_currency;
+    let (rates, rounding) = (config.rates.clone(), config.rounding);
+    let mut engine = ShardedEngine::new(args.threads, config, reporter(args)?)?;
+
+    let reader: Box<dyn Read + Send> = match open_input(args)? {
+        Input::Stdin(stdin) => Box::new(stdin),
+        Input::File(file) => Box::new(file),
+    };
+
+    for r in input_format(args).records(reader)? {
+        match r {
+            Ok(CsvRecord {
+                record: Ok(record), ..
+            }) => engine.process(record)?,
+            Ok(CsvRecord {
+                record: Err(err), ..
+            }) => eprintln!("Error parsing record: {}", err),
+            Err(err) => eprintln!("Error parsing record: {}", err),
+        }
+    }
+
+    let output = engine.finish()?;
+    for e in output.errors {
+        eprintln!("Skipping transaction {}: {}", e.tx, e.error);
+    }
+    let view = args.balance_view();
+    let clients = output
+        .clients
+        .iter()
+        .map(|client| match view {
+            BalanceView::Default => Ok(vec![OutputRecord::new(client, config_currency)]),
+            BalanceView::PerCurrency => Ok(OutputRecord::per_currency(client, config_currency)),
+            BalanceView::Consolidated(currency) => {
+                OutputRecord::consolidated(client, currency, &rates, rounding).map(|r| vec![r])
+            }
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    write_clients(clients.into_iter().flatten(), args.output_format, view)
+}
+
+fn reporter(args: &Args) -> anyhow::Result<Box<dyn SuspiciousActivityReporter + Send>> {
+    match &args.suspicious {
+        Some(path) => Ok(Box::new(CsvReporter::new(create_output(path)?))),
+        None => Ok(Box::new(NoopReporter)),
+    }
+}
+
+fn create_output(path: &Path) -> anyhow::Result<File> {
+    File::create(path)
+        .map_err(|err| anyhow::anyhow!("Error creating file '{}': {}", path.display(), err))
+}
+
+// Which balances are printed at the end of a run.
+#[derive(Debug, Clone, Copy)]
+enum BalanceView {
+    Default,
+    PerCurrency,
+    Consolidated(Currency),
+}
+
+impl BalanceView {
+    fn new(per_currency: bool, report_currency: Option<Currency>) -> Self {
+        match (per_currency, report_currency) {
+            (_, Some(currency)) => Self::Consolidated(currency),
+            (true, None) => Self::PerCurrency,
+            (false, None) => Self::Default,
+        }
+    }
+}
+
+impl Args {
+    fn balance_view(&self) -> BalanceView {
+        BalanceView::new(self.per_currency, self.report_currency)
+    }
+}
+
+fn print_clients<T, V>(
+    transaction_service: &TransactionService<T, V>,
+    format: OutputFormat,
+    view: BalanceView,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    match view {
+        BalanceView::Default => write_clients(transaction_service.get_all_clients()?, format, view),
+        BalanceView::PerCurrency => {
+            write_clients(transaction_service.get_all_balances()?, format, view)
+        }
+        BalanceView::Consolidated(currency) => write_clients(
+            transaction_service.get_consolidated_balances(currency)?,
+            format,
+            view,
+        ),
+    }
+}
+
+// Clients come in ID order from every repository and from the sharded engine.
+fn write_clients(
+    clients: impl IntoIterator<Item = OutputRecord>,
+    format: OutputFormat,
+    view: BalanceView,
+) -> anyhow::Result<()> {
+    let with_currency = !matches!(view, BalanceView::Default);
+    let mut writer = format.writer(io::stdout().lock(), with_currency)?;
+    for c in clients {
+        writer.write(&c)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Args {
+        let argv = ["rust-exercise"].iter().chain(flags);
+        Cli::try_parse_from(argv).unwrap().args
+    }
+
+    fn process_args(args: &Args, start: Option<Checkpoint>) -> anyhow::Result<()> {
+        let service = TransactionService::new(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+        );
+        process(service, args, start, |_, _, _| Ok(()))
+    }
+
+    #[test]
+    fn test_follow_and_resume_need_a_file() {
+        let err = process_args(&args(&["--follow", "-"]), None).unwrap_err();
+        assert_eq!(err.to_string(), "--follow needs a file as input, not stdin");
+
+        let start = Checkpoint {
+            byte: 0,
+            line: 1,
+            record: 0,
+        };
+        let err = process_args(&args(&["-"]), Some(start)).unwrap_err();
+        assert_eq!(err.to_string(), "--resume needs a file as input, not stdin");
+    }
+
+    #[test]
+    fn test_missing_input_file_is_an_error() {
+        let path = std::env::temp_dir().join(format!("missing-{}.csv", std::process::id()));
+        let err = process_args(&args(&[path.to_str().unwrap()]), None).unwrap_err();
+        assert!(err.to_string().starts_with("Error opening file"), "{err}");
+    }
+
+    #[test]
+    fn test_emit_interval() {
+        assert_eq!(emit_interval(&args(&["in.csv"])), None);
+        assert_eq!(
+            emit_interval(&args(&["--follow", "in.csv"])),
+            Some(DEFAULT_EMIT_INTERVAL)
+        );
+        assert_eq!(
+            emit_interval(&args(&["--emit-interval", "2", "in.csv"])),
+            Some(Duration::from_secs(2))
+        );
+
+        let argv = ["rust-exercise", "--emit-interval", "0", "in.csv"];
+        assert!(Cli::try_parse_from(argv).is_err());
+    }
+
+    #[test]
+    fn test_reprocess_needs_a_corrections_file() {
+        let path = std::env::temp_dir().join(format!("missing-{}.csv", std::process::id()));
+        let mut service = TransactionService::new(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+        );
+
+        let err = reprocess_corrections(&mut service, &path).unwrap_err();
+
+        assert!(err.to_string().starts_with("Error opening file"), "{err}");
+    }
+}
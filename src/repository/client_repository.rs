@@ -1,49 +1,108 @@
 use crate::domain::client::Client;
 use crate::domain::client_id::ClientId;
 use crate::error::ClientError;
-use std::collections::HashMap;
+use crate::repository::unit_of_work::UnitOfWork;
+use std::collections::BTreeMap;
 
-pub trait ClientRepository {
+pub trait ClientRepository: UnitOfWork {
     fn get_client(&self, id: &ClientId) -> anyhow::Result<Client, ClientError>;
     fn create_client(&mut self, client: &Client) -> anyhow::Result<Client, ClientError>;
     fn update_client(&mut self, client: &Client) -> anyhow::Result<Client, ClientError>;
 
-    fn get_all_clients(&self) -> impl Iterator<Item = &Client>;
+    // In client ID order.
+    fn get_all_clients(&self) -> anyhow::Result<impl Iterator<Item = Client>, ClientError>;
 }
 
 #[derive(Debug, Clone)]
 pub struct ClientRepositoryImpl {
-    clients: HashMap<ClientId, Client>,
+    clients: BTreeMap<ClientId, Client>,
+    journal: Option<Vec<(ClientId, Option<Client>)>>,
+}
+
+impl Default for ClientRepositoryImpl {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ClientRepositoryImpl {
     pub fn new() -> Self {
         Self {
-            clients: HashMap::new(),
+            clients: BTreeMap::new(),
+            journal: None,
+        }
+    }
+
+    pub fn from_clients(clients: impl IntoIterator<Item = Client>) -> Self {
+        Self {
+            clients: clients
+                .into_iter()
+                .map(|client| (client.id, client))
+                .collect(),
+            journal: None,
+        }
+    }
+
+    fn store(&mut self, client: &Client) {
+        let previous = self.clients.insert(client.id, client.clone());
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push((client.id, previous));
         }
     }
 }
 
+impl UnitOfWork for ClientRepositoryImpl {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        if self.journal.is_some() {
+            anyhow::bail!("Unit of work already in progress");
+        }
+        self.journal = Some(Vec::new());
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        self.journal
+            .take()
+            .map(|_| ())
+            .ok_or_else(|| anyhow::anyhow!("No unit of work in progress"))
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        let journal = self
+            .journal
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("No unit of work in progress"))?;
+
+        for (id, previous) in journal.into_iter().rev() {
+            match previous {
+                Some(client) => self.clients.insert(id, client),
+                None => self.clients.remove(&id),
+            };
+        }
+        Ok(())
+    }
+}
+
 impl ClientRepository for ClientRepositoryImpl {
     fn get_client(&self, id: &ClientId) -> anyhow::Result<Client, ClientError> {
         self.clients
             .get(id)
             .cloned()
-            .ok_or(ClientError::NotFound { id: id.clone() })
+            .ok_or(ClientError::NotFound { id: *id })
     }
 
     fn create_client(&mut self, client: &Client) -> anyhow::Result<Client, ClientError> {
-        self.clients.insert(client.id.clone(), client.clone());
+        self.store(client);
         Ok(client.clone())
     }
 
     fn update_client(&mut self, client: &Client) -> anyhow::Result<Client, ClientError> {
-        self.clients.insert(client.id.clone(), client.clone());
+        self.store(client);
         Ok(client.clone())
     }
 
-    fn get_all_clients(&self) -> impl Iterator<Item = &Client> {
-        self.clients.values()
+    fn get_all_clients(&self) -> anyhow::Result<impl Iterator<Item = Client>, ClientError> {
+        Ok(self.clients.values().cloned())
     }
 }
 
@@ -52,6 +111,7 @@ mod tests {
     use super::*;
     use crate::domain::client::Client;
     use crate::domain::client_id::ClientId;
+    use crate::domain::currency::Currency;
 
     fn create_test_client(id: &str) -> Client {
         Client::new(ClientId::try_from(id.to_string()).unwrap())
@@ -59,9 +119,18 @@ mod tests {
 
     fn assert_clients_equal(client1: &Client, client2: &Client) {
         assert_eq!(client1.id, client2.id);
-        assert_eq!(client1.available.get(), client2.available.get());
-        assert_eq!(client1.held.get(), client2.held.get());
-        assert_eq!(client1.total.get(), client2.total.get());
+        assert_eq!(
+            client1.balance(Currency::EUR).available.get(),
+            client2.balance(Currency::EUR).available.get()
+        );
+        assert_eq!(
+            client1.balance(Currency::EUR).held.get(),
+            client2.balance(Currency::EUR).held.get()
+        );
+        assert_eq!(
+            client1.balance(Currency::EUR).total.get(),
+            client2.balance(Currency::EUR).total.get()
+        );
         assert_eq!(client1.status, client2.status);
     }
 
@@ -80,10 +149,12 @@ mod tests {
     fn test_update_client() {
         let mut repo = ClientRepositoryImpl::new();
         let client = create_test_client("1");
-        repo.clients.insert(client.id.clone(), client.clone());
+        repo.clients.insert(client.id, client.clone());
 
         let mut updated_client = client.clone();
-        updated_client.deposit(100.0).unwrap();
+        updated_client
+            .deposit(Currency::EUR, "100".parse().unwrap())
+            .unwrap();
 
         let result = repo.update_client(&updated_client).unwrap();
 
@@ -94,7 +165,7 @@ mod tests {
     fn test_get_client_success() {
         let mut repo = ClientRepositoryImpl::new();
         let client = create_test_client("1");
-        repo.clients.insert(client.id.clone(), client.clone());
+        repo.clients.insert(client.id, client.clone());
 
         let result = repo.get_client(&client.id).unwrap();
 
@@ -117,13 +188,53 @@ mod tests {
         let client1 = create_test_client("1");
         let client2 = create_test_client("2");
 
-        repo.clients.insert(client1.id.clone(), client1.clone());
-        repo.clients.insert(client2.id.clone(), client2.clone());
+        repo.clients.insert(client2.id, client2.clone());
+        repo.clients.insert(client1.id, client1.clone());
+
+        let ids: Vec<ClientId> = repo.get_all_clients().unwrap().map(|c| c.id).collect();
+
+        assert_eq!(ids, vec![client1.id, client2.id]);
+    }
+
+    #[test]
+    fn test_rollback_restores_previous_state() {
+        let mut repo = ClientRepositoryImpl::new();
+        let client = create_test_client("1");
+        repo.create_client(&client).unwrap();
+
+        repo.begin().unwrap();
+        let mut updated_client = client.clone();
+        updated_client
+            .deposit(Currency::EUR, "100".parse().unwrap())
+            .unwrap();
+        repo.update_client(&updated_client).unwrap();
+        repo.create_client(&create_test_client("2")).unwrap();
+        repo.rollback().unwrap();
+
+        let clients: Vec<Client> = repo.get_all_clients().unwrap().collect();
+        assert_eq!(clients.len(), 1);
+        assert_clients_equal(&repo.get_client(&client.id).unwrap(), &client);
+    }
+
+    #[test]
+    fn test_commit_keeps_changes() {
+        let mut repo = ClientRepositoryImpl::new();
+        let client = create_test_client("1");
+
+        repo.begin().unwrap();
+        repo.create_client(&client).unwrap();
+        repo.commit().unwrap();
+
+        assert_clients_equal(&repo.get_client(&client.id).unwrap(), &client);
+        assert!(repo.rollback().is_err());
+    }
+
+    #[test]
+    fn test_begin_twice_fails() {
+        let mut repo = ClientRepositoryImpl::new();
 
-        let clients: Vec<&Client> = repo.get_all_clients().collect();
+        repo.begin().unwrap();
 
-        assert_eq!(clients.len(), 2);
-        assert!(clients.iter().any(|c| c.id == client1.id));
-        assert!(clients.iter().any(|c| c.id == client2.id));
+        assert!(repo.begin().is_err());
     }
 }
@@ -1,5 +1,15 @@
 pub mod client_repository;
+pub mod snapshot;
+pub mod sqlite_client_repository;
+pub mod sqlite_store;
+pub mod sqlite_transaction_repository;
 pub mod transaction_repository;
+pub mod unit_of_work;
 
 pub use client_repository::*;
+pub use snapshot::*;
+pub use sqlite_client_repository::*;
+pub use sqlite_store::*;
+pub use sqlite_transaction_repository::*;
 pub use transaction_repository::*;
+pub use unit_of_work::*;
@@ -0,0 +1,188 @@
+use crate::domain::client::Client;
+use crate::domain::transaction::Transaction;
+use crate::repository::client_repository::{ClientRepository, ClientRepositoryImpl};
+use crate::repository::transaction_repository::TransactionRepositoryImpl;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+// Position in the input right after the last record included in a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub byte: u64,
+    pub line: u64,
+    pub record: u64,
+}
+
+impl From<&csv::Position> for Checkpoint {
+    fn from(position: &csv::Position) -> Self {
+        Self {
+            byte: position.byte(),
+            line: position.line(),
+            record: position.record(),
+        }
+    }
+}
+
+impl From<Checkpoint> for csv::Position {
+    fn from(checkpoint: Checkpoint) -> Self {
+        let mut position = csv::Position::new();
+        position
+            .set_byte(checkpoint.byte)
+            .set_line(checkpoint.line)
+            .set_record(checkpoint.record);
+        position
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub checkpoint: Checkpoint,
+    clients: Vec<Client>,
+    transactions: Vec<Transaction>,
+    #[serde(default)]
+    fees: Vec<Transaction>,
+    // Snapshots taken before hold expiry was persisted have no record count.
+    #[serde(default)]
+    records: u64,
+}
+
+impl Snapshot {
+    // Must be taken between two records, never while a unit of work is open.
+    pub fn capture(
+        checkpoint: Checkpoint,
+        client_repository: &ClientRepositoryImpl,
+        transaction_repository: &TransactionRepositoryImpl,
+    ) -> anyhow::Result<Self> {
+        let clients: Vec<Client> = client_repository.get_all_clients()?.collect();
+        // Kept in creation order, which `restore` rebuilds the client histories from.
+        let transactions: Vec<Transaction> = transaction_repository
+            .get_all_transactions()
+            .cloned()
+            .collect();
+        let mut fees: Vec<Transaction> = transaction_repository.get_all_fees().cloned().collect();
+        fees.sort_by_key(|fee| fee.id.get());
+
+        Ok(Self {
+            checkpoint,
+            clients,
+            transactions,
+            fees,
+            records: transaction_repository.record_count(),
+        })
+    }
+
+    pub fn restore(self) -> (ClientRepositoryImpl, TransactionRepositoryImpl) {
+        (
+            ClientRepositoryImpl::from_clients(self.clients),
+            TransactionRepositoryImpl::from_transactions(self.transactions)
+                .with_fees(self.fees)
+                .with_record_count(self.records),
+        )
+    }
+
+    // Written next to the target and renamed over it, so a crash mid-write never leaves
+    // a truncated snapshot behind.
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path).map_err(|err| {
+            anyhow::anyhow!("Error opening snapshot '{}': {}", path.display(), err)
+        })?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::currency::Currency;
+    use crate::domain::{ClientId, ClientStatus, TransactionStatus, TxId, TxType};
+    use crate::repository::transaction_repository::TransactionRepository;
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint {
+            byte: 42,
+            line: 3,
+            record: 2,
+        }
+    }
+
+    fn repositories() -> (ClientRepositoryImpl, TransactionRepositoryImpl) {
+        let mut client = Client::new(ClientId::new(1));
+        client
+            .deposit(Currency::EUR, "10.5".parse().unwrap())
+            .unwrap();
+        client.status = ClientStatus::Locked;
+        let mut transaction = Transaction::new(
+            TxId::new(7),
+            client.id,
+            "10.5".parse().unwrap(),
+            TxType::Deposit,
+        );
+        transaction.status = TransactionStatus::ChargedBack;
+        let fee = Transaction::fee(&transaction, ClientId::new(9000), "0.5".parse().unwrap());
+
+        (
+            ClientRepositoryImpl::from_clients([client]),
+            TransactionRepositoryImpl::from_transactions([transaction])
+                .with_fees([fee])
+                .with_record_count(5),
+        )
+    }
+
+    #[test]
+    fn test_checkpoint_position_round_trip() {
+        let position = csv::Position::from(checkpoint());
+
+        assert_eq!(Checkpoint::from(&position), checkpoint());
+    }
+
+    #[test]
+    fn test_save_and_load_restores_repositories() {
+        let (clients, transactions) = repositories();
+        let snapshot = Snapshot::capture(checkpoint(), &clients, &transactions).unwrap();
+        let path = std::env::temp_dir().join(format!("snapshot-{}.json", std::process::id()));
+
+        snapshot.save(&path).unwrap();
+        let loaded = Snapshot::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.checkpoint, checkpoint());
+        let (clients, transactions) = loaded.restore();
+        let client = clients.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(
+            client.balance(Currency::EUR).available.get(),
+            "10.5".parse().unwrap()
+        );
+        assert_eq!(
+            client.balance(Currency::EUR).total.get(),
+            "10.5".parse().unwrap()
+        );
+        assert_eq!(client.status, ClientStatus::Locked);
+        let transaction = transactions.get_transaction(&TxId::new(7)).unwrap();
+        assert_eq!(transaction.client_id, client.id);
+        assert_eq!(transaction.status, TransactionStatus::ChargedBack);
+        let fee = transactions.get_fee(&TxId::new(7)).unwrap();
+        assert_eq!(fee.destination_id, Some(ClientId::new(9000)));
+        assert_eq!(transactions.get_record_count().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_load_missing_file_fails() {
+        let path = std::env::temp_dir().join("missing-snapshot.json");
+
+        assert!(Snapshot::load(&path).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::domain::amount::Amount;
+use crate::domain::amounts::{AvailableAmount, Balance, HeldAmount, TotalAmount};
+use crate::domain::client::Client;
+use crate::domain::client_id::ClientId;
+use crate::domain::client_status::ClientStatus;
+use crate::domain::currency::Currency;
+use crate::error::ClientError;
+use crate::repository::client_repository::ClientRepository;
+use crate::repository::sqlite_store::{
+    SharedConnection, begin_savepoint, conversion_error, lock, release_savepoint,
+    rollback_savepoint,
+};
+use crate::repository::unit_of_work::UnitOfWork;
+use rusqlite::{Row, params};
+
+#[derive(Debug, Clone)]
+pub struct SqliteClientRepository {
+    connection: SharedConnection,
+}
+
+impl SqliteClientRepository {
+    pub fn new(connection: SharedConnection) -> Self {
+        Self { connection }
+    }
+
+    // Balances are never removed from a client, so upserting the ones it has is enough.
+    fn upsert(&self, client: &Client) -> anyhow::Result<()> {
+        let connection = lock(&self.connection)?;
+        connection.execute(
+            "INSERT INTO clients (id, status) VALUES (?1, ?2)
+             ON CONFLICT (id) DO UPDATE SET status = excluded.status",
+            params![client.id.get(), client.status.to_string()],
+        )?;
+        for (currency, balance) in &client.balances {
+            connection.execute(
+                "INSERT INTO client_balances (client_id, currency, available, held, total)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (client_id, currency) DO UPDATE SET
+                    available = excluded.available,
+                    held = excluded.held,
+                    total = excluded.total",
+                params![
+                    client.id.get(),
+                    currency.to_string(),
+                    balance.available.get().raw(),
+                    balance.held.get().raw(),
+                    balance.total.get().raw(),
+                ],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn query_clients(
+        &self,
+        filter: &str,
+        params: impl rusqlite::Params + Copy,
+    ) -> anyhow::Result<Vec<Client>> {
+        let connection = lock(&self.connection)?;
+        let mut clients = connection
+            .prepare(&format!(
+                "SELECT id, status FROM clients {filter} ORDER BY id"
+            ))?
+            .query_map(params, client_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut stmt = connection.prepare(&format!(
+            "SELECT client_id, currency, available, held, total FROM client_balances
+             WHERE client_id IN (SELECT id FROM clients {filter})"
+        ))?;
+        for row in stmt.query_map(params, balance_from_row)? {
+            let (id, currency, balance) = row?;
+            if let Ok(index) = clients.binary_search_by_key(&id, |client| client.id.get()) {
+                clients[index].balances.insert(currency, balance);
+            }
+        }
+
+        Ok(clients)
+    }
+}
+
+fn client_from_row(row: &Row<'_>) -> rusqlite::Result<Client> {
+    Ok(Client {
+        status: ClientStatus::try_from(row.get::<_, String>(1)?)
+            .map_err(|err| conversion_error(1, err))?,
+        ..Client::new(ClientId::new(row.get(0)?))
+    })
+}
+
+fn balance_from_row(row: &Row<'_>) -> rusqlite::Result<(u16, Currency, Balance)> {
+    Ok((
+        row.get(0)?,
+        row.get::<_, String>(1)?
+            .parse()
+            .map_err(|err| conversion_error(1, err))?,
+        Balance {
+            available: AvailableAmount::new(Amount::from_raw(row.get(2)?)),
+            held: HeldAmount::new(Amount::from_raw(row.get(3)?)),
+            total: TotalAmount::new(Amount::from_raw(row.get(4)?)),
+        },
+    ))
+}
+
+const SAVEPOINT: &str = "client_repository";
+
+impl UnitOfWork for SqliteClientRepository {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        begin_savepoint(&self.connection, SAVEPOINT)
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        release_savepoint(&self.connection, SAVEPOINT)
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        rollback_savepoint(&self.connection, SAVEPOINT)
+    }
+}
+
+impl ClientRepository for SqliteClientRepository {
+    fn get_client(&self, id: &ClientId) -> anyhow::Result<Client, ClientError> {
+        self.query_clients("WHERE id = ?1", params![id.get()])?
+            .pop()
+            .ok_or(ClientError::NotFound { id: *id })
+    }
+
+    fn create_client(&mut self, client: &Client) -> anyhow::Result<Client, ClientError> {
+        self.upsert(client)?;
+        Ok(client.clone())
+    }
+
+    fn update_client(&mut self, client: &Client) -> anyhow::Result<Client, ClientError> {
+        self.upsert(client)?;
+        Ok(client.clone())
+    }
+
+    fn get_all_clients(&self) -> anyhow::Result<impl Iterator<Item = Client>, ClientError> {
+        // Client ids are u16, so the whole table always fits comfortably in memory.
+        Ok(self.query_clients("", [])?.into_iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::dispute_policy::DisputePolicy;
+    use crate::repository::sqlite_store::SqliteStore;
+
+    fn create_test_client(id: &str) -> Client {
+        Client::new(ClientId::try_from(id.to_string()).unwrap())
+    }
+
+    fn assert_clients_equal(client1: &Client, client2: &Client) {
+        assert_eq!(client1.id, client2.id);
+        assert_eq!(client1.balances.len(), client2.balances.len());
+        for (currency, balance) in &client1.balances {
+            let other = client2.balance(*currency);
+            assert_eq!(balance.available.get(), other.available.get());
+            assert_eq!(balance.held.get(), other.held.get());
+            assert_eq!(balance.total.get(), other.total.get());
+        }
+        assert_eq!(client1.status, client2.status);
+    }
+
+    #[test]
+    fn test_create_and_get_client() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.client_repository();
+        let client = create_test_client("1");
+
+        repo.create_client(&client).unwrap();
+        let result = repo.get_client(&client.id).unwrap();
+
+        assert_clients_equal(&result, &client);
+    }
+
+    #[test]
+    fn test_update_client() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.client_repository();
+        let client = create_test_client("1");
+        repo.create_client(&client).unwrap();
+
+        let mut updated_client = client.clone();
+        updated_client
+            .deposit(Currency::EUR, "100.1234".parse().unwrap())
+            .unwrap();
+        updated_client
+            .deposit("USD".parse().unwrap(), "5".parse().unwrap())
+            .unwrap();
+        updated_client
+            .dispute(
+                Currency::EUR,
+                "0.1234".parse().unwrap(),
+                &DisputePolicy::default(),
+            )
+            .unwrap();
+        updated_client
+            .chargeback(
+                Currency::EUR,
+                "0.1234".parse().unwrap(),
+                &DisputePolicy::default(),
+            )
+            .unwrap();
+        repo.update_client(&updated_client).unwrap();
+
+        let result = repo.get_client(&client.id).unwrap();
+
+        assert_clients_equal(&result, &updated_client);
+    }
+
+    #[test]
+    fn test_get_client_not_found() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let repo = store.client_repository();
+        let id = ClientId::try_from("999".to_string()).unwrap();
+
+        let result = repo.get_client(&id);
+
+        assert!(matches!(result, Err(ClientError::NotFound { id: _ })));
+    }
+
+    #[test]
+    fn test_get_all_clients() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.client_repository();
+        let client1 = create_test_client("1");
+        let client2 = create_test_client("2");
+
+        repo.create_client(&client2).unwrap();
+        repo.create_client(&client1).unwrap();
+
+        let ids: Vec<ClientId> = repo.get_all_clients().unwrap().map(|c| c.id).collect();
+
+        assert_eq!(ids, vec![client1.id, client2.id]);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::repository::sqlite_client_repository::SqliteClientRepository;
+use crate::repository::sqlite_transaction_repository::SqliteTransactionRepository;
+use rusqlite::Connection;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+pub type SharedConnection = Arc<Mutex<Connection>>;
+
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE clients (
+        id INTEGER PRIMARY KEY,
+        available INTEGER NOT NULL,
+        held INTEGER NOT NULL,
+        total INTEGER NOT NULL,
+        status TEXT NOT NULL
+    );
+
+    CREATE TABLE transactions (
+        id INTEGER PRIMARY KEY,
+        client_id INTEGER NOT NULL,
+        amount INTEGER NOT NULL,
+        tx_type TEXT NOT NULL,
+        status TEXT NOT NULL
+    );
+
+    CREATE INDEX idx_transactions_client_id ON transactions (client_id);
+",
+    "
+    -- Position of each transaction in its client's history. Rows written before this
+    -- migration have no recorded order and are ranked by ID.
+    ALTER TABLE transactions ADD COLUMN seq INTEGER;
+    UPDATE transactions SET seq = id;
+    DROP INDEX idx_transactions_client_id;
+    CREATE INDEX idx_transactions_client_seq ON transactions (client_id, seq);
+",
+    "
+    -- Transfers also belong to the history of the client they credit.
+    ALTER TABLE transactions ADD COLUMN destination_id INTEGER;
+    ALTER TABLE transactions ADD COLUMN destination_seq INTEGER;
+    CREATE INDEX idx_transactions_destination_seq ON transactions (destination_id, destination_seq);
+",
+    "
+    -- Balances are kept per currency. Everything written before this migration is in EUR.
+    CREATE TABLE client_balances (
+        client_id INTEGER NOT NULL,
+        currency TEXT NOT NULL,
+        available INTEGER NOT NULL,
+        held INTEGER NOT NULL,
+        total INTEGER NOT NULL,
+        PRIMARY KEY (client_id, currency)
+    );
+    INSERT INTO client_balances SELECT id, 'EUR', available, held, total FROM clients;
+    ALTER TABLE clients DROP COLUMN available;
+    ALTER TABLE clients DROP COLUMN held;
+    ALTER TABLE clients DROP COLUMN total;
+    ALTER TABLE transactions ADD COLUMN currency TEXT NOT NULL DEFAULT 'EUR';
+",
+    "
+    ALTER TABLE transactions ADD COLUMN destination_currency TEXT;
+    ALTER TABLE transactions ADD COLUMN destination_amount INTEGER;
+    ALTER TABLE transactions ADD COLUMN rate TEXT;
+",
+    "
+    ALTER TABLE transactions ADD COLUMN captured_amount INTEGER;
+    -- Deadlines of authorizations, and the record count `expires_after_record` is compared
+    -- with.
+    ALTER TABLE transactions ADD COLUMN expires_after_record INTEGER;
+    ALTER TABLE transactions ADD COLUMN expires_at INTEGER;
+    CREATE INDEX idx_transactions_expiring ON transactions (id)
+        WHERE expires_after_record IS NOT NULL OR expires_at IS NOT NULL;
+    CREATE TABLE record_count (count INTEGER NOT NULL);
+    INSERT INTO record_count VALUES (0);
+",
+    "
+    -- Rows written before this migration derive these amounts from their status.
+    ALTER TABLE transactions ADD COLUMN disputed_amount INTEGER;
+    ALTER TABLE transactions ADD COLUMN charged_back_amount INTEGER;
+    ALTER TABLE transactions ADD COLUMN resolved_amount INTEGER;
+",
+    "
+    ALTER TABLE transactions ADD COLUMN refunded_amount INTEGER;
+",
+    "
+    -- Fees are kept apart from the transactions, under the ID of the one they were charged on.
+    CREATE TABLE fees (
+        id INTEGER PRIMARY KEY,
+        client_id INTEGER NOT NULL,
+        house_id INTEGER NOT NULL,
+        amount INTEGER NOT NULL,
+        currency TEXT NOT NULL,
+        refunded_amount INTEGER
+    );
+",
+];
+
+#[derive(Debug, Clone)]
+pub struct SqliteStore {
+    connection: SharedConnection,
+}
+
+impl SqliteStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut connection: Connection) -> anyhow::Result<Self> {
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    pub fn client_repository(&self) -> SqliteClientRepository {
+        SqliteClientRepository::new(self.connection.clone())
+    }
+
+    pub fn transaction_repository(&self) -> SqliteTransactionRepository {
+        SqliteTransactionRepository::new(self.connection.clone())
+    }
+}
+
+pub(crate) fn lock(connection: &SharedConnection) -> anyhow::Result<MutexGuard<'_, Connection>> {
+    connection
+        .lock()
+        .map_err(|_| anyhow::anyhow!("SQLite connection lock poisoned"))
+}
+
+pub(crate) fn conversion_error(column: usize, err: anyhow::Error) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(column, rusqlite::types::Type::Text, err.into())
+}
+
+pub(crate) fn begin_savepoint(connection: &SharedConnection, name: &str) -> anyhow::Result<()> {
+    lock(connection)?.execute_batch(&format!("SAVEPOINT {name}"))?;
+    Ok(())
+}
+
+pub(crate) fn release_savepoint(connection: &SharedConnection, name: &str) -> anyhow::Result<()> {
+    lock(connection)?.execute_batch(&format!("RELEASE SAVEPOINT {name}"))?;
+    Ok(())
+}
+
+pub(crate) fn rollback_savepoint(connection: &SharedConnection, name: &str) -> anyhow::Result<()> {
+    lock(connection)?.execute_batch(&format!(
+        "ROLLBACK TO SAVEPOINT {name}; RELEASE SAVEPOINT {name}"
+    ))?;
+    Ok(())
+}
+
+fn migrate(connection: &mut Connection) -> anyhow::Result<()> {
+    let version: usize = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    if version > MIGRATIONS.len() {
+        anyhow::bail!(
+            "Database schema version {} is newer than supported version {}",
+            version,
+            MIGRATIONS.len()
+        );
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = connection.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Client, ClientId, Currency, Transaction, TxId, TxType};
+    use crate::error::{ClientError, TransactionError};
+    use crate::repository::client_repository::ClientRepository;
+    use crate::repository::transaction_repository::TransactionRepository;
+    use crate::repository::unit_of_work::UnitOfWork;
+
+    fn write_client_and_transaction(
+        clients: &mut SqliteClientRepository,
+        transactions: &mut SqliteTransactionRepository,
+    ) {
+        let client = Client::new(ClientId::new(1));
+        let transaction = Transaction::new(
+            TxId::new(1),
+            client.id,
+            "1".parse().unwrap(),
+            TxType::Deposit,
+        );
+        clients.create_client(&client).unwrap();
+        transactions.create_transaction(&transaction).unwrap();
+    }
+
+    fn schema_version(store: &SqliteStore) -> usize {
+        store
+            .connection
+            .lock()
+            .unwrap()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_open_in_memory_applies_migrations() {
+        let store = SqliteStore::open_in_memory().unwrap();
+
+        assert_eq!(schema_version(&store), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_migrate_is_idempotent() {
+        let mut connection = Connection::open_in_memory().unwrap();
+
+        migrate(&mut connection).unwrap();
+        migrate(&mut connection).unwrap();
+
+        let version: usize = connection
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_migration_ranks_existing_transactions_by_id() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO transactions VALUES (7, 1, 10000, 'deposit', 'confirmed');
+                 INSERT INTO transactions VALUES (3, 1, 10000, 'deposit', 'confirmed');",
+            )
+            .unwrap();
+
+        migrate(&mut connection).unwrap();
+        let store = SqliteStore {
+            connection: Arc::new(Mutex::new(connection)),
+        };
+        let mut transactions = store.transaction_repository();
+        transactions
+            .create_transaction(&Transaction::new(
+                TxId::new(1),
+                ClientId::new(1),
+                "1".parse().unwrap(),
+                TxType::Deposit,
+            ))
+            .unwrap();
+
+        let ids: Vec<u32> = transactions
+            .get_transactions_for_client(&ClientId::new(1), 0..10)
+            .unwrap()
+            .iter()
+            .map(|tx| tx.id.get())
+            .collect();
+        assert_eq!(ids, vec![3, 7, 1]);
+    }
+
+    #[test]
+    fn test_migration_moves_balances_to_eur() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        for migration in &MIGRATIONS[..3] {
+            connection.execute_batch(migration).unwrap();
+        }
+        connection.pragma_update(None, "user_version", 3).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO clients VALUES (1, 15000, 5000, 20000, 'active');
+                 INSERT INTO transactions (id, client_id, amount, tx_type, status, seq)
+                 VALUES (1, 1, 20000, 'deposit', 'disputed', 1);",
+            )
+            .unwrap();
+
+        migrate(&mut connection).unwrap();
+        let store = SqliteStore {
+            connection: Arc::new(Mutex::new(connection)),
+        };
+
+        let client = store
+            .client_repository()
+            .get_client(&ClientId::new(1))
+            .unwrap();
+        let balance = client.balance(Currency::EUR);
+        assert_eq!(client.balances.len(), 1);
+        assert_eq!(balance.available.get(), "1.5".parse().unwrap());
+        assert_eq!(balance.held.get(), "0.5".parse().unwrap());
+        assert_eq!(balance.total.get(), "2".parse().unwrap());
+        let transaction = store
+            .transaction_repository()
+            .get_transaction(&TxId::new(1))
+            .unwrap();
+        assert_eq!(transaction.currency, Currency::EUR);
+    }
+
+    #[test]
+    fn test_migrate_rejects_newer_schema() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+
+        assert!(migrate(&mut connection).is_err());
+    }
+
+    #[test]
+    fn test_rollback_spans_both_repositories() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut clients = store.client_repository();
+        let mut transactions = store.transaction_repository();
+
+        clients.begin().unwrap();
+        transactions.begin().unwrap();
+        write_client_and_transaction(&mut clients, &mut transactions);
+        transactions.rollback().unwrap();
+        clients.rollback().unwrap();
+
+        assert!(matches!(
+            clients.get_client(&ClientId::new(1)),
+            Err(ClientError::NotFound { .. })
+        ));
+        assert!(matches!(
+            transactions.get_transaction(&TxId::new(1)),
+            Err(TransactionError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_commit_spans_both_repositories() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut clients = store.client_repository();
+        let mut transactions = store.transaction_repository();
+
+        clients.begin().unwrap();
+        transactions.begin().unwrap();
+        write_client_and_transaction(&mut clients, &mut transactions);
+        transactions.commit().unwrap();
+        clients.commit().unwrap();
+
+        assert!(clients.get_client(&ClientId::new(1)).is_ok());
+        assert!(transactions.get_transaction(&TxId::new(1)).is_ok());
+    }
+}
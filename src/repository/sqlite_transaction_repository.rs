@@ -0,0 +1,595 @@
+use crate::domain::amount::Amount;
+use crate::domain::client_id::ClientId;
+use crate::domain::transaction::Transaction;
+use crate::domain::transaction_status::TransactionStatus;
+use crate::domain::tx_id::TxId;
+use crate::domain::tx_type::TxType;
+use crate::error::transaction_error::TransactionError;
+use crate::repository::sqlite_store::{
+    SharedConnection, begin_savepoint, conversion_error, lock, release_savepoint,
+    rollback_savepoint,
+};
+use crate::repository::transaction_repository::TransactionRepository;
+use crate::repository::unit_of_work::UnitOfWork;
+use rusqlite::{OptionalExtension, Row, params};
+use std::ops::Range;
+
+#[derive(Debug, Clone)]
+pub struct SqliteTransactionRepository {
+    connection: SharedConnection,
+}
+
+impl SqliteTransactionRepository {
+    pub fn new(connection: SharedConnection) -> Self {
+        Self { connection }
+    }
+
+    fn write(&self, transaction: &Transaction, on_conflict: &str) -> anyhow::Result<usize> {
+        let changed = lock(&self.connection)?.execute(
+            &format!(
+                "INSERT INTO transactions
+                     (id, client_id, amount, tx_type, status, destination_id, currency,
+                      destination_currency, destination_amount, rate, captured_amount,
+                      disputed_amount, charged_back_amount, refunded_amount, expires_after_record,
+                      expires_at, resolved_amount, seq, destination_seq)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16,
+                         ?17, {},
+                         CASE WHEN ?6 IS NOT NULL THEN {} END)
+                 ON CONFLICT (id) {on_conflict}",
+                next_seq("?2"),
+                next_seq("?6"),
+            ),
+            params![
+                transaction.id.get(),
+                transaction.client_id.get(),
+                transaction.amount.raw(),
+                transaction.tx_type.to_string(),
+                transaction.status.to_string(),
+                transaction.destination_id.map(|id| id.get()),
+                transaction.currency.to_string(),
+                transaction.destination_currency.map(|c| c.to_string()),
+                transaction.destination_amount.map(|a| a.raw()),
+                transaction.rate.map(|r| r.to_string()),
+                transaction.captured_amount.map(|a| a.raw()),
+                transaction.disputed_amount.map(|a| a.raw()),
+                transaction.charged_back_amount.map(|a| a.raw()),
+                transaction.refunded_amount.map(|a| a.raw()),
+                transaction.expires_after_record.map(|n| n as i64),
+                transaction.expires_at.map(|t| t as i64),
+                transaction.resolved_amount.map(|a| a.raw()),
+            ],
+        )?;
+
+        Ok(changed)
+    }
+
+    fn write_fee(&self, fee: &Transaction, on_conflict: &str) -> anyhow::Result<usize> {
+        let house = fee
+            .destination_id
+            .ok_or(TransactionError::InvalidTransaction { id: fee.id })?;
+        let changed = lock(&self.connection)?.execute(
+            &format!(
+                "INSERT INTO fees (id, client_id, house_id, amount, currency, refunded_amount)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                 ON CONFLICT (id) {on_conflict}"
+            ),
+            params![
+                fee.id.get(),
+                fee.client_id.get(),
+                house.get(),
+                fee.amount.raw(),
+                fee.currency.to_string(),
+                fee.refunded_amount.map(|a| a.raw()),
+            ],
+        )?;
+
+        Ok(changed)
+    }
+}
+
+const COLUMNS: &str = "id, client_id, amount, tx_type, status, destination_id, currency, \
+                       destination_currency, destination_amount, rate, captured_amount, \
+                       disputed_amount, charged_back_amount, refunded_amount, \
+                       expires_after_record, expires_at, resolved_amount";
+
+// Next position in the history of a client, which spans the transactions it sent and received.
+fn next_seq(client: &str) -> String {
+    format!(
+        "(SELECT COALESCE(MAX(seq), 0) + 1 FROM (
+             SELECT seq FROM transactions WHERE client_id = {client}
+             UNION ALL
+             SELECT destination_seq FROM transactions WHERE destination_id = {client}
+         ))"
+    )
+}
+
+fn transaction_from_row(row: &Row<'_>) -> rusqlite::Result<Transaction> {
+    Ok(Transaction {
+        id: TxId::new(row.get(0)?),
+        client_id: ClientId::new(row.get(1)?),
+        amount: Amount::from_raw(row.get(2)?),
+        tx_type: TxType::try_from(row.get::<_, String>(3)?)
+            .map_err(|err| conversion_error(3, err))?,
+        status: TransactionStatus::try_from(row.get::<_, String>(4)?)
+            .map_err(|err| conversion_error(4, err))?,
+        destination_id: row.get::<_, Option<u16>>(5)?.map(ClientId::new),
+        currency: row
+            .get::<_, String>(6)?
+            .parse()
+            .map_err(|err| conversion_error(6, err))?,
+        destination_currency: row
+            .get::<_, Option<String>>(7)?
+            .map(|c| c.parse())
+            .transpose()
+            .map_err(|err| conversion_error(7, err))?,
+        destination_amount: row.get::<_, Option<i64>>(8)?.map(Amount::from_raw),
+        rate: row
+            .get::<_, Option<String>>(9)?
+            .map(|r| r.parse())
+            .transpose()
+            .map_err(|err| conversion_error(9, err))?,
+        captured_amount: row.get::<_, Option<i64>>(10)?.map(Amount::from_raw),
+        disputed_amount: row.get::<_, Option<i64>>(11)?.map(Amount::from_raw),
+        charged_back_amount: row.get::<_, Option<i64>>(12)?.map(Amount::from_raw),
+        refunded_amount: row.get::<_, Option<i64>>(13)?.map(Amount::from_raw),
+        expires_after_record: row.get::<_, Option<i64>>(14)?.map(|n| n as u64),
+        expires_at: row.get::<_, Option<i64>>(15)?.map(|t| t as u64),
+        resolved_amount: row.get::<_, Option<i64>>(16)?.map(Amount::from_raw),
+    })
+}
+
+fn fee_from_row(row: &Row<'_>) -> rusqlite::Result<Transaction> {
+    Ok(Transaction {
+        currency: row
+            .get::<_, String>(4)?
+            .parse()
+            .map_err(|err| conversion_error(4, err))?,
+        destination_id: Some(ClientId::new(row.get(2)?)),
+        refunded_amount: row.get::<_, Option<i64>>(5)?.map(Amount::from_raw),
+        ..Transaction::new(
+            TxId::new(row.get(0)?),
+            ClientId::new(row.get(1)?),
+            Amount::from_raw(row.get(3)?),
+            TxType::Fee,
+        )
+    })
+}
+
+const SAVEPOINT: &str = "transaction_repository";
+
+impl UnitOfWork for SqliteTransactionRepository {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        begin_savepoint(&self.connection, SAVEPOINT)
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        release_savepoint(&self.connection, SAVEPOINT)
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        rollback_savepoint(&self.connection, SAVEPOINT)
+    }
+}
+
+impl TransactionRepository for SqliteTransactionRepository {
+    fn get_transaction(&self, id: &TxId) -> anyhow::Result<Transaction, TransactionError> {
+        lock(&self.connection)?
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM transactions WHERE id = ?1"),
+                params![id.get()],
+                transaction_from_row,
+            )
+            .optional()
+            .map_err(anyhow::Error::from)?
+            .ok_or(TransactionError::NotFound { id: *id })
+    }
+
+    fn get_transaction_under_dispute(
+        &self,
+        id: &TxId,
+    ) -> anyhow::Result<Transaction, TransactionError> {
+        let tx = self.get_transaction(id)?;
+
+        tx.is_under_dispute()
+            .then(|| tx.clone())
+            .ok_or(TransactionError::NotUnderDispute { id: *id })
+    }
+
+    fn create_transaction(
+        &mut self,
+        transaction: &Transaction,
+    ) -> anyhow::Result<Transaction, TransactionError> {
+        if self.write(transaction, "DO NOTHING")? == 0 {
+            return Err(TransactionError::Duplicate { id: transaction.id });
+        }
+        Ok(transaction.clone())
+    }
+
+    fn update_transaction(
+        &mut self,
+        transaction: &Transaction,
+    ) -> anyhow::Result<Transaction, TransactionError> {
+        self.write(
+            transaction,
+            "DO UPDATE SET
+                client_id = excluded.client_id,
+                amount = excluded.amount,
+                tx_type = excluded.tx_type,
+                status = excluded.status,
+                destination_id = excluded.destination_id,
+                currency = excluded.currency,
+                destination_currency = excluded.destination_currency,
+                destination_amount = excluded.destination_amount,
+                rate = excluded.rate,
+                captured_amount = excluded.captured_amount,
+                disputed_amount = excluded.disputed_amount,
+                charged_back_amount = excluded.charged_back_amount,
+                refunded_amount = excluded.refunded_amount,
+                expires_after_record = excluded.expires_after_record,
+                expires_at = excluded.expires_at,
+                resolved_amount = excluded.resolved_amount",
+        )?;
+        Ok(transaction.clone())
+    }
+
+    fn get_transactions_for_client(
+        &self,
+        client_id: &ClientId,
+        range: Range<usize>,
+    ) -> anyhow::Result<Vec<Transaction>, TransactionError> {
+        let connection = lock(&self.connection)?;
+        let mut statement = connection
+            .prepare_cached(&format!(
+                "SELECT {COLUMNS} FROM (
+                     SELECT {COLUMNS}, seq AS position FROM transactions WHERE client_id = ?1
+                     UNION ALL
+                     SELECT {COLUMNS}, destination_seq FROM transactions WHERE destination_id = ?1
+                 )
+                 ORDER BY position LIMIT ?2 OFFSET ?3"
+            ))
+            .map_err(anyhow::Error::from)?;
+        let limit = range.end.saturating_sub(range.start);
+        let transactions = statement
+            .query_map(
+                params![client_id.get(), limit as i64, range.start as i64],
+                transaction_from_row,
+            )
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(anyhow::Error::from)?;
+
+        Ok(transactions)
+    }
+
+    fn get_expiring_authorizations(&self) -> anyhow::Result<Vec<Transaction>, TransactionError> {
+        let connection = lock(&self.connection)?;
+        let mut statement = connection
+            .prepare_cached(&format!(
+                "SELECT {COLUMNS} FROM transactions
+                 WHERE (expires_after_record IS NOT NULL OR expires_at IS NOT NULL)
+                     AND status = ?1
+                 ORDER BY id"
+            ))
+            .map_err(anyhow::Error::from)?;
+        let transactions = statement
+            .query_map(
+                params![TransactionStatus::Authorized.to_string()],
+                transaction_from_row,
+            )
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(anyhow::Error::from)?;
+
+        Ok(transactions)
+    }
+
+    fn get_record_count(&self) -> anyhow::Result<u64, TransactionError> {
+        let count: i64 = lock(&self.connection)?
+            .query_row("SELECT count FROM record_count", [], |row| row.get(0))
+            .map_err(anyhow::Error::from)?;
+        Ok(count as u64)
+    }
+
+    fn set_record_count(&mut self, count: u64) -> anyhow::Result<(), TransactionError> {
+        lock(&self.connection)?
+            .execute("UPDATE record_count SET count = ?1", params![count as i64])
+            .map_err(anyhow::Error::from)?;
+        Ok(())
+    }
+
+    fn get_fee(&self, parent: &TxId) -> anyhow::Result<Transaction, TransactionError> {
+        lock(&self.connection)?
+            .query_row(
+                "SELECT id, client_id, house_id, amount, currency, refunded_amount
+                 FROM fees WHERE id = ?1",
+                params![parent.get()],
+                fee_from_row,
+            )
+            .optional()
+            .map_err(anyhow::Error::from)?
+            .ok_or(TransactionError::NotFound { id: *parent })
+    }
+
+    fn create_fee(&mut self, fee: &Transaction) -> anyhow::Result<Transaction, TransactionError> {
+        if self.write_fee(fee, "DO NOTHING")? == 0 {
+            return Err(TransactionError::Duplicate { id: fee.id });
+        }
+        Ok(fee.clone())
+    }
+
+    fn update_fee(&mut self, fee: &Transaction) -> anyhow::Result<Transaction, TransactionError> {
+        self.write_fee(
+            fee,
+            "DO UPDATE SET
+                client_id = excluded.client_id,
+                house_id = excluded.house_id,
+                amount = excluded.amount,
+                currency = excluded.currency,
+                refunded_amount = excluded.refunded_amount",
+        )?;
+        Ok(fee.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::sqlite_store::SqliteStore;
+
+    fn create_test_transaction(id: u32, status: TransactionStatus) -> Transaction {
+        let mut tx = Transaction::new(
+            TxId::try_from(id.to_string()).unwrap(),
+            ClientId::try_from("1".to_string()).unwrap(),
+            "100.0001".parse().unwrap(),
+            TxType::Deposit,
+        );
+        tx.status = status;
+        tx
+    }
+
+    fn assert_transactions_equal(tx1: &Transaction, tx2: &Transaction) {
+        assert_eq!(tx1.id, tx2.id);
+        assert_eq!(tx1.client_id, tx2.client_id);
+        assert_eq!(tx1.amount, tx2.amount);
+        assert_eq!(tx1.currency, tx2.currency);
+        assert_eq!(tx1.tx_type, tx2.tx_type);
+        assert_eq!(tx1.status, tx2.status);
+    }
+
+    #[test]
+    fn test_create_and_get_transaction() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        let mut tx = create_test_transaction(1, TransactionStatus::Confirmed);
+        tx.currency = "USD".parse().unwrap();
+
+        repo.create_transaction(&tx).unwrap();
+        let result = repo.get_transaction(&tx.id).unwrap();
+
+        assert_transactions_equal(&result, &tx);
+    }
+
+    #[test]
+    fn test_create_duplicate_transaction() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        let tx = create_test_transaction(1, TransactionStatus::Confirmed);
+        repo.create_transaction(&tx).unwrap();
+
+        let mut duplicate = tx.clone();
+        duplicate.amount = "1".parse().unwrap();
+        let result = repo.create_transaction(&duplicate);
+
+        assert!(matches!(result, Err(TransactionError::Duplicate { id: _ })));
+        assert_transactions_equal(&repo.get_transaction(&tx.id).unwrap(), &tx);
+    }
+
+    #[test]
+    fn test_update_transaction() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        let tx = create_test_transaction(1, TransactionStatus::Confirmed);
+        repo.create_transaction(&tx).unwrap();
+
+        let mut updated_tx = tx.clone();
+        updated_tx.status = TransactionStatus::Disputed;
+        updated_tx.disputed_amount = Some("40".parse().unwrap());
+        updated_tx.charged_back_amount = Some("10".parse().unwrap());
+        updated_tx.resolved_amount = Some("20".parse().unwrap());
+        updated_tx.refunded_amount = Some("5".parse().unwrap());
+        repo.update_transaction(&updated_tx).unwrap();
+
+        let result = repo.get_transaction(&tx.id).unwrap();
+
+        assert_transactions_equal(&result, &updated_tx);
+        assert_eq!(result.disputed_amount, updated_tx.disputed_amount);
+        assert_eq!(result.charged_back_amount, updated_tx.charged_back_amount);
+        assert_eq!(result.resolved_amount, updated_tx.resolved_amount);
+        assert_eq!(result.refunded_amount, updated_tx.refunded_amount);
+    }
+
+    #[test]
+    fn test_fees_are_kept_apart_from_transactions() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        let deposit = create_test_transaction(u32::MAX, TransactionStatus::Confirmed);
+        repo.create_transaction(&deposit).unwrap();
+        let mut fee = Transaction::fee(&deposit, ClientId::new(9000), "1.5".parse().unwrap());
+        repo.create_fee(&fee).unwrap();
+
+        assert!(matches!(
+            repo.create_fee(&fee),
+            Err(TransactionError::Duplicate { id: _ })
+        ));
+        fee.refunded_amount = Some(fee.amount);
+        repo.update_fee(&fee).unwrap();
+
+        let result = repo.get_fee(&deposit.id).unwrap();
+        assert_transactions_equal(&result, &fee);
+        assert_eq!(result.destination_id, Some(ClientId::new(9000)));
+        assert_eq!(result.refunded_amount, fee.refunded_amount);
+        assert_eq!(
+            repo.get_transaction(&deposit.id).unwrap().tx_type,
+            TxType::Deposit
+        );
+        assert!(matches!(
+            repo.get_fee(&TxId::new(1)),
+            Err(TransactionError::NotFound { id: _ })
+        ));
+    }
+
+    #[test]
+    fn test_expiring_authorizations_and_record_count() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        let mut open =
+            Transaction::authorization(TxId::new(1), ClientId::new(1), "5".parse().unwrap());
+        open.expires_after_record = Some(12);
+        open.expires_at = Some(1_700_000_000);
+        let mut captured = Transaction {
+            id: TxId::new(2),
+            status: TransactionStatus::Captured,
+            ..open.clone()
+        };
+        captured.expires_at = None;
+        let without_deadline =
+            Transaction::authorization(TxId::new(3), ClientId::new(1), "5".parse().unwrap());
+        for tx in [&open, &captured, &without_deadline] {
+            repo.create_transaction(tx).unwrap();
+        }
+        repo.set_record_count(7).unwrap();
+
+        let expiring = repo.get_expiring_authorizations().unwrap();
+
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].id, open.id);
+        assert_eq!(expiring[0].expires_after_record, Some(12));
+        assert_eq!(expiring[0].expires_at, Some(1_700_000_000));
+        assert_eq!(
+            store.transaction_repository().get_record_count().unwrap(),
+            7
+        );
+    }
+
+    #[test]
+    fn test_get_transaction_not_found() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let repo = store.transaction_repository();
+        let id = TxId::try_from("999".to_string()).unwrap();
+
+        let result = repo.get_transaction(&id);
+
+        assert!(matches!(result, Err(TransactionError::NotFound { id: _ })));
+    }
+
+    #[test]
+    fn test_get_transaction_under_dispute_success() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        let tx = create_test_transaction(1, TransactionStatus::Disputed);
+        repo.create_transaction(&tx).unwrap();
+
+        let result = repo.get_transaction_under_dispute(&tx.id).unwrap();
+
+        assert_transactions_equal(&result, &tx);
+    }
+
+    #[test]
+    fn test_get_transaction_under_dispute_not_under_dispute() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        let tx = create_test_transaction(1, TransactionStatus::Confirmed);
+        repo.create_transaction(&tx).unwrap();
+
+        let result = repo.get_transaction_under_dispute(&tx.id);
+
+        assert!(matches!(
+            result,
+            Err(TransactionError::NotUnderDispute { id: _ })
+        ));
+    }
+
+    #[test]
+    fn test_state_survives_reopening_file() {
+        let path =
+            std::env::temp_dir().join(format!("rust-exercise-sqlite-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let tx = create_test_transaction(1, TransactionStatus::Confirmed);
+
+        {
+            let store = SqliteStore::open(&path).unwrap();
+            store
+                .transaction_repository()
+                .create_transaction(&tx)
+                .unwrap();
+        }
+
+        let store = SqliteStore::open(&path).unwrap();
+        let result = store
+            .transaction_repository()
+            .get_transaction(&tx.id)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_transactions_equal(&result, &tx);
+    }
+
+    #[test]
+    fn test_get_transactions_for_client_in_creation_order() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        for id in [5, 2, 9] {
+            repo.create_transaction(&create_test_transaction(id, TransactionStatus::Confirmed))
+                .unwrap();
+        }
+        let mut other = create_test_transaction(3, TransactionStatus::Confirmed);
+        other.client_id = ClientId::new(2);
+        repo.create_transaction(&other).unwrap();
+        repo.update_transaction(&create_test_transaction(5, TransactionStatus::Disputed))
+            .unwrap();
+
+        let ids = |range| -> Vec<u32> {
+            repo.get_transactions_for_client(&ClientId::new(1), range)
+                .unwrap()
+                .iter()
+                .map(|tx| tx.id.get())
+                .collect()
+        };
+        assert_eq!(ids(0..10), vec![5, 2, 9]);
+        assert_eq!(ids(1..3), vec![2, 9]);
+        assert_eq!(ids(4..10), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_transfer_is_in_both_histories() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut repo = store.transaction_repository();
+        let mut other = create_test_transaction(1, TransactionStatus::Confirmed);
+        other.client_id = ClientId::new(2);
+        repo.create_transaction(&other).unwrap();
+        let transfer = Transaction::transfer(
+            TxId::new(2),
+            ClientId::new(1),
+            ClientId::new(2),
+            "1".parse().unwrap(),
+        );
+        repo.create_transaction(&transfer).unwrap();
+        repo.create_transaction(&create_test_transaction(3, TransactionStatus::Confirmed))
+            .unwrap();
+        let mut received = create_test_transaction(4, TransactionStatus::Confirmed);
+        received.client_id = ClientId::new(2);
+        repo.create_transaction(&received).unwrap();
+
+        let ids = |client| -> Vec<u32> {
+            repo.get_transactions_for_client(&ClientId::new(client), 0..10)
+                .unwrap()
+                .iter()
+                .map(|tx| tx.id.get())
+                .collect()
+        };
+        assert_eq!(ids(1), vec![2, 3]);
+        assert_eq!(ids(2), vec![1, 2, 4]);
+        assert_eq!(
+            repo.get_transaction(&TxId::new(2)).unwrap().destination_id,
+            Some(ClientId::new(2))
+        );
+    }
+}
@@ -1,9 +1,12 @@
+use crate::domain::client_id::ClientId;
 use crate::domain::transaction::Transaction;
 use crate::domain::tx_id::TxId;
 use crate::error::transaction_error::TransactionError;
+use crate::repository::unit_of_work::UnitOfWork;
 use std::collections::HashMap;
+use std::ops::Range;
 
-pub trait TransactionRepository {
+pub trait TransactionRepository: UnitOfWork {
     fn get_transaction(&self, id: &TxId) -> anyhow::Result<Transaction, TransactionError>;
     fn get_transaction_under_dispute(
         &self,
@@ -17,18 +20,172 @@ pub trait TransactionRepository {
         &mut self,
         tx: &Transaction,
     ) -> anyhow::Result<Transaction, TransactionError>;
+    // `range` selects by position in the client's history, oldest first.
+    fn get_transactions_for_client(
+        &self,
+        client_id: &ClientId,
+        range: Range<usize>,
+    ) -> anyhow::Result<Vec<Transaction>, TransactionError>;
+    // Authorizations with a deadline that were neither captured, voided nor expired yet.
+    fn get_expiring_authorizations(&self) -> anyhow::Result<Vec<Transaction>, TransactionError>;
+    // Records processed so far, the clock of `HoldExpiry::after_records`. Written in the unit
+    // of work of the record counted, or one of its own for a rejected record.
+    fn get_record_count(&self) -> anyhow::Result<u64, TransactionError>;
+    fn set_record_count(&mut self, count: u64) -> anyhow::Result<(), TransactionError>;
+    // Fees are kept apart from the transactions, under the ID of the transaction they were
+    // charged on, so they never take an ID the input may still use.
+    fn get_fee(&self, parent: &TxId) -> anyhow::Result<Transaction, TransactionError>;
+    fn create_fee(&mut self, fee: &Transaction) -> anyhow::Result<Transaction, TransactionError>;
+    fn update_fee(&mut self, fee: &Transaction) -> anyhow::Result<Transaction, TransactionError>;
 }
 
 pub struct TransactionRepositoryImpl {
     transactions: HashMap<TxId, Transaction>,
+    // Every transaction in the order it was created.
+    order: Vec<TxId>,
+    // Secondary index: every client's transactions in the order they were created, transfers
+    // in the history of both parties.
+    by_client: HashMap<ClientId, Vec<TxId>>,
+    fees: HashMap<TxId, Transaction>,
+    records: u64,
+    journal: Option<Vec<Change>>,
+}
+
+// What a unit of work overwrote, to be put back on rollback.
+enum Change {
+    Transaction(TxId, Option<Transaction>),
+    Fee(TxId, Option<Transaction>),
+    RecordCount(u64),
+}
+
+impl Default for TransactionRepositoryImpl {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionRepositoryImpl {
     pub fn new() -> Self {
         Self {
             transactions: HashMap::new(),
+            order: Vec::new(),
+            by_client: HashMap::new(),
+            fees: HashMap::new(),
+            records: 0,
+            journal: None,
+        }
+    }
+
+    // Histories are rebuilt in the order the transactions are given.
+    pub fn from_transactions(transactions: impl IntoIterator<Item = Transaction>) -> Self {
+        let mut repository = Self::new();
+        for transaction in transactions {
+            repository.store(&transaction);
+        }
+        repository
+    }
+
+    pub fn with_record_count(self, records: u64) -> Self {
+        Self { records, ..self }
+    }
+
+    pub fn with_fees(self, fees: impl IntoIterator<Item = Transaction>) -> Self {
+        Self {
+            fees: fees.into_iter().map(|fee| (fee.id, fee)).collect(),
+            ..self
+        }
+    }
+
+    pub fn record_count(&self) -> u64 {
+        self.records
+    }
+
+    // Oldest first, so `from_transactions` gives back the same histories.
+    pub fn get_all_transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.order.iter().map(|id| &self.transactions[id])
+    }
+
+    pub fn get_all_fees(&self) -> impl Iterator<Item = &Transaction> {
+        self.fees.values()
+    }
+
+    fn parties(transaction: &Transaction) -> impl Iterator<Item = ClientId> {
+        std::iter::once(transaction.client_id).chain(transaction.destination_id)
+    }
+
+    fn store(&mut self, transaction: &Transaction) {
+        let previous = self
+            .transactions
+            .insert(transaction.id, transaction.clone());
+        if previous.is_none() {
+            self.order.push(transaction.id);
+            for client in Self::parties(transaction) {
+                self.by_client
+                    .entry(client)
+                    .or_default()
+                    .push(transaction.id);
+            }
+        }
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(Change::Transaction(transaction.id, previous));
         }
     }
+
+    fn store_fee(&mut self, fee: &Transaction) {
+        let previous = self.fees.insert(fee.id, fee.clone());
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(Change::Fee(fee.id, previous));
+        }
+    }
+}
+
+impl UnitOfWork for TransactionRepositoryImpl {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        if self.journal.is_some() {
+            anyhow::bail!("Unit of work already in progress");
+        }
+        self.journal = Some(Vec::new());
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        self.journal
+            .take()
+            .map(|_| ())
+            .ok_or_else(|| anyhow::anyhow!("No unit of work in progress"))
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        let journal = self
+            .journal
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("No unit of work in progress"))?;
+
+        // Undone newest first, so a created transaction is always the last of every list.
+        for change in journal.into_iter().rev() {
+            match change {
+                Change::Transaction(id, Some(transaction)) => {
+                    self.transactions.insert(id, transaction);
+                }
+                Change::Fee(id, Some(fee)) => {
+                    self.fees.insert(id, fee);
+                }
+                Change::Fee(id, None) => {
+                    self.fees.remove(&id);
+                }
+                Change::RecordCount(count) => self.records = count,
+                Change::Transaction(id, None) => {
+                    if let Some(transaction) = self.transactions.remove(&id) {
+                        self.order.pop();
+                        for client in Self::parties(&transaction) {
+                            self.by_client.get_mut(&client).and_then(|ids| ids.pop());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl TransactionRepository for TransactionRepositoryImpl {
@@ -36,7 +193,7 @@ impl TransactionRepository for TransactionRepositoryImpl {
         self.transactions
             .get(id)
             .cloned()
-            .ok_or(TransactionError::NotFound { id: id.clone() })
+            .ok_or(TransactionError::NotFound { id: *id })
     }
 
     fn get_transaction_under_dispute(
@@ -46,19 +203,21 @@ impl TransactionRepository for TransactionRepositoryImpl {
         let tx = self
             .transactions
             .get(id)
-            .ok_or(TransactionError::NotFound { id: id.clone() })?;
+            .ok_or(TransactionError::NotFound { id: *id })?;
 
         tx.is_under_dispute()
             .then(|| tx.clone())
-            .ok_or(TransactionError::NotUnderDispute { id: id.clone() })
+            .ok_or(TransactionError::NotUnderDispute { id: *id })
     }
 
     fn create_transaction(
         &mut self,
         transaction: &Transaction,
     ) -> anyhow::Result<Transaction, TransactionError> {
-        self.transactions
-            .insert(transaction.id.clone(), transaction.clone());
+        if self.transactions.contains_key(&transaction.id) {
+            return Err(TransactionError::Duplicate { id: transaction.id });
+        }
+        self.store(transaction);
         Ok(transaction.clone())
     }
 
@@ -66,10 +225,64 @@ impl TransactionRepository for TransactionRepositoryImpl {
         &mut self,
         transaction: &Transaction,
     ) -> anyhow::Result<Transaction, TransactionError> {
-        self.transactions
-            .insert(transaction.id.clone(), transaction.clone());
+        self.store(transaction);
         Ok(transaction.clone())
     }
+
+    fn get_transactions_for_client(
+        &self,
+        client_id: &ClientId,
+        range: Range<usize>,
+    ) -> anyhow::Result<Vec<Transaction>, TransactionError> {
+        let ids = self.by_client.get(client_id).map_or(&[][..], Vec::as_slice);
+        let end = range.end.min(ids.len());
+        let start = range.start.min(end);
+
+        Ok(ids[start..end]
+            .iter()
+            .map(|id| self.transactions[id].clone())
+            .collect())
+    }
+
+    fn get_expiring_authorizations(&self) -> anyhow::Result<Vec<Transaction>, TransactionError> {
+        Ok(self
+            .get_all_transactions()
+            .filter(|tx| tx.is_expiring())
+            .cloned()
+            .collect())
+    }
+
+    fn get_record_count(&self) -> anyhow::Result<u64, TransactionError> {
+        Ok(self.records)
+    }
+
+    fn set_record_count(&mut self, count: u64) -> anyhow::Result<(), TransactionError> {
+        let previous = std::mem::replace(&mut self.records, count);
+        if let Some(journal) = self.journal.as_mut() {
+            journal.push(Change::RecordCount(previous));
+        }
+        Ok(())
+    }
+
+    fn get_fee(&self, parent: &TxId) -> anyhow::Result<Transaction, TransactionError> {
+        self.fees
+            .get(parent)
+            .cloned()
+            .ok_or(TransactionError::NotFound { id: *parent })
+    }
+
+    fn create_fee(&mut self, fee: &Transaction) -> anyhow::Result<Transaction, TransactionError> {
+        if self.fees.contains_key(&fee.id) {
+            return Err(TransactionError::Duplicate { id: fee.id });
+        }
+        self.store_fee(fee);
+        Ok(fee.clone())
+    }
+
+    fn update_fee(&mut self, fee: &Transaction) -> anyhow::Result<Transaction, TransactionError> {
+        self.store_fee(fee);
+        Ok(fee.clone())
+    }
 }
 
 #[cfg(test)]
@@ -85,7 +298,7 @@ mod tests {
         let mut tx = Transaction::new(
             TxId::try_from(id.to_string()).unwrap(),
             ClientId::try_from("1".to_string()).unwrap(),
-            100.0,
+            "100".parse().unwrap(),
             TxType::Deposit,
         );
         tx.status = status;
@@ -111,11 +324,25 @@ mod tests {
         assert!(repo.transactions.contains_key(&tx.id));
     }
 
+    #[test]
+    fn test_create_duplicate_transaction() {
+        let mut repo = TransactionRepositoryImpl::new();
+        let tx = create_test_transaction(1, TransactionStatus::Confirmed);
+        repo.transactions.insert(tx.id, tx.clone());
+
+        let mut duplicate = tx.clone();
+        duplicate.amount = "1".parse().unwrap();
+        let result = repo.create_transaction(&duplicate);
+
+        assert!(matches!(result, Err(TransactionError::Duplicate { id: _ })));
+        assert_transactions_equal(&repo.transactions[&tx.id], &tx);
+    }
+
     #[test]
     fn test_update_transaction() {
         let mut repo = TransactionRepositoryImpl::new();
         let tx = create_test_transaction(1, TransactionStatus::Confirmed);
-        repo.transactions.insert(tx.id.clone(), tx.clone());
+        repo.transactions.insert(tx.id, tx.clone());
 
         let mut updated_tx = tx.clone();
         updated_tx.status = TransactionStatus::Disputed;
@@ -129,7 +356,7 @@ mod tests {
     fn test_get_transaction_success() {
         let mut repo = TransactionRepositoryImpl::new();
         let tx = create_test_transaction(1, TransactionStatus::Confirmed);
-        repo.transactions.insert(tx.id.clone(), tx.clone());
+        repo.transactions.insert(tx.id, tx.clone());
 
         let result = repo.get_transaction(&tx.id).unwrap();
 
@@ -150,7 +377,7 @@ mod tests {
     fn test_get_transaction_under_dispute_success() {
         let mut repo = TransactionRepositoryImpl::new();
         let tx = create_test_transaction(1, TransactionStatus::Disputed);
-        repo.transactions.insert(tx.id.clone(), tx.clone());
+        repo.transactions.insert(tx.id, tx.clone());
 
         let result = repo.get_transaction_under_dispute(&tx.id).unwrap();
 
@@ -171,7 +398,7 @@ mod tests {
     fn test_get_transaction_under_dispute_not_under_dispute() {
         let mut repo = TransactionRepositoryImpl::new();
         let tx = create_test_transaction(1, TransactionStatus::Confirmed);
-        repo.transactions.insert(tx.id.clone(), tx.clone());
+        repo.transactions.insert(tx.id, tx.clone());
 
         let result = repo.get_transaction_under_dispute(&tx.id);
 
@@ -180,4 +407,153 @@ mod tests {
             Err(TransactionError::NotUnderDispute { id: _ })
         ));
     }
+
+    #[test]
+    fn test_rollback_restores_previous_state() {
+        let mut repo = TransactionRepositoryImpl::new();
+        let tx = create_test_transaction(1, TransactionStatus::Confirmed);
+        repo.create_transaction(&tx).unwrap();
+
+        repo.begin().unwrap();
+        let mut updated_tx = tx.clone();
+        updated_tx.status = TransactionStatus::Disputed;
+        repo.update_transaction(&updated_tx).unwrap();
+        repo.create_transaction(&create_test_transaction(2, TransactionStatus::Confirmed))
+            .unwrap();
+        repo.set_record_count(1).unwrap();
+        repo.rollback().unwrap();
+
+        assert_transactions_equal(&repo.get_transaction(&tx.id).unwrap(), &tx);
+        assert!(!repo.transactions.contains_key(&TxId::new(2)));
+        assert_eq!(repo.get_record_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_commit_keeps_changes() {
+        let mut repo = TransactionRepositoryImpl::new();
+        let tx = create_test_transaction(1, TransactionStatus::Confirmed);
+
+        repo.begin().unwrap();
+        repo.create_transaction(&tx).unwrap();
+        repo.commit().unwrap();
+
+        assert_transactions_equal(&repo.get_transaction(&tx.id).unwrap(), &tx);
+        assert!(repo.commit().is_err());
+    }
+
+    #[test]
+    fn test_get_transactions_for_client_in_creation_order() {
+        let mut repo = TransactionRepositoryImpl::new();
+        for id in [5, 2, 9] {
+            repo.create_transaction(&create_test_transaction(id, TransactionStatus::Confirmed))
+                .unwrap();
+        }
+        let mut other = create_test_transaction(3, TransactionStatus::Confirmed);
+        other.client_id = ClientId::new(2);
+        repo.create_transaction(&other).unwrap();
+        repo.update_transaction(&create_test_transaction(2, TransactionStatus::Disputed))
+            .unwrap();
+
+        let ids = |range| -> Vec<u32> {
+            repo.get_transactions_for_client(&ClientId::new(1), range)
+                .unwrap()
+                .iter()
+                .map(|tx| tx.id.get())
+                .collect()
+        };
+        assert_eq!(ids(0..10), vec![5, 2, 9]);
+        assert_eq!(ids(1..2), vec![2]);
+        assert_eq!(ids(5..10), Vec::<u32>::new());
+        assert_eq!(
+            repo.get_transactions_for_client(&ClientId::new(1), 1..2)
+                .unwrap()[0]
+                .status,
+            TransactionStatus::Disputed
+        );
+        assert!(
+            repo.get_transactions_for_client(&ClientId::new(7), 0..10)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_rollback_removes_transactions_from_client_history() {
+        let mut repo = TransactionRepositoryImpl::new();
+        repo.create_transaction(&create_test_transaction(1, TransactionStatus::Confirmed))
+            .unwrap();
+
+        repo.begin().unwrap();
+        repo.create_transaction(&create_test_transaction(2, TransactionStatus::Confirmed))
+            .unwrap();
+        repo.rollback().unwrap();
+
+        let history = repo
+            .get_transactions_for_client(&ClientId::new(1), 0..10)
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].id, TxId::new(1));
+    }
+
+    #[test]
+    fn test_transfer_is_in_both_histories() {
+        let mut repo = TransactionRepositoryImpl::new();
+        let transfer = Transaction::transfer(
+            TxId::new(1),
+            ClientId::new(1),
+            ClientId::new(2),
+            "1".parse().unwrap(),
+        );
+        repo.create_transaction(&transfer).unwrap();
+
+        repo.begin().unwrap();
+        let mut other = create_test_transaction(2, TransactionStatus::Confirmed);
+        other.client_id = ClientId::new(2);
+        repo.create_transaction(&other).unwrap();
+        repo.rollback().unwrap();
+
+        for client in [1, 2] {
+            let history = repo
+                .get_transactions_for_client(&ClientId::new(client), 0..10)
+                .unwrap();
+            assert_eq!(history.len(), 1);
+            assert_eq!(history[0].id, TxId::new(1));
+        }
+        assert_eq!(repo.get_all_transactions().count(), 1);
+    }
+
+    #[test]
+    fn test_fees_are_kept_apart_from_transactions() {
+        let mut repo = TransactionRepositoryImpl::new();
+        let deposit = create_test_transaction(1, TransactionStatus::Confirmed);
+        repo.create_transaction(&deposit).unwrap();
+        let fee = Transaction::fee(&deposit, ClientId::new(9), "1".parse().unwrap());
+        repo.create_fee(&fee).unwrap();
+
+        assert!(matches!(
+            repo.create_fee(&fee),
+            Err(TransactionError::Duplicate { id: _ })
+        ));
+        assert_eq!(repo.get_fee(&TxId::new(1)).unwrap().tx_type, TxType::Fee);
+        assert_eq!(
+            repo.get_transaction(&TxId::new(1)).unwrap().tx_type,
+            TxType::Deposit
+        );
+        assert_eq!(repo.get_all_transactions().count(), 1);
+
+        repo.begin().unwrap();
+        let mut refunded = fee.clone();
+        refunded.refunded_amount = Some(fee.amount);
+        repo.update_fee(&refunded).unwrap();
+        let other = create_test_transaction(2, TransactionStatus::Confirmed);
+        repo.create_fee(&Transaction::fee(&other, ClientId::new(9), fee.amount))
+            .unwrap();
+        repo.rollback().unwrap();
+
+        assert_eq!(repo.get_fee(&TxId::new(1)).unwrap().refunded_amount, None);
+        assert!(matches!(
+            repo.get_fee(&TxId::new(2)),
+            Err(TransactionError::NotFound { id: _ })
+        ));
+    }
 }
@@ -0,0 +1,5 @@
+pub trait UnitOfWork {
+    fn begin(&mut self) -> anyhow::Result<()>;
+    fn commit(&mut self) -> anyhow::Result<()>;
+    fn rollback(&mut self) -> anyhow::Result<()>;
+}
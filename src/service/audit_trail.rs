@@ -0,0 +1,147 @@
+use crate::domain::client_id::ClientId;
+use crate::domain::client_status::ClientStatus;
+use crate::domain::event::Event;
+use crate::domain::tx_id::TxId;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatusChange {
+    pub client: ClientId,
+    pub tx: TxId,
+    pub from: ClientStatus,
+    pub to: ClientStatus,
+    pub reason: String,
+    pub operator: String,
+}
+
+impl StatusChange {
+    pub const COLUMNS: [&'static str; 6] = ["client", "tx", "from", "to", "reason", "operator"];
+}
+
+// Every status change in the event log, in order: the ones made by operators and the locks
+// set by chargebacks, which carry the chargeback's ID, the reason `chargeback` and no operator.
+pub fn audit_trail<I>(events: I, client: Option<ClientId>) -> anyhow::Result<Vec<StatusChange>>
+where
+    I: IntoIterator<Item = anyhow::Result<Event>>,
+{
+    let mut statuses: HashMap<ClientId, ClientStatus> = HashMap::new();
+    let mut last_chargeback = None;
+    let mut changes = Vec::new();
+
+    for event in events {
+        let change = match event? {
+            Event::ChargedBack { tx, .. } => {
+                last_chargeback = Some(tx);
+                continue;
+            }
+            // Always logged right after the chargeback that caused it.
+            Event::AccountLocked { client } => {
+                let tx = last_chargeback.ok_or_else(|| {
+                    anyhow::anyhow!("Lock of client {} without a chargeback", client)
+                })?;
+                StatusChange {
+                    client,
+                    tx,
+                    from: statuses
+                        .get(&client)
+                        .copied()
+                        .unwrap_or(ClientStatus::Active),
+                    to: ClientStatus::Locked,
+                    reason: "chargeback".to_string(),
+                    operator: String::new(),
+                }
+            }
+            Event::StatusChanged {
+                client,
+                tx,
+                from,
+                to,
+                reason,
+                operator,
+            } => StatusChange {
+                client,
+                tx,
+                from,
+                to,
+                reason,
+                operator,
+            },
+            _ => continue,
+        };
+        statuses.insert(change.client, change.to);
+        if client.is_none_or(|client| client == change.client) {
+            changes.push(change);
+        }
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chargeback_locks_and_operator_changes() {
+        let (one, two) = (ClientId::new(1), ClientId::new(2));
+        let events = vec![
+            Event::ChargedBack {
+                client: one,
+                tx: TxId::new(3),
+                amount: "1".parse().unwrap(),
+            },
+            Event::AccountLocked { client: one },
+            Event::StatusChanged {
+                client: two,
+                tx: TxId::new(4),
+                from: ClientStatus::Active,
+                to: ClientStatus::Frozen,
+                reason: "kyc".to_string(),
+                operator: "bob".to_string(),
+            },
+            Event::StatusChanged {
+                client: one,
+                tx: TxId::new(5),
+                from: ClientStatus::Locked,
+                to: ClientStatus::Active,
+                reason: "refunded".to_string(),
+                operator: "alice".to_string(),
+            },
+        ];
+
+        let changes = audit_trail(events.into_iter().map(Ok), Some(one)).unwrap();
+
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|c| {
+                (
+                    c.tx.get(),
+                    c.from,
+                    c.to,
+                    c.reason.as_str(),
+                    c.operator.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    3,
+                    ClientStatus::Active,
+                    ClientStatus::Locked,
+                    "chargeback",
+                    ""
+                ),
+                (
+                    5,
+                    ClientStatus::Locked,
+                    ClientStatus::Active,
+                    "refunded",
+                    "alice"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::domain::event::Event;
+use std::io::{Read, Write};
+
+pub trait EventLog {
+    fn append(&mut self, event: &Event) -> anyhow::Result<()>;
+}
+
+pub struct NoopEventLog;
+
+impl EventLog for NoopEventLog {
+    fn append(&mut self, _event: &Event) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+// One JSON object per line; the file is only ever appended to.
+pub struct JsonlEventLog<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonlEventLog<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> EventLog for JsonlEventLog<W> {
+    fn append(&mut self, event: &Event) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.writer, event)?;
+        writeln!(self.writer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn read_events<R: Read>(reader: R) -> impl Iterator<Item = anyhow::Result<Event>> {
+    serde_json::Deserializer::from_reader(reader)
+        .into_iter::<Event>()
+        .map(|event| event.map_err(anyhow::Error::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ClientId, Currency, TxId};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_written_events_are_read_back() {
+        let events = vec![
+            Event::Deposited {
+                client: ClientId::new(1),
+                tx: TxId::new(1),
+                amount: "2".parse().unwrap(),
+                currency: Currency::EUR,
+            },
+            Event::AccountLocked {
+                client: ClientId::new(1),
+            },
+        ];
+        let mut log = JsonlEventLog::new(Vec::new());
+
+        for event in &events {
+            log.append(event).unwrap();
+        }
+
+        let read: Vec<Event> = read_events(Cursor::new(log.writer))
+            .collect::<anyhow::Result<_>>()
+            .unwrap();
+        assert_eq!(read, events);
+    }
+
+    #[test]
+    fn test_read_events_reports_malformed_lines() {
+        let data = "{\"event\":\"AccountLocked\",\"client\":\"1\"}\nnot json\n";
+
+        let results: Vec<_> = read_events(Cursor::new(data)).collect();
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+}
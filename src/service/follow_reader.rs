@@ -0,0 +1,121 @@
+use std::io::{self, Read, Seek, SeekFrom};
+use std::thread;
+use std::time::Duration;
+
+// Turns the end of a growing file into a wait for more data, like `tail -f`. The CSV reader
+// on top only ever sees complete records: a partial trailing line stays buffered until the
+// rest of it is written.
+pub struct FollowReader<R> {
+    inner: R,
+    poll_interval: Duration,
+}
+
+impl<R> FollowReader<R> {
+    pub fn new(inner: R, poll_interval: Duration) -> Self {
+        Self {
+            inner,
+            poll_interval,
+        }
+    }
+}
+
+impl<R: Read> Read for FollowReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.inner.read(buf)? {
+                0 => thread::sleep(self.poll_interval),
+                n => return Ok(n),
+            }
+        }
+    }
+}
+
+impl<R: Seek> Seek for FollowReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{InputRecord, TxId};
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{ErrorKind, Write};
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(ErrorKind::PermissionDenied, "denied"))
+        }
+    }
+
+    #[test]
+    fn test_read_errors_are_not_waited_on() {
+        let mut reader = FollowReader::new(FailingReader, Duration::from_secs(60));
+
+        let err = reader.read(&mut [0; 8]).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+        assert_eq!(reader.read(&mut []).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_invalid_rows_do_not_stop_following() {
+        let path = std::env::temp_dir().join(format!("follow-invalid-{}.csv", std::process::id()));
+        fs::write(&path, "type,client,tx,amount\ndeposit,1,x,1.0\n").unwrap();
+
+        let reader = FollowReader::new(File::open(&path).unwrap(), Duration::from_millis(5));
+        let mut records = InputRecord::records_from_csv(reader).unwrap();
+
+        let first = records.next().unwrap().unwrap();
+        assert!(first.record.is_err());
+
+        let writer_path = path.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let mut file = OpenOptions::new().append(true).open(writer_path).unwrap();
+            file.write_all(b"deposit,1,2,1.0\n").unwrap();
+        });
+
+        let second = records.next().unwrap().unwrap().record.unwrap();
+        writer.join().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(second.tx, TxId::new(2));
+    }
+
+    #[test]
+    fn test_partial_lines_wait_for_the_rest_of_the_record() {
+        let path = std::env::temp_dir().join(format!("follow-{}.csv", std::process::id()));
+        fs::write(
+            &path,
+            "type,client,tx,amount\ndeposit,1,1,1.0\ndeposit,1,2,",
+        )
+        .unwrap();
+
+        let reader = FollowReader::new(File::open(&path).unwrap(), Duration::from_millis(5));
+        let mut records = InputRecord::records_from_csv(reader).unwrap();
+
+        let first = records.next().unwrap().unwrap().record.unwrap();
+        assert_eq!(first.tx, TxId::new(1));
+
+        let writer_path = path.clone();
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            let mut file = OpenOptions::new().append(true).open(writer_path).unwrap();
+            file.write_all(b"2.5\n").unwrap();
+        });
+
+        let second = records.next().unwrap().unwrap().record.unwrap();
+        writer.join().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(second.tx, TxId::new(2));
+        assert_eq!(second.amount, Some("2.5".parse().unwrap()));
+    }
+}
@@ -0,0 +1,482 @@
+use crate::domain::client_id::ClientId;
+use crate::domain::input_record::InputRecord;
+use crate::domain::output_record::OutputRecord;
+use crate::domain::tx_id::TxId;
+use crate::error::amount_error::AmountError;
+use crate::error::client_error::ClientError;
+use crate::error::error_code::error_code;
+use crate::error::transaction_error::TransactionError;
+use crate::repository::client_repository::ClientRepository;
+use crate::repository::transaction_repository::TransactionRepository;
+use crate::service::transaction_service::TransactionService;
+use serde_json::{Value, json};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const MAX_HEADERS: usize = 32;
+const MAX_HEAD_SIZE: usize = 16 * 1024;
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: json!({ "code": code, "message": message.into() }),
+        }
+    }
+
+    fn from_error(err: &anyhow::Error) -> Self {
+        Self::error(error_status(err), error_code(err), err.to_string())
+    }
+}
+
+// One request per connection, answered with `Connection: close`. Requests from all
+// connections go through the same service one at a time, like the socket server.
+pub struct HttpServer<T, V>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    service: Arc<Mutex<TransactionService<T, V>>>,
+}
+
+impl<T, V> HttpServer<T, V>
+where
+    T: ClientRepository + Send + 'static,
+    V: TransactionRepository + Send + 'static,
+{
+    pub fn new(service: TransactionService<T, V>) -> Self {
+        Self {
+            service: Arc::new(Mutex::new(service)),
+        }
+    }
+
+    pub fn serve(&self, listener: TcpListener) -> anyhow::Result<()> {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let service = Arc::clone(&self.service);
+                    thread::spawn(move || {
+                        if let Err(err) = handle_connection(&service, stream) {
+                            eprintln!("Connection closed: {}", err);
+                        }
+                    });
+                }
+                Err(err) => eprintln!("Error accepting connection: {}", err),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn route<T, V>(
+    service: &mut TransactionService<T, V>,
+    method: &str,
+    path: &str,
+    body: &[u8],
+) -> Response
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    let result = match (method, segments.as_slice()) {
+        ("POST", ["transactions"]) => return post_transactions(service, body),
+        ("GET", ["clients"]) => get_clients(service),
+        ("GET", ["clients", id]) => match ClientId::try_from(id.to_string()) {
+            Ok(id) => service
+                .get_client(&id)
+                .map(|client| json!(OutputRecord::new(&client, service.default_currency())))
+                .map_err(anyhow::Error::from),
+            Err(err) => return Response::error(400, "invalid_client", err.to_string()),
+        },
+        ("GET", ["transactions", id]) => match TxId::try_from(id.to_string()) {
+            Ok(id) => service
+                .get_transaction(&id)
+                .map(|transaction| json!(transaction))
+                .map_err(anyhow::Error::from),
+            Err(err) => return Response::error(400, "invalid_tx", err.to_string()),
+        },
+        (_, ["transactions"] | ["clients"] | ["clients", _] | ["transactions", _]) => {
+            return Response::error(405, "method_not_allowed", "method not allowed");
+        }
+        _ => return Response::error(404, "not_found", "not found"),
+    };
+
+    match result {
+        Ok(body) => Response::ok(body),
+        Err(err) => Response::from_error(&err),
+    }
+}
+
+// A single record answers with the client it touched, or the error with its own status.
+// A batch always answers 200 with one result per record, each carrying its own status;
+// records are applied one by one, so a failing record doesn't undo the others.
+fn post_transactions<T, V>(service: &mut TransactionService<T, V>, body: &[u8]) -> Response
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let value: Value = match serde_json::from_slice(body) {
+        Ok(value) => value,
+        Err(err) => return Response::error(400, "invalid_json", err.to_string()),
+    };
+
+    match value {
+        Value::Array(values) => Response::ok(Value::Array(
+            values
+                .into_iter()
+                .map(|value| {
+                    let response = apply(service, value);
+                    let mut result = json!({ "status": response.status });
+                    match response.body {
+                        Value::Object(fields) if response.status != 200 => {
+                            result.as_object_mut().unwrap().extend(fields)
+                        }
+                        body => result["client"] = body,
+                    }
+                    result
+                })
+                .collect(),
+        )),
+        value => apply(service, value),
+    }
+}
+
+fn apply<T, V>(service: &mut TransactionService<T, V>, value: Value) -> Response
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let record: InputRecord = match serde_json::from_value(value) {
+        Ok(record) => record,
+        Err(err) => return Response::error(400, "invalid_record", err.to_string()),
+    };
+    match service.process_transaction(&record) {
+        Ok(client) => {
+            let currency = record.currency.unwrap_or(service.default_currency());
+            Response::ok(json!(OutputRecord::new(&client, currency)))
+        }
+        Err(err) => Response::from_error(&err),
+    }
+}
+
+fn get_clients<T, V>(service: &TransactionService<T, V>) -> anyhow::Result<Value>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let clients: Vec<OutputRecord> = service.get_all_clients()?.collect();
+    Ok(json!(clients))
+}
+
+fn error_status(err: &anyhow::Error) -> u16 {
+    if let Some(err) = err.downcast_ref::<ClientError>() {
+        match err {
+            ClientError::NotFound { .. } => 404,
+            ClientError::Locked { .. }
+            | ClientError::Frozen { .. }
+            | ClientError::Closed { .. }
+            | ClientError::InvalidStatusChange { .. } => 409,
+            ClientError::FundsUpdateError { .. } | ClientError::AmountError(_) => 422,
+            ClientError::UnexpectedError(_) => 500,
+        }
+    } else if let Some(err) = err.downcast_ref::<TransactionError>() {
+        match err {
+            TransactionError::NotFound { .. } => 404,
+            TransactionError::Duplicate { .. }
+            | TransactionError::NotUnderDispute { .. }
+            | TransactionError::AlreadyDisputed { .. }
+            | TransactionError::AlreadyResolved { .. }
+            | TransactionError::AlreadyChargedBack { .. }
+            | TransactionError::AlreadyReversed { .. }
+            | TransactionError::AuthorizationClosed { .. } => 409,
+            TransactionError::ClientMismatch { .. } => 403,
+            TransactionError::DisputeNotAllowed { .. }
+            | TransactionError::NotAnAuthorization { .. }
+            | TransactionError::CaptureExceedsAuthorization { .. }
+            | TransactionError::DisputeExceedsAmount { .. }
+            | TransactionError::ExceedsDisputedAmount { .. }
+            | TransactionError::NotRefundable { .. }
+            | TransactionError::RefundExceedsAmount { .. }
+            | TransactionError::NotReversible { .. }
+            | TransactionError::CurrencyMismatch { .. }
+            | TransactionError::RateNotFound { .. }
+            | TransactionError::ExchangeRoundsToZero { .. }
+            | TransactionError::InvalidTransaction { .. }
+            | TransactionError::AmountError(_) => 422,
+            TransactionError::UnexpectedError(_) => 500,
+        }
+    } else if err.downcast_ref::<AmountError>().is_some() {
+        422
+    } else {
+        500
+    }
+}
+
+fn handle_connection<T, V>(
+    service: &Mutex<TransactionService<T, V>>,
+    stream: TcpStream,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader)? {
+        Ok((method, path, body)) => {
+            let mut service = service
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Transaction service lock poisoned"))?;
+            route(&mut service, &method, &path, &body)
+        }
+        Err(response) => response,
+    };
+    write_response(stream, &response)
+}
+
+type Request = (String, String, Vec<u8>);
+
+// The outer error is for the connection itself; the inner one is a request the server
+// refuses and still answers.
+fn read_request(reader: &mut impl BufRead) -> anyhow::Result<Result<Request, Response>> {
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") && !head.ends_with(b"\n\n") {
+        if reader.read_until(b'\n', &mut head)? == 0 {
+            anyhow::bail!("connection closed before the end of the request");
+        }
+        if head.len() > MAX_HEAD_SIZE {
+            return Ok(Err(Response::error(
+                431,
+                "headers_too_large",
+                "request headers too large",
+            )));
+        }
+    }
+
+    let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+    let mut request = httparse::Request::new(&mut headers);
+    if let Err(err) = request.parse(&head) {
+        return Ok(Err(Response::error(400, "bad_request", err.to_string())));
+    }
+
+    let mut length = 0;
+    for header in request.headers.iter() {
+        if header.name.eq_ignore_ascii_case("transfer-encoding") {
+            return Ok(Err(Response::error(
+                411,
+                "length_required",
+                "chunked bodies are not supported, send a Content-Length",
+            )));
+        }
+        if header.name.eq_ignore_ascii_case("content-length") {
+            match std::str::from_utf8(header.value).map(|value| value.trim().parse::<usize>()) {
+                Ok(Ok(value)) => length = value,
+                _ => {
+                    return Ok(Err(Response::error(
+                        400,
+                        "bad_request",
+                        "invalid Content-Length",
+                    )));
+                }
+            }
+        }
+    }
+    if length > MAX_BODY_SIZE {
+        return Ok(Err(Response::error(
+            413,
+            "payload_too_large",
+            "request body too large",
+        )));
+    }
+
+    let method = request.method.unwrap_or_default().to_string();
+    let path = request.path.unwrap_or_default().to_string();
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok(Ok((method, path, body)))
+}
+
+fn write_response(mut writer: impl Write, response: &Response) -> anyhow::Result<()> {
+    let body = response.body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        reason(response.status),
+        body.len(),
+        body
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        422 => "Unprocessable Entity",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::client_repository::ClientRepositoryImpl;
+    use crate::repository::transaction_repository::TransactionRepositoryImpl;
+    use std::io::Read;
+
+    fn service() -> TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl> {
+        TransactionService::new(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+        )
+    }
+
+    fn post(
+        service: &mut TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl>,
+        body: &str,
+    ) -> Response {
+        route(service, "POST", "/transactions", body.as_bytes())
+    }
+
+    #[test]
+    fn test_single_transactions_map_errors_to_statuses() {
+        let mut service = service();
+
+        let response = post(
+            &mut service,
+            r#"{"type":"deposit","client":"1","tx":"1","amount":"2.5"}"#,
+        );
+        assert_eq!(response.status, 200);
+        assert_eq!(
+            response.body,
+            json!({"client":"1","available":"2.5000","held":"0.0000","total":"2.5000","locked":false})
+        );
+
+        let duplicate = post(
+            &mut service,
+            r#"{"type":"deposit","client":"1","tx":"1","amount":"1"}"#,
+        );
+        assert_eq!(duplicate.status, 409);
+        assert_eq!(duplicate.body["code"], "duplicate_transaction");
+        assert_eq!(
+            post(
+                &mut service,
+                r#"{"type":"withdrawal","client":"1","tx":"2","amount":"5"}"#
+            )
+            .status,
+            422
+        );
+        assert_eq!(
+            post(&mut service, r#"{"type":"dispute","client":"2","tx":"1"}"#).status,
+            404
+        );
+        assert_eq!(
+            post(&mut service, r#"{"type":"rebate","client":"1","tx":"3"}"#).status,
+            400
+        );
+        assert_eq!(post(&mut service, "{").body["code"], "invalid_json");
+    }
+
+    #[test]
+    fn test_batch_reports_each_record() {
+        let mut service = service();
+
+        let response = post(
+            &mut service,
+            r#"[
+                {"type":"deposit","client":"1","tx":"1","amount":"3"},
+                {"type":"withdrawal","client":"1","tx":"2","amount":"4"},
+                {"type":"deposit","client":"2","tx":"3","amount":"1"}
+            ]"#,
+        );
+
+        assert_eq!(response.status, 200);
+        let statuses: Vec<_> = response
+            .body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| result["status"].as_u64().unwrap())
+            .collect();
+        assert_eq!(statuses, vec![200, 422, 200]);
+        assert_eq!(response.body[1]["code"], "insufficient_funds");
+        assert_eq!(response.body[2]["client"]["client"], "2");
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut service = service();
+        post(
+            &mut service,
+            r#"[{"type":"deposit","client":"2","tx":"7","amount":"1"},
+                {"type":"deposit","client":"1","tx":"8","amount":"1"}]"#,
+        );
+
+        let clients = route(&mut service, "GET", "/clients", b"");
+        assert_eq!(clients.body[0]["client"], "1");
+        assert_eq!(clients.body[1]["client"], "2");
+        assert_eq!(route(&mut service, "GET", "/clients/2", b"").status, 200);
+        assert_eq!(route(&mut service, "GET", "/clients/3", b"").status, 404);
+        assert_eq!(route(&mut service, "GET", "/clients/x", b"").status, 400);
+
+        let transaction = route(&mut service, "GET", "/transactions/7", b"");
+        assert_eq!(transaction.status, 200);
+        assert_eq!(transaction.body["client_id"], "2");
+        assert_eq!(
+            route(&mut service, "GET", "/transactions/9", b"").status,
+            404
+        );
+        assert_eq!(route(&mut service, "DELETE", "/clients/2", b"").status, 405);
+        assert_eq!(route(&mut service, "GET", "/accounts", b"").status, 404);
+    }
+
+    #[test]
+    fn test_http_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = HttpServer::new(service());
+        thread::spawn(move || server.serve(listener));
+
+        let body = r#"{"type":"deposit","client":"4","tx":"1","amount":"1"}"#;
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /transactions HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["total"], "1.0000");
+    }
+}
@@ -0,0 +1,150 @@
+use crate::domain::client_id::ClientId;
+use crate::domain::input_record::{CsvRecord, InputRecord};
+use crate::domain::output_record::OutputRecord;
+use crate::error::error_code::{csv_error_code, csv_error_message, error_code};
+use crate::repository::client_repository::ClientRepository;
+use crate::repository::transaction_repository::TransactionRepository;
+use crate::service::transaction_service::TransactionService;
+use csv::StringRecord;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Request {
+    Transaction(InputRecord),
+    Balance(ClientId),
+    // The CSV header line, accepted so a CSV file can be streamed as is.
+    Header,
+}
+
+#[derive(Debug)]
+pub enum Reply {
+    Ok,
+    Balance(OutputRecord),
+    Error { code: &'static str, message: String },
+}
+
+// Replies are one line each: `ok`, `ok <client>,<available>,<held>,<total>,<locked>` or
+// `error <code> <message>`, with the same codes as the rejects file.
+impl fmt::Display for Reply {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ok => write!(f, "ok"),
+            Self::Balance(c) => write!(
+                f,
+                "ok {},{},{},{},{}",
+                c.client,
+                c.available.get(),
+                c.held.get(),
+                c.total.get(),
+                c.locked
+            ),
+            Self::Error { code, message } => write!(f, "error {} {}", code, message),
+        }
+    }
+}
+
+pub fn parse_request(line: &str) -> Result<Request, Reply> {
+    if let Some(client) = line.trim().strip_prefix("balance ") {
+        return ClientId::try_from(client.trim().to_string())
+            .map(Request::Balance)
+            .map_err(|err| Reply::Error {
+                code: "invalid_client",
+                message: err.to_string(),
+            });
+    }
+
+    match InputRecord::from_line(line) {
+        Ok(CsvRecord { fields, .. }) if InputRecord::is_header(&fields) => Ok(Request::Header),
+        Ok(CsvRecord {
+            record: Ok(record), ..
+        }) => Ok(Request::Transaction(record)),
+        Ok(CsvRecord {
+            fields,
+            record: Err(err),
+            ..
+        }) => Err(Reply::Error {
+            code: csv_error_code(
+                &err,
+                &StringRecord::from(InputRecord::COLUMNS.to_vec()),
+                &fields,
+            ),
+            message: csv_error_message(&err),
+        }),
+        Err(err) => Err(Reply::Error {
+            code: csv_error_code(
+                &err,
+                &StringRecord::from(InputRecord::COLUMNS.to_vec()),
+                &StringRecord::new(),
+            ),
+            message: csv_error_message(&err),
+        }),
+    }
+}
+
+pub fn handle_request<T, V>(service: &mut TransactionService<T, V>, request: Request) -> Reply
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    match request {
+        Request::Transaction(record) => match service.process_transaction(&record) {
+            Ok(_) => Reply::Ok,
+            Err(err) => Reply::Error {
+                code: error_code(&err),
+                message: err.to_string(),
+            },
+        },
+        Request::Balance(id) => match service.get_client(&id) {
+            Ok(client) => Reply::Balance(OutputRecord::new(&client, service.default_currency())),
+            Err(err) => Reply::Error {
+                code: err.code(),
+                message: err.to_string(),
+            },
+        },
+        Request::Header => Reply::Ok,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::client_repository::ClientRepositoryImpl;
+    use crate::repository::transaction_repository::TransactionRepositoryImpl;
+
+    fn reply(
+        service: &mut TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl>,
+        line: &str,
+    ) -> String {
+        match parse_request(line) {
+            Ok(request) => handle_request(service, request).to_string(),
+            Err(reply) => reply.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_replies_per_line() {
+        let mut service = TransactionService::new(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+        );
+
+        assert_eq!(reply(&mut service, "type,client,tx,amount"), "ok");
+        assert_eq!(reply(&mut service, "type,client,tx,amount,to"), "ok");
+        assert_eq!(reply(&mut service, "deposit,1,1,2.5"), "ok");
+        assert_eq!(
+            reply(&mut service, "withdrawal, 1, 2, 3"),
+            "error insufficient_funds error updating funds for client 1 with tx type withdraw"
+        );
+        assert_eq!(
+            reply(&mut service, "balance 1"),
+            "ok 1,2.5000,0.0000,2.5000,false"
+        );
+        assert_eq!(
+            reply(&mut service, "balance 2"),
+            "error client_not_found client with id 2 not found"
+        );
+        assert!(reply(&mut service, "balance x").starts_with("error invalid_client "));
+        assert!(reply(&mut service, "deposit,1,3,1.00001").starts_with("error "));
+        assert!(reply(&mut service, "rebate,1,4,1").starts_with("error invalid_type "));
+    }
+}
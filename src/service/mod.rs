@@ -1,4 +1,30 @@
+pub mod audit_trail;
+pub mod event_log;
+pub mod follow_reader;
+#[cfg(feature = "http")]
+pub mod http_api;
+pub mod line_protocol;
+pub mod rejects;
+pub mod reprocessing;
+pub mod service_config;
+pub mod sharded_engine;
+pub mod socket_server;
+pub mod statement;
 pub mod stubs;
+pub mod suspicious_activity;
 pub mod transaction_service;
 
+pub use audit_trail::*;
+pub use event_log::*;
+pub use follow_reader::*;
+#[cfg(feature = "http")]
+pub use http_api::*;
+pub use line_protocol::*;
+pub use rejects::*;
+pub use reprocessing::*;
+pub use service_config::*;
+pub use sharded_engine::*;
+pub use socket_server::*;
+pub use statement::*;
+pub use suspicious_activity::*;
 pub use transaction_service::*;
@@ -0,0 +1,74 @@
+use csv::{StringRecord, WriterBuilder};
+use std::io::Write;
+
+// Each rejected row is written as `line,code,message` followed by the original fields, so
+// the last columns can be corrected and resubmitted as they are.
+pub struct RejectsWriter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> RejectsWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: WriterBuilder::new().flexible(true).from_writer(writer),
+        }
+    }
+
+    pub fn write_header(&mut self, headers: &StringRecord) -> anyhow::Result<()> {
+        self.writer
+            .write_record(["line", "code", "message"].into_iter().chain(headers))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn reject(
+        &mut self,
+        line: u64,
+        fields: &StringRecord,
+        code: &str,
+        message: &str,
+    ) -> anyhow::Result<()> {
+        let line = line.to_string();
+        self.writer
+            .write_record([line.as_str(), code, message].into_iter().chain(fields))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_are_written_with_original_fields() {
+        let mut rejects = RejectsWriter::new(Vec::new());
+
+        rejects
+            .write_header(&StringRecord::from(vec!["type", "client", "tx", "amount"]))
+            .unwrap();
+        rejects
+            .reject(
+                3,
+                &StringRecord::from(vec!["withdrawal", "1", "2", "5.0"]),
+                "insufficient_funds",
+                "error updating funds for client 1, tx type withdraw",
+            )
+            .unwrap();
+        rejects
+            .reject(
+                4,
+                &StringRecord::from(vec!["dispute", "1"]),
+                "missing_field",
+                "missing tx",
+            )
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(rejects.writer.into_inner().unwrap()).unwrap(),
+            "line,code,message,type,client,tx,amount\n\
+             3,insufficient_funds,\"error updating funds for client 1, tx type withdraw\",withdrawal,1,2,5.0\n\
+             4,missing_field,missing tx,dispute,1\n"
+        );
+    }
+}
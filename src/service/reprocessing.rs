@@ -0,0 +1,508 @@
+use crate::domain::amount::Amount;
+use crate::domain::client::Client;
+use crate::domain::client_id::ClientId;
+use crate::domain::currency::Currency;
+use crate::domain::input_format::RecordReader;
+use crate::domain::input_record::{CsvRecord, InputRecord};
+use crate::error::ClientError;
+use crate::error::error_code::{csv_error_code, csv_error_message, error_code};
+use crate::repository::client_repository::ClientRepository;
+use crate::repository::transaction_repository::TransactionRepository;
+use crate::service::transaction_service::TransactionService;
+use csv::StringRecord;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+#[derive(Debug)]
+pub struct Rejection {
+    pub code: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub struct Correction {
+    pub line: u64,
+    pub fields: StringRecord,
+    pub rejection: Option<Rejection>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceDelta {
+    pub client: ClientId,
+    pub currency: Currency,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+#[derive(Debug)]
+pub struct ReprocessReport {
+    pub corrections: Vec<Correction>,
+    pub deltas: Vec<BalanceDelta>,
+}
+
+// Corrections go through `process_transaction` like any other record, so they are validated
+// against the loaded state exactly as they would have been in the original run.
+pub fn reprocess<T, V, I>(
+    service: &mut TransactionService<T, V>,
+    records: I,
+) -> anyhow::Result<ReprocessReport>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+    I: RecordReader,
+{
+    let headers = records.headers().clone();
+    let input_columns = input_columns(&headers);
+    let mut before: HashMap<ClientId, Option<Client>> = HashMap::new();
+    let mut corrections = Vec::new();
+
+    for r in records {
+        let correction = match r {
+            Ok(CsvRecord {
+                line,
+                fields,
+                record: Ok(record),
+            }) => {
+                for party in parties(service, &record) {
+                    if let Entry::Vacant(entry) = before.entry(party) {
+                        entry.insert(find_client(service, &party)?);
+                    }
+                }
+                let rejection = service
+                    .process_transaction(&record)
+                    .err()
+                    .map(|err| Rejection {
+                        code: error_code(&err),
+                        message: err.to_string(),
+                    });
+                Correction {
+                    line,
+                    fields: project(&fields, &input_columns),
+                    rejection,
+                }
+            }
+            Ok(CsvRecord {
+                line,
+                fields,
+                record: Err(err),
+            }) => Correction {
+                line,
+                fields: project(&fields, &input_columns),
+                rejection: Some(Rejection {
+                    code: csv_error_code(&err, &headers, &fields),
+                    message: csv_error_message(&err),
+                }),
+            },
+            Err(err) => Correction {
+                line: err.line(),
+                fields: StringRecord::new(),
+                rejection: Some(Rejection {
+                    code: err.code(),
+                    message: err.to_string(),
+                }),
+            },
+        };
+        corrections.push(correction);
+    }
+
+    let mut deltas = Vec::new();
+    for (id, before) in before {
+        let Some(after) = find_client(service, &id)? else {
+            continue;
+        };
+        // Balances are never removed, so the client's currencies after cover those before.
+        let mut currencies: Vec<Currency> = after.balances.keys().copied().collect();
+        if currencies.is_empty() {
+            currencies.push(service.default_currency());
+        }
+        for currency in currencies {
+            let delta = balance_delta(before.as_ref(), &after, currency)?;
+            let unchanged = delta.available == Amount::ZERO
+                && delta.held == Amount::ZERO
+                && delta.total == Amount::ZERO
+                && before
+                    .as_ref()
+                    .is_some_and(|before| before.is_locked() == after.is_locked());
+            if !unchanged {
+                deltas.push(delta);
+            }
+        }
+    }
+    deltas.sort_by_key(|delta| (delta.client.get(), delta.currency));
+
+    Ok(ReprocessReport {
+        corrections,
+        deltas,
+    })
+}
+
+pub fn report_headers(headers: &StringRecord) -> StringRecord {
+    REPORT_COLUMNS
+        .into_iter()
+        .chain(project(headers, &input_columns(headers)).iter())
+        .collect()
+}
+
+// Corrections can be fed straight from a rejects file or a previous report, whose leading
+// columns describe the earlier failure rather than the record itself.
+const REPORT_COLUMNS: [&str; 4] = ["line", "result", "code", "message"];
+
+fn input_columns(headers: &StringRecord) -> Vec<usize> {
+    headers
+        .iter()
+        .enumerate()
+        .filter(|(_, header)| !REPORT_COLUMNS.contains(header))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+fn project(fields: &StringRecord, columns: &[usize]) -> StringRecord {
+    columns
+        .iter()
+        .filter_map(|&index| fields.get(index))
+        .collect()
+}
+
+// Every client a record can change: the recipient of a transfer, the recipient of the transfer
+// a dispute refers to, and the house account fees are paid into.
+fn parties<T, V>(service: &TransactionService<T, V>, record: &InputRecord) -> Vec<ClientId>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let referenced = service
+        .get_transaction(&record.tx)
+        .ok()
+        .filter(|transaction| transaction.client_id == record.client)
+        .and_then(|transaction| transaction.destination_id);
+    std::iter::once(record.client)
+        .chain(record.to)
+        .chain(referenced)
+        .chain(service.house_account())
+        .collect()
+}
+
+fn find_client<T, V>(
+    service: &TransactionService<T, V>,
+    id: &ClientId,
+) -> anyhow::Result<Option<Client>>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    match service.get_client(id) {
+        Ok(client) => Ok(Some(client)),
+        Err(ClientError::NotFound { .. }) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn balance_delta(
+    before: Option<&Client>,
+    after: &Client,
+    currency: Currency,
+) -> anyhow::Result<BalanceDelta> {
+    let before = before
+        .map(|client| client.balance(currency))
+        .unwrap_or_default();
+    let after_balance = after.balance(currency);
+
+    Ok(BalanceDelta {
+        client: after.id,
+        currency,
+        available: after_balance
+            .available
+            .get()
+            .checked_sub(before.available.get())?,
+        held: after_balance.held.get().checked_sub(before.held.get())?,
+        total: after_balance.total.get().checked_sub(before.total.get())?,
+        locked: after.is_locked(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::tx_id::TxId;
+    use crate::repository::client_repository::ClientRepositoryImpl;
+    use crate::repository::transaction_repository::TransactionRepositoryImpl;
+    use crate::service::service_config::ServiceConfig;
+    use std::io::Cursor;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    fn service_with_history() -> TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl>
+    {
+        let mut service = TransactionService::new(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+        );
+        let history = "\
+type,client,tx,amount
+deposit,1,1,10
+deposit,2,2,5
+";
+        for row in InputRecord::records_from_csv(Cursor::new(history)).unwrap() {
+            service
+                .process_transaction(&row.unwrap().record.unwrap())
+                .unwrap();
+        }
+        service
+    }
+
+    #[test]
+    fn test_reports_applied_and_failed_corrections() {
+        let mut service = service_with_history();
+        let corrections = "\
+line,code,message,type,client,tx,amount
+4,insufficient_funds,not enough,withdrawal,1,3,4
+5,invalid_amount,bad,deposit,2,4,1.5
+6,duplicate_transaction,dup,deposit,3,1,2
+7,invalid_type,bad,payout,1,5,1
+";
+
+        let report = reprocess(
+            &mut service,
+            InputRecord::records_from_csv(Cursor::new(corrections)).unwrap(),
+        )
+        .unwrap();
+
+        let codes: Vec<_> = report
+            .corrections
+            .iter()
+            .map(|c| (c.line, c.rejection.as_ref().map(|r| r.code)))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (2, None),
+                (3, None),
+                (4, Some("duplicate_transaction")),
+                (5, Some("invalid_type")),
+            ]
+        );
+        assert_eq!(report.corrections[3].fields, vec!["payout", "1", "5", "1"]);
+        assert_eq!(
+            report_headers(&StringRecord::from(vec![
+                "line", "code", "message", "type", "client", "tx", "amount"
+            ])),
+            vec![
+                "line", "result", "code", "message", "type", "client", "tx", "amount"
+            ]
+        );
+        assert_eq!(
+            report.deltas,
+            vec![
+                BalanceDelta {
+                    client: ClientId::new(1),
+                    currency: Currency::EUR,
+                    available: amount("-4"),
+                    held: Amount::ZERO,
+                    total: amount("-4"),
+                    locked: false,
+                },
+                BalanceDelta {
+                    client: ClientId::new(2),
+                    currency: Currency::EUR,
+                    available: amount("1.5"),
+                    held: Amount::ZERO,
+                    total: amount("1.5"),
+                    locked: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transfers_report_both_parties() {
+        let mut service = service_with_history();
+        let corrections = "\
+type,client,tx,amount,to
+transfer,1,3,5,2
+dispute,1,3,,
+";
+
+        let report = reprocess(
+            &mut service,
+            InputRecord::records_from_csv(Cursor::new(corrections)).unwrap(),
+        )
+        .unwrap();
+
+        assert!(report.corrections.iter().all(|c| c.rejection.is_none()));
+        assert_eq!(
+            report.deltas,
+            vec![
+                BalanceDelta {
+                    client: ClientId::new(1),
+                    currency: Currency::EUR,
+                    available: amount("-5"),
+                    held: Amount::ZERO,
+                    total: amount("-5"),
+                    locked: false,
+                },
+                BalanceDelta {
+                    client: ClientId::new(2),
+                    currency: Currency::EUR,
+                    available: Amount::ZERO,
+                    held: amount("5"),
+                    total: amount("5"),
+                    locked: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fees_report_the_house_account() {
+        let config = ServiceConfig::from_toml(
+            r#"
+            [fees]
+            house_account = "900"
+            [fees.withdrawal]
+            flat = "0.5"
+            "#,
+        )
+        .unwrap();
+        let mut service = TransactionService::with_config(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+            config,
+        );
+        let corrections = "\
+type,client,tx,amount
+deposit,1,1,10
+withdrawal,1,2,4
+";
+
+        let report = reprocess(
+            &mut service,
+            InputRecord::records_from_csv(Cursor::new(corrections)).unwrap(),
+        )
+        .unwrap();
+
+        let totals: Vec<_> = report
+            .deltas
+            .iter()
+            .map(|delta| (delta.client.get(), delta.total.to_string()))
+            .collect();
+        assert_eq!(
+            totals,
+            vec![(1, "5.5000".to_string()), (900, "0.5000".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_new_clients_are_reported_from_zero() {
+        let mut service = service_with_history();
+        let corrections = "\
+type,client,tx,amount
+deposit,9,10,3
+withdrawal,8,11,1
+";
+
+        let report = reprocess(
+            &mut service,
+            InputRecord::records_from_csv(Cursor::new(corrections)).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            report.deltas,
+            vec![BalanceDelta {
+                client: ClientId::new(9),
+                currency: Currency::EUR,
+                available: amount("3"),
+                held: Amount::ZERO,
+                total: amount("3"),
+                locked: false,
+            }]
+        );
+        assert!(report.corrections[1].rejection.is_some());
+    }
+
+    #[test]
+    fn test_rejected_corrections_leave_the_state_alone() {
+        let mut service = service_with_history();
+        let corrections = "\
+type,client,tx,amount
+withdrawal,1,3,50
+dispute,2,1,
+deposit,1,2,5
+resolve,1,1,
+";
+
+        let report = reprocess(
+            &mut service,
+            InputRecord::records_from_csv(Cursor::new(corrections)).unwrap(),
+        )
+        .unwrap();
+
+        let codes: Vec<_> = report
+            .corrections
+            .iter()
+            .map(|c| c.rejection.as_ref().map(|r| r.code))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                Some("insufficient_funds"),
+                Some("client_mismatch"),
+                Some("duplicate_transaction"),
+                Some("not_under_dispute"),
+            ]
+        );
+        assert!(report.deltas.is_empty());
+        let client = service.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(client.balance(Currency::EUR).total.get(), amount("10"));
+        assert!(service.get_transaction(&TxId::new(3)).is_err());
+    }
+
+    #[test]
+    fn test_malformed_rows_are_reported_and_the_rest_applied() {
+        let mut service = service_with_history();
+        let corrections = "\
+type,client,tx,amount
+deposit,1,3,abc
+deposit,1
+deposit,70000,4,1
+deposit,1,5,2
+";
+
+        let report = reprocess(
+            &mut service,
+            InputRecord::records_from_csv(Cursor::new(corrections)).unwrap(),
+        )
+        .unwrap();
+
+        let codes: Vec<_> = report
+            .corrections
+            .iter()
+            .map(|c| (c.line, c.rejection.as_ref().map(|r| r.code)))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (2, Some("invalid_amount")),
+                (3, Some("missing_field")),
+                (4, Some("invalid_client")),
+                (5, None),
+            ]
+        );
+        assert_eq!(report.corrections[1].fields, vec!["deposit", "1"]);
+        assert_eq!(
+            report.deltas,
+            vec![BalanceDelta {
+                client: ClientId::new(1),
+                currency: Currency::EUR,
+                available: amount("2"),
+                held: Amount::ZERO,
+                total: amount("2"),
+                locked: false,
+            }]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use crate::domain::currency::Currency;
+use crate::domain::dispute_policy::DisputePolicy;
+use crate::domain::fee_schedule::FeeSchedule;
+use crate::domain::hold_expiry::HoldExpiry;
+use crate::domain::rate::RateTable;
+use crate::domain::rounding::Rounding;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceConfig {
+    pub dispute_policy: DisputePolicy,
+    pub hold_expiry: HoldExpiry,
+    pub fees: FeeSchedule,
+    pub idempotent_replays: bool,
+    // Currency of records without one, and of the balances printed per client.
+    pub default_currency: Currency,
+    // Applied to converted amounts and fees that don't fit in four decimals.
+    pub rounding: Rounding,
+    // Loaded from the file given with `--rates`, not from the config.
+    #[serde(skip)]
+    pub rates: RateTable,
+}
+
+impl ServiceConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("Error reading config '{}': {}", path.display(), err))?;
+        Self::from_toml(&content)
+            .map_err(|err| anyhow::anyhow!("Error parsing config '{}': {}", path.display(), err))
+    }
+
+    pub fn from_toml(content: &str) -> anyhow::Result<Self> {
+        let config: Self = toml::from_str(content)?;
+        config.fees.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::client_id::ClientId;
+    use crate::domain::dispute_policy::WithdrawalDisputes;
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config = ServiceConfig::from_toml("").unwrap();
+
+        assert_eq!(config.dispute_policy, DisputePolicy::default());
+        assert!(!config.idempotent_replays);
+        assert_eq!(config.default_currency, Currency::EUR);
+        assert_eq!(config.rounding, Rounding::HalfEven);
+        assert_eq!(config.hold_expiry, HoldExpiry::default());
+        assert_eq!(config.fees, FeeSchedule::default());
+    }
+
+    #[test]
+    fn test_parse_dispute_policy() {
+        let config = ServiceConfig::from_toml(
+            "
+            idempotent_replays = true
+            default_currency = \"usd\"
+            rounding = \"half_up\"
+
+            [dispute_policy]
+            withdrawal_disputes = \"reverse\"
+            allow_negative_available = true
+            chargeback_locks_account = false
+
+            [hold_expiry]
+            after_records = 100
+
+            [fees]
+            house_account = \"9000\"
+            [fees.withdrawal]
+            flat = \"0.25\"
+            ",
+        )
+        .unwrap();
+
+        assert!(config.idempotent_replays);
+        assert_eq!(config.default_currency.to_string(), "USD");
+        assert_eq!(config.rounding, Rounding::HalfUp);
+        assert_eq!(config.hold_expiry.after_records, Some(100));
+        assert_eq!(config.hold_expiry.after_seconds, None);
+        assert_eq!(config.fees.house_account, Some(ClientId::new(9000)));
+        assert_eq!(
+            config.fees.withdrawal.map(|fee| fee.flat),
+            Some("0.25".parse().unwrap())
+        );
+        assert_eq!(
+            config.dispute_policy,
+            DisputePolicy {
+                allow_redispute_after_resolve: false,
+                withdrawal_disputes: WithdrawalDisputes::Reverse,
+                allow_negative_available: true,
+                chargeback_locks_account: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_reject_unknown_fields() {
+        let result = ServiceConfig::from_toml("[dispute_policy]\nlock_on_chargeback = true\n");
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,422 @@
+use crate::domain::client::Client;
+use crate::domain::client_id::ClientId;
+use crate::domain::input_record::InputRecord;
+use crate::domain::transaction::Transaction;
+use crate::domain::tx_id::TxId;
+use crate::domain::tx_type::TxType;
+use crate::error::{ClientError, TransactionError};
+use crate::repository::client_repository::{ClientRepository, ClientRepositoryImpl};
+use crate::repository::transaction_repository::TransactionRepositoryImpl;
+use crate::service::service_config::ServiceConfig;
+use crate::service::suspicious_activity::{
+    CrossClientAttempt, SharedReporter, SuspiciousActivityReporter,
+};
+use crate::service::transaction_service::TransactionService;
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, Sender, SyncSender, channel, sync_channel};
+use std::thread::{self, JoinHandle};
+
+const QUEUE_SIZE: usize = 1024;
+
+type Service = TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl>;
+
+enum Command {
+    Process(usize, InputRecord),
+    HasClient(ClientId, Sender<bool>),
+    GetTransaction(TxId, Sender<Option<Transaction>>),
+}
+
+#[derive(Debug)]
+pub struct RecordError {
+    pub index: usize,
+    pub tx: TxId,
+    pub error: anyhow::Error,
+}
+
+#[derive(Debug)]
+pub struct EngineOutput {
+    pub clients: Vec<Client>,
+    pub errors: Vec<RecordError>,
+}
+
+struct ShardOutput {
+    clients: Vec<Client>,
+    errors: Vec<RecordError>,
+}
+
+struct Shard {
+    sender: SyncSender<Command>,
+    handle: JoinHandle<anyhow::Result<ShardOutput>>,
+}
+
+pub struct ShardedEngine {
+    shards: Vec<Shard>,
+    // Shard of the client that first used each transaction ID for a new transaction.
+    owners: HashMap<TxId, usize>,
+    reporter: SharedReporter,
+    errors: Vec<RecordError>,
+    next_index: usize,
+}
+
+impl ShardedEngine {
+    pub fn new(
+        threads: usize,
+        config: ServiceConfig,
+        reporter: Box<dyn SuspiciousActivityReporter + Send>,
+    ) -> anyhow::Result<Self> {
+        if threads == 0 {
+            anyhow::bail!("The number of threads must be at least 1");
+        }
+
+        let reporter = SharedReporter::new(reporter);
+        let shards = (0..threads)
+            .map(|index| {
+                let (sender, receiver) = sync_channel(QUEUE_SIZE);
+                let mut service = TransactionService::with_config(
+                    ClientRepositoryImpl::new(),
+                    TransactionRepositoryImpl::new(),
+                    config.clone(),
+                );
+                service.set_reporter(Box::new(reporter.clone()));
+
+                let handle = thread::Builder::new()
+                    .name(format!("shard-{index}"))
+                    .spawn(move || run_shard(service, receiver))?;
+
+                Ok(Shard { sender, handle })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            shards,
+            owners: HashMap::new(),
+            reporter,
+            errors: Vec::new(),
+            next_index: 0,
+        })
+    }
+
+    pub fn process(&mut self, record: InputRecord) -> anyhow::Result<()> {
+        let index = self.next_index;
+        self.next_index += 1;
+
+        let shard = self.shard_for(&record.client);
+        let owner = self.owners.get(&record.tx).copied();
+
+        // Each shard only sees its own clients, so a transfer can't be applied atomically
+        // across two of them. Rejecting it would give a different ledger than a single-threaded
+        // run, so the whole run fails instead.
+        if let Some(to) = record
+            .to
+            .filter(|to| record.tx_type == TxType::Transfer && self.shard_for(to) != shard)
+        {
+            anyhow::bail!(
+                "Transfer {} from client {} to client {} crosses shards, which isn't supported with multiple threads",
+                record.tx,
+                record.client,
+                to
+            );
+        }
+
+        let new_transaction = matches!(
+            record.tx_type,
+            TxType::Deposit
+                | TxType::Withdrawal
+                | TxType::Transfer
+                | TxType::Exchange
+                | TxType::Authorize
+        );
+        match (record.tx_type, owner) {
+            (_, None) if new_transaction => {
+                self.owners.insert(record.tx, shard);
+            }
+            (_, Some(owner)) if new_transaction && owner != shard && record.amount.is_some() => {
+                if self.get_transaction(owner, record.tx)?.is_some() {
+                    return self.reject(
+                        index,
+                        &record,
+                        TransactionError::Duplicate { id: record.tx },
+                    );
+                }
+                self.owners.insert(record.tx, shard);
+            }
+            (
+                TxType::Dispute
+                | TxType::Resolve
+                | TxType::Chargeback
+                | TxType::Refund
+                | TxType::Reversal
+                | TxType::Capture
+                | TxType::Void,
+                Some(owner),
+            ) if owner != shard => {
+                if !self.has_client(shard, record.client)? {
+                    return self.reject(
+                        index,
+                        &record,
+                        ClientError::NotFound { id: record.client },
+                    );
+                }
+                if let Some(transaction) = self.get_transaction(owner, record.tx)? {
+                    self.reporter
+                        .report_cross_client_attempt(&CrossClientAttempt {
+                            tx_type: record.tx_type,
+                            tx: record.tx,
+                            client: record.client,
+                            owner: transaction.client_id,
+                        });
+                    return self.reject(
+                        index,
+                        &record,
+                        TransactionError::ClientMismatch {
+                            id: record.tx,
+                            client: record.client,
+                        },
+                    );
+                }
+            }
+            _ => {}
+        }
+
+        self.send(shard, Command::Process(index, record))
+    }
+
+    pub fn finish(self) -> anyhow::Result<EngineOutput> {
+        let mut clients = Vec::new();
+        let mut errors = self.errors;
+
+        for shard in self.shards {
+            drop(shard.sender);
+            let output = shard
+                .handle
+                .join()
+                .map_err(|_| anyhow::anyhow!("Shard worker panicked"))??;
+            clients.extend(output.clients);
+            errors.extend(output.errors);
+        }
+
+        clients.sort_by_key(|client| client.id.get());
+        errors.sort_by_key(|error| error.index);
+
+        Ok(EngineOutput { clients, errors })
+    }
+
+    fn shard_for(&self, client: &ClientId) -> usize {
+        client.get() as usize % self.shards.len()
+    }
+
+    fn send(&self, shard: usize, command: Command) -> anyhow::Result<()> {
+        self.shards[shard]
+            .sender
+            .send(command)
+            .map_err(|_| anyhow::anyhow!("Shard {} stopped unexpectedly", shard))
+    }
+
+    // Queries are queued behind every record already routed to the shard, so the answer
+    // reflects the same state a single-threaded run would see at this point.
+    fn has_client(&self, shard: usize, id: ClientId) -> anyhow::Result<bool> {
+        let (reply, response) = channel();
+        self.send(shard, Command::HasClient(id, reply))?;
+        response
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Shard {} stopped unexpectedly", shard))
+    }
+
+    fn get_transaction(&self, shard: usize, id: TxId) -> anyhow::Result<Option<Transaction>> {
+        let (reply, response) = channel();
+        self.send(shard, Command::GetTransaction(id, reply))?;
+        response
+            .recv()
+            .map_err(|_| anyhow::anyhow!("Shard {} stopped unexpectedly", shard))
+    }
+
+    fn reject(
+        &mut self,
+        index: usize,
+        record: &InputRecord,
+        error: impl Into<anyhow::Error>,
+    ) -> anyhow::Result<()> {
+        self.errors.push(RecordError {
+            index,
+            tx: record.tx,
+            error: error.into(),
+        });
+        Ok(())
+    }
+}
+
+fn run_shard(mut service: Service, receiver: Receiver<Command>) -> anyhow::Result<ShardOutput> {
+    let mut errors = Vec::new();
+
+    for command in receiver {
+        match command {
+            Command::Process(index, record) => {
+                if let Err(error) = service.process_transaction(&record) {
+                    errors.push(RecordError {
+                        index,
+                        tx: record.tx,
+                        error,
+                    });
+                }
+            }
+            Command::HasClient(id, reply) => {
+                let _ = reply.send(service.get_client(&id).is_ok());
+            }
+            Command::GetTransaction(id, reply) => {
+                let _ = reply.send(service.get_transaction(&id).ok());
+            }
+        }
+    }
+
+    Ok(ShardOutput {
+        clients: service.client_repository().get_all_clients()?.collect(),
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::currency::Currency;
+    use crate::domain::output_record::OutputRecord;
+    use crate::service::suspicious_activity::NoopReporter;
+
+    fn record(tx_type: TxType, client: u16, tx: u32, amount: Option<&str>) -> InputRecord {
+        InputRecord {
+            tx_type,
+            client: ClientId::new(client),
+            tx: TxId::new(tx),
+            amount: amount.map(|a| a.parse().unwrap()),
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
+        }
+    }
+
+    fn sample_records() -> Vec<InputRecord> {
+        let mut records = Vec::new();
+        for i in 0..200u32 {
+            let client = (i % 7) as u16;
+            records.push(record(TxType::Deposit, client, i * 10, Some("10.5")));
+            records.push(record(TxType::Withdrawal, client, i * 10 + 1, Some("3.25")));
+            if i % 3 == 0 {
+                records.push(record(TxType::Dispute, client, i * 10, None));
+            }
+            if i % 6 == 0 {
+                records.push(record(TxType::Resolve, client, i * 10, None));
+            }
+            if i % 9 == 3 {
+                records.push(record(TxType::Chargeback, client, i * 10, None));
+            }
+            // Cross-client references: duplicated ids and disputes on other clients' deposits.
+            records.push(record(TxType::Deposit, client + 1, i * 10, Some("1")));
+            records.push(record(TxType::Dispute, client + 2, i * 10, None));
+        }
+        // A failed withdrawal frees its id for another client's deposit.
+        records.push(record(TxType::Withdrawal, 50, 5000, Some("1")));
+        records.push(record(TxType::Deposit, 51, 5000, Some("2")));
+        records
+    }
+
+    fn single_threaded(records: &[InputRecord]) -> (Vec<OutputRecord>, Vec<(TxId, String)>) {
+        let mut service = TransactionService::new(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+        );
+        let errors = records
+            .iter()
+            .filter_map(|r| {
+                service
+                    .process_transaction(r)
+                    .err()
+                    .map(|e| (r.tx, e.to_string()))
+            })
+            .collect();
+        let mut clients: Vec<OutputRecord> = service.get_all_clients().unwrap().collect();
+        clients.sort_by_key(|c| c.client.get());
+        (clients, errors)
+    }
+
+    fn sharded(
+        records: &[InputRecord],
+        threads: usize,
+    ) -> (Vec<OutputRecord>, Vec<(TxId, String)>) {
+        let mut engine =
+            ShardedEngine::new(threads, ServiceConfig::default(), Box::new(NoopReporter)).unwrap();
+        for r in records {
+            engine.process(r.clone()).unwrap();
+        }
+        let output = engine.finish().unwrap();
+        let errors = output
+            .errors
+            .into_iter()
+            .map(|e| (e.tx, e.error.to_string()))
+            .collect();
+        let clients = output
+            .clients
+            .iter()
+            .map(|client| OutputRecord::new(client, Currency::EUR))
+            .collect();
+        (clients, errors)
+    }
+
+    fn assert_same_clients(expected: &[OutputRecord], actual: &[OutputRecord]) {
+        assert_eq!(expected.len(), actual.len());
+        for (e, a) in expected.iter().zip(actual) {
+            assert_eq!(e.client, a.client);
+            assert_eq!(e.available.get(), a.available.get());
+            assert_eq!(e.held.get(), a.held.get());
+            assert_eq!(e.total.get(), a.total.get());
+            assert_eq!(e.locked, a.locked);
+        }
+    }
+
+    #[test]
+    fn test_matches_single_threaded_results() {
+        let records = sample_records();
+        let (expected_clients, expected_errors) = single_threaded(&records);
+
+        for threads in [1, 2, 3, 8] {
+            let (clients, errors) = sharded(&records, threads);
+
+            assert_same_clients(&expected_clients, &clients);
+            assert_eq!(expected_errors, errors);
+        }
+    }
+
+    #[test]
+    fn test_cross_shard_transfer_fails_the_run() {
+        let transfer = |tx, to| InputRecord {
+            to: Some(ClientId::new(to)),
+            ..record(TxType::Transfer, 1, tx, Some("1"))
+        };
+        let records = vec![
+            record(TxType::Deposit, 1, 1, Some("5")),
+            record(TxType::Deposit, 2, 2, Some("5")),
+            record(TxType::Deposit, 3, 3, Some("5")),
+            transfer(4, 3),
+        ];
+        let (expected_clients, expected_errors) = single_threaded(&records);
+
+        let (clients, errors) = sharded(&records, 2);
+        assert_same_clients(&expected_clients, &clients);
+        assert_eq!(expected_errors, errors);
+
+        let mut engine =
+            ShardedEngine::new(2, ServiceConfig::default(), Box::new(NoopReporter)).unwrap();
+        for r in records {
+            engine.process(r).unwrap();
+        }
+        let err = engine.process(transfer(5, 2)).unwrap_err();
+        assert!(err.to_string().contains("crosses shards"));
+    }
+
+    #[test]
+    fn test_zero_threads_is_rejected() {
+        let result = ShardedEngine::new(0, ServiceConfig::default(), Box::new(NoopReporter));
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::repository::client_repository::ClientRepository;
+use crate::repository::transaction_repository::TransactionRepository;
+use crate::service::line_protocol::{handle_request, parse_request};
+use crate::service::transaction_service::TransactionService;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+// Every connection gets its own thread, records from all of them are applied one at a time
+// through the shared service.
+pub struct SocketServer<T, V>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    service: Arc<Mutex<TransactionService<T, V>>>,
+}
+
+impl<T, V> SocketServer<T, V>
+where
+    T: ClientRepository + Send + 'static,
+    V: TransactionRepository + Send + 'static,
+{
+    pub fn new(service: TransactionService<T, V>) -> Self {
+        Self {
+            service: Arc::new(Mutex::new(service)),
+        }
+    }
+
+    pub fn serve(&self, listener: Listener) -> anyhow::Result<()> {
+        match listener {
+            Listener::Tcp(listener) => {
+                for stream in listener.incoming() {
+                    match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                        Ok((reader, writer)) => self.spawn(reader, writer),
+                        Err(err) => eprintln!("Error accepting connection: {}", err),
+                    }
+                }
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                for stream in listener.incoming() {
+                    match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                        Ok((reader, writer)) => self.spawn(reader, writer),
+                        Err(err) => eprintln!("Error accepting connection: {}", err),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn spawn<R, W>(&self, reader: R, writer: W)
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let service = Arc::clone(&self.service);
+        thread::spawn(move || {
+            if let Err(err) = handle_connection(&service, reader, writer) {
+                eprintln!("Connection closed: {}", err);
+            }
+        });
+    }
+}
+
+fn handle_connection<T, V>(
+    service: &Mutex<TransactionService<T, V>>,
+    reader: impl Read,
+    writer: impl Write,
+) -> anyhow::Result<()>
+where
+    T: ClientRepository,
+    V: TransactionRepository,
+{
+    let mut writer = BufWriter::new(writer);
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let reply = match parse_request(&line) {
+            Ok(request) => {
+                let mut service = service
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("Transaction service lock poisoned"))?;
+                handle_request(&mut service, request)
+            }
+            Err(reply) => reply,
+        };
+        writeln!(writer, "{}", reply)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::client_repository::ClientRepositoryImpl;
+    use crate::repository::transaction_repository::TransactionRepositoryImpl;
+    use std::io::Lines;
+    use std::net::TcpStream;
+
+    fn start(listener: Listener) {
+        let server = SocketServer::new(TransactionService::new(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+        ));
+        thread::spawn(move || server.serve(listener));
+    }
+
+    fn send<R: BufRead>(writer: &mut impl Write, replies: &mut Lines<R>, line: &str) -> String {
+        writeln!(writer, "{}", line).unwrap();
+        replies.next().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_concurrent_tcp_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        start(Listener::Tcp(listener));
+
+        let clients: Vec<_> = (1..=4u32)
+            .map(|client| {
+                thread::spawn(move || {
+                    let mut stream = TcpStream::connect(address).unwrap();
+                    let mut replies = BufReader::new(stream.try_clone().unwrap()).lines();
+                    for i in 0..25 {
+                        let tx = client * 100 + i;
+                        let reply = send(
+                            &mut stream,
+                            &mut replies,
+                            &format!("deposit,{},{},1", client, tx),
+                        );
+                        assert_eq!(reply, "ok");
+                    }
+                })
+            })
+            .collect();
+        for client in clients {
+            client.join().unwrap();
+        }
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        let mut replies = BufReader::new(stream.try_clone().unwrap()).lines();
+        for client in 1..=4 {
+            assert_eq!(
+                send(&mut stream, &mut replies, &format!("balance {}", client)),
+                format!("ok {},25.0000,0.0000,25.0000,false", client)
+            );
+        }
+        assert_eq!(
+            send(&mut stream, &mut replies, "deposit,1,101,1"),
+            "error duplicate_transaction transaction with id 101 already exists"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unix_socket_connection() {
+        use std::os::unix::net::UnixStream;
+
+        let path = std::env::temp_dir().join(format!("server-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        start(Listener::Unix(UnixListener::bind(&path).unwrap()));
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        let mut replies = BufReader::new(stream.try_clone().unwrap()).lines();
+        assert_eq!(send(&mut stream, &mut replies, "deposit,7,1,3"), "ok");
+        assert_eq!(
+            send(&mut stream, &mut replies, "balance 7"),
+            "ok 7,3.0000,0.0000,3.0000,false"
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,353 @@
+use crate::domain::amount::Amount;
+use crate::domain::client_id::ClientId;
+use crate::domain::currency::Currency;
+use crate::domain::event::Event;
+use crate::domain::tx_id::TxId;
+use crate::domain::tx_type::TxType;
+use crate::repository::client_repository::ClientRepositoryImpl;
+use crate::repository::transaction_repository::TransactionRepositoryImpl;
+use crate::service::transaction_service::TransactionService;
+use serde::Serialize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StatementLine {
+    pub client: ClientId,
+    pub tx: TxId,
+    #[serde(rename = "type")]
+    pub tx_type: TxType,
+    pub amount: Amount,
+    pub currency: Currency,
+    pub available: Amount,
+    pub held: Amount,
+    pub total: Amount,
+    pub locked: bool,
+}
+
+impl StatementLine {
+    pub const COLUMNS: [&'static str; 9] = [
+        "client",
+        "tx",
+        "type",
+        "amount",
+        "currency",
+        "available",
+        "held",
+        "total",
+        "locked",
+    ];
+}
+
+// The event log is the only record of every applied operation in order, disputes included.
+// It is replayed through a fresh service like the `replay` command does, and each line gets
+// the client's balances in the operation's currency right after it. Lines are grouped by
+// client, oldest first.
+pub fn statement<I>(events: I, client: Option<ClientId>) -> anyhow::Result<Vec<StatementLine>>
+where
+    I: IntoIterator<Item = anyhow::Result<Event>>,
+{
+    let mut service = TransactionService::new(
+        ClientRepositoryImpl::new(),
+        TransactionRepositoryImpl::new(),
+    );
+    let mut lines: Vec<StatementLine> = Vec::new();
+
+    for (index, event) in events.into_iter().enumerate() {
+        let event = event?;
+        service
+            .apply_event(&event)
+            .map_err(|err| anyhow::anyhow!("Error replaying event {}: {}", index + 1, err))?;
+
+        let fee = matches!(event, Event::FeeCharged { .. } | Event::FeeRefunded { .. });
+        let (tx, tx_type, amount) = match event {
+            Event::Deposited { tx, amount, .. } => (tx, TxType::Deposit, amount),
+            Event::Withdrawn { tx, amount, .. } => (tx, TxType::Withdrawal, amount),
+            Event::Transferred { tx, amount, .. } => (tx, TxType::Transfer, amount),
+            Event::Exchanged { tx, amount, .. } => (tx, TxType::Exchange, amount),
+            Event::Authorized { tx, amount, .. } => (tx, TxType::Authorize, amount),
+            Event::Captured { tx, amount, .. } => (tx, TxType::Capture, amount),
+            // Expired holds are shown as voids.
+            Event::Voided { tx, amount, .. } => (tx, TxType::Void, amount),
+            Event::FundsHeld { tx, amount, .. } => (tx, TxType::Dispute, amount),
+            Event::FundsReleased { tx, amount, .. } => (tx, TxType::Resolve, amount),
+            Event::ChargedBack { tx, amount, .. } => (tx, TxType::Chargeback, amount),
+            Event::Refunded { tx, amount, .. } => (tx, TxType::Refund, amount),
+            Event::Reversed { tx, amount, .. } => (tx, TxType::Reversal, amount),
+            Event::FeeCharged { parent, amount, .. } => (parent, TxType::Fee, amount),
+            Event::FeeRefunded { tx, amount, .. } => (tx, TxType::Refund, amount),
+            // Always logged right after the chargeback that caused it.
+            Event::AccountLocked { client } => {
+                if let Some(line) = lines.iter_mut().rev().find(|line| line.client == client) {
+                    line.locked = true;
+                }
+                continue;
+            }
+            // Listed by the audit trail instead.
+            Event::StatusChanged { .. } | Event::Rejected { .. } => continue,
+        };
+
+        // Transfers, fees, and disputes of transfers change the balances of both parties. An
+        // exchange changes two balances of the same client.
+        let transaction = if fee {
+            service.get_fee(&tx)?
+        } else {
+            service.get_transaction(&tx)?
+        };
+        let legs = std::iter::once((transaction.client_id, transaction.currency, amount))
+            .chain(
+                transaction
+                    .destination_id
+                    .map(|to| (to, transaction.currency, amount)),
+            )
+            .chain(
+                transaction
+                    .destination_currency
+                    .zip(transaction.destination_amount)
+                    .map(|(currency, amount)| (transaction.client_id, currency, amount)),
+            );
+        for (party, currency, amount) in
+            legs.filter(|(party, ..)| client.is_none_or(|client| client == *party))
+        {
+            let party = service.get_client(&party)?;
+            let balance = party.balance(currency);
+            lines.push(StatementLine {
+                client: party.id,
+                tx,
+                tx_type,
+                amount,
+                currency,
+                available: balance.available.get(),
+                held: balance.held.get(),
+                total: balance.total.get(),
+                locked: party.is_locked(),
+            });
+        }
+    }
+
+    lines.sort_by_key(|line| line.client.get());
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(value: &str) -> Amount {
+        value.parse().unwrap()
+    }
+
+    fn events() -> Vec<anyhow::Result<Event>> {
+        let (one, two) = (ClientId::new(1), ClientId::new(2));
+        vec![
+            Event::Deposited {
+                client: one,
+                tx: TxId::new(1),
+                amount: amount("10"),
+                currency: Currency::EUR,
+            },
+            Event::Deposited {
+                client: two,
+                tx: TxId::new(2),
+                amount: amount("5"),
+                currency: Currency::EUR,
+            },
+            Event::Withdrawn {
+                client: one,
+                tx: TxId::new(3),
+                amount: amount("3"),
+                currency: Currency::EUR,
+            },
+            Event::Rejected {
+                tx_type: TxType::Withdrawal,
+                client: one,
+                tx: TxId::new(4),
+                reason: "insufficient funds".to_string(),
+            },
+            Event::FundsHeld {
+                client: one,
+                tx: TxId::new(1),
+                amount: amount("10"),
+            },
+            Event::ChargedBack {
+                client: one,
+                tx: TxId::new(1),
+                amount: amount("10"),
+            },
+            Event::AccountLocked { client: one },
+        ]
+        .into_iter()
+        .map(Ok)
+        .collect()
+    }
+
+    #[test]
+    fn test_running_balances_for_one_client() {
+        let lines = statement(events(), Some(ClientId::new(1))).unwrap();
+
+        let balances: Vec<_> = lines
+            .iter()
+            .map(|line| {
+                (
+                    line.tx_type,
+                    line.available.to_string(),
+                    line.held.to_string(),
+                    line.total.to_string(),
+                    line.locked,
+                )
+            })
+            .collect();
+        assert_eq!(
+            balances,
+            vec![
+                (
+                    TxType::Deposit,
+                    "10.0000".to_string(),
+                    "0.0000".to_string(),
+                    "10.0000".to_string(),
+                    false
+                ),
+                (
+                    TxType::Withdrawal,
+                    "7.0000".to_string(),
+                    "0.0000".to_string(),
+                    "7.0000".to_string(),
+                    false
+                ),
+                (
+                    TxType::Dispute,
+                    "-3.0000".to_string(),
+                    "10.0000".to_string(),
+                    "7.0000".to_string(),
+                    false
+                ),
+                (
+                    TxType::Chargeback,
+                    "-3.0000".to_string(),
+                    "0.0000".to_string(),
+                    "-3.0000".to_string(),
+                    true
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_all_clients_are_grouped() {
+        let lines = statement(events(), None).unwrap();
+
+        let order: Vec<_> = lines
+            .iter()
+            .map(|line| (line.client.get(), line.tx.get()))
+            .collect();
+        assert_eq!(order, vec![(1, 1), (1, 3), (1, 1), (1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn test_transfer_gives_a_line_to_both_parties() {
+        let with_transfer = || {
+            let mut events = events();
+            events.insert(
+                4,
+                Ok(Event::Transferred {
+                    client: ClientId::new(2),
+                    to: ClientId::new(1),
+                    tx: TxId::new(5),
+                    amount: amount("2"),
+                    currency: Currency::EUR,
+                }),
+            );
+            events
+        };
+        let transfers = |lines: Vec<StatementLine>| -> Vec<_> {
+            lines
+                .iter()
+                .filter(|line| line.tx_type == TxType::Transfer)
+                .map(|line| (line.client.get(), line.total.to_string()))
+                .collect()
+        };
+
+        let lines = statement(with_transfer(), None).unwrap();
+        assert_eq!(
+            transfers(lines),
+            vec![(1, "9.0000".to_string()), (2, "3.0000".to_string())]
+        );
+
+        let lines = statement(with_transfer(), Some(ClientId::new(2))).unwrap();
+        assert_eq!(transfers(lines), vec![(2, "3.0000".to_string())]);
+    }
+
+    #[test]
+    fn test_balances_are_in_the_operation_currency() {
+        let mut events = events();
+        events.insert(
+            1,
+            Ok(Event::Deposited {
+                client: ClientId::new(1),
+                tx: TxId::new(6),
+                amount: amount("4"),
+                currency: "USD".parse().unwrap(),
+            }),
+        );
+
+        let lines = statement(events, Some(ClientId::new(1))).unwrap();
+
+        let balances: Vec<_> = lines
+            .iter()
+            .take(3)
+            .map(|line| (line.currency.to_string(), line.total.to_string()))
+            .collect();
+        assert_eq!(
+            balances,
+            vec![
+                ("EUR".to_string(), "10.0000".to_string()),
+                ("USD".to_string(), "4.0000".to_string()),
+                ("EUR".to_string(), "7.0000".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exchange_gives_a_line_per_currency() {
+        let mut events = events();
+        events.insert(
+            3,
+            Ok(Event::Exchanged {
+                client: ClientId::new(1),
+                tx: TxId::new(7),
+                amount: amount("2"),
+                currency: Currency::EUR,
+                to_amount: amount("2.17"),
+                to_currency: "USD".parse().unwrap(),
+                rate: "1.085".parse().unwrap(),
+            }),
+        );
+
+        let lines = statement(events, Some(ClientId::new(1))).unwrap();
+
+        let exchange: Vec<_> = lines
+            .iter()
+            .filter(|line| line.tx_type == TxType::Exchange)
+            .map(|line| {
+                (
+                    line.currency.to_string(),
+                    line.amount.to_string(),
+                    line.total.to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            exchange,
+            vec![
+                (
+                    "EUR".to_string(),
+                    "2.0000".to_string(),
+                    "5.0000".to_string()
+                ),
+                (
+                    "USD".to_string(),
+                    "2.1700".to_string(),
+                    "2.1700".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -2,29 +2,61 @@ use crate::domain::{client::Client, client_id::ClientId, transaction::Transactio
 use crate::error::{ClientError, TransactionError};
 use crate::repository::{
     client_repository::ClientRepository, transaction_repository::TransactionRepository,
+    unit_of_work::UnitOfWork,
 };
+use std::ops::Range;
 
 pub struct TestClientRepository {
     client: Option<Client>,
+    snapshot: Option<Option<Client>>,
+}
+
+impl Default for TestClientRepository {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TestClientRepository {
     pub fn new() -> Self {
-        Self { client: None }
+        Self {
+            client: None,
+            snapshot: None,
+        }
     }
 
     pub fn with_client(client: Client) -> Self {
         Self {
             client: Some(client),
+            snapshot: None,
         }
     }
 }
 
+impl UnitOfWork for TestClientRepository {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        self.snapshot = Some(self.client.clone());
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        self.snapshot = None;
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        if let Some(client) = self.snapshot.take() {
+            self.client = client;
+        }
+        Ok(())
+    }
+}
+
 impl ClientRepository for TestClientRepository {
     fn get_client(&self, id: &ClientId) -> anyhow::Result<Client, ClientError> {
         match &self.client {
             Some(client) if client.id == *id => Ok(client.clone()),
-            _ => Err(ClientError::NotFound { id: id.clone() }),
+            _ => Err(ClientError::NotFound { id: *id }),
         }
     }
 
@@ -38,20 +70,34 @@ impl ClientRepository for TestClientRepository {
         Ok(client.clone())
     }
 
-    fn get_all_clients(&self) -> impl Iterator<Item = &Client> {
-        Vec::<&Client>::new().into_iter()
+    fn get_all_clients(&self) -> anyhow::Result<impl Iterator<Item = Client>, ClientError> {
+        Ok(self.client.clone().into_iter())
     }
 }
 
 pub struct TestTransactionRepository {}
 
+impl UnitOfWork for TestTransactionRepository {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
 impl TransactionRepository for TestTransactionRepository {
     fn get_transaction(&self, id: &TxId) -> Result<Transaction, TransactionError> {
-        Err(TransactionError::NotFound { id: id.clone() })
+        Err(TransactionError::NotFound { id: *id })
     }
 
     fn get_transaction_under_dispute(&self, id: &TxId) -> Result<Transaction, TransactionError> {
-        Err(TransactionError::NotFound { id: id.clone() })
+        Err(TransactionError::NotFound { id: *id })
     }
 
     fn create_transaction(
@@ -67,23 +113,69 @@ impl TransactionRepository for TestTransactionRepository {
     ) -> Result<Transaction, TransactionError> {
         Ok(transaction.clone())
     }
+
+    fn get_transactions_for_client(
+        &self,
+        _client_id: &ClientId,
+        _range: Range<usize>,
+    ) -> Result<Vec<Transaction>, TransactionError> {
+        Ok(Vec::new())
+    }
+
+    fn get_expiring_authorizations(&self) -> Result<Vec<Transaction>, TransactionError> {
+        Ok(Vec::new())
+    }
+
+    fn get_record_count(&self) -> Result<u64, TransactionError> {
+        Ok(0)
+    }
+
+    fn set_record_count(&mut self, _count: u64) -> Result<(), TransactionError> {
+        Ok(())
+    }
+
+    fn get_fee(&self, parent: &TxId) -> Result<Transaction, TransactionError> {
+        Err(TransactionError::NotFound { id: *parent })
+    }
+
+    fn create_fee(&mut self, fee: &Transaction) -> Result<Transaction, TransactionError> {
+        Ok(fee.clone())
+    }
+
+    fn update_fee(&mut self, fee: &Transaction) -> Result<Transaction, TransactionError> {
+        Ok(fee.clone())
+    }
 }
 
 pub struct DisputeTransactionRepository {
     pub transaction: Transaction,
 }
 
+impl UnitOfWork for DisputeTransactionRepository {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
 impl TransactionRepository for DisputeTransactionRepository {
     fn get_transaction(&self, id: &TxId) -> Result<Transaction, TransactionError> {
         if self.transaction.id == *id {
             Ok(self.transaction.clone())
         } else {
-            Err(TransactionError::NotFound { id: id.clone() })
+            Err(TransactionError::NotFound { id: *id })
         }
     }
 
     fn get_transaction_under_dispute(&self, id: &TxId) -> Result<Transaction, TransactionError> {
-        Err(TransactionError::NotFound { id: id.clone() })
+        Err(TransactionError::NotFound { id: *id })
     }
 
     fn create_transaction(
@@ -99,18 +191,69 @@ impl TransactionRepository for DisputeTransactionRepository {
     ) -> Result<Transaction, TransactionError> {
         Ok(transaction.clone())
     }
+
+    fn get_transactions_for_client(
+        &self,
+        client_id: &ClientId,
+        range: Range<usize>,
+    ) -> Result<Vec<Transaction>, TransactionError> {
+        Ok(
+            (self.transaction.client_id == *client_id && range.contains(&0))
+                .then(|| self.transaction.clone())
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn get_expiring_authorizations(&self) -> Result<Vec<Transaction>, TransactionError> {
+        Ok(Vec::new())
+    }
+
+    fn get_record_count(&self) -> Result<u64, TransactionError> {
+        Ok(0)
+    }
+
+    fn set_record_count(&mut self, _count: u64) -> Result<(), TransactionError> {
+        Ok(())
+    }
+
+    fn get_fee(&self, parent: &TxId) -> Result<Transaction, TransactionError> {
+        Err(TransactionError::NotFound { id: *parent })
+    }
+
+    fn create_fee(&mut self, fee: &Transaction) -> Result<Transaction, TransactionError> {
+        Ok(fee.clone())
+    }
+
+    fn update_fee(&mut self, fee: &Transaction) -> Result<Transaction, TransactionError> {
+        Ok(fee.clone())
+    }
 }
 
 pub struct ChargebackTransactionRepository {
     pub transaction: Transaction,
 }
 
+impl UnitOfWork for ChargebackTransactionRepository {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
 impl TransactionRepository for ChargebackTransactionRepository {
     fn get_transaction(&self, id: &TxId) -> Result<Transaction, TransactionError> {
         if self.transaction.id == *id {
             Ok(self.transaction.clone())
         } else {
-            Err(TransactionError::NotFound { id: id.clone() })
+            Err(TransactionError::NotFound { id: *id })
         }
     }
 
@@ -118,7 +261,7 @@ impl TransactionRepository for ChargebackTransactionRepository {
         if self.transaction.id == *id {
             Ok(self.transaction.clone())
         } else {
-            Err(TransactionError::NotFound { id: id.clone() })
+            Err(TransactionError::NotFound { id: *id })
         }
     }
 
@@ -135,4 +278,113 @@ impl TransactionRepository for ChargebackTransactionRepository {
     ) -> Result<Transaction, TransactionError> {
         Ok(transaction.clone())
     }
+
+    fn get_transactions_for_client(
+        &self,
+        client_id: &ClientId,
+        range: Range<usize>,
+    ) -> Result<Vec<Transaction>, TransactionError> {
+        Ok(
+            (self.transaction.client_id == *client_id && range.contains(&0))
+                .then(|| self.transaction.clone())
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    fn get_expiring_authorizations(&self) -> Result<Vec<Transaction>, TransactionError> {
+        Ok(Vec::new())
+    }
+
+    fn get_record_count(&self) -> Result<u64, TransactionError> {
+        Ok(0)
+    }
+
+    fn set_record_count(&mut self, _count: u64) -> Result<(), TransactionError> {
+        Ok(())
+    }
+
+    fn get_fee(&self, parent: &TxId) -> Result<Transaction, TransactionError> {
+        Err(TransactionError::NotFound { id: *parent })
+    }
+
+    fn create_fee(&mut self, fee: &Transaction) -> Result<Transaction, TransactionError> {
+        Ok(fee.clone())
+    }
+
+    fn update_fee(&mut self, fee: &Transaction) -> Result<Transaction, TransactionError> {
+        Ok(fee.clone())
+    }
+}
+
+pub struct FailingTransactionRepository {}
+
+impl UnitOfWork for FailingTransactionRepository {
+    fn begin(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+impl TransactionRepository for FailingTransactionRepository {
+    fn get_transaction(&self, id: &TxId) -> Result<Transaction, TransactionError> {
+        Err(TransactionError::NotFound { id: *id })
+    }
+
+    fn get_transaction_under_dispute(&self, id: &TxId) -> Result<Transaction, TransactionError> {
+        Err(TransactionError::NotFound { id: *id })
+    }
+
+    fn create_transaction(
+        &mut self,
+        _transaction: &Transaction,
+    ) -> Result<Transaction, TransactionError> {
+        Err(anyhow::anyhow!("storage unavailable").into())
+    }
+
+    fn update_transaction(
+        &mut self,
+        _transaction: &Transaction,
+    ) -> Result<Transaction, TransactionError> {
+        Err(anyhow::anyhow!("storage unavailable").into())
+    }
+
+    fn get_transactions_for_client(
+        &self,
+        _client_id: &ClientId,
+        _range: Range<usize>,
+    ) -> Result<Vec<Transaction>, TransactionError> {
+        Ok(Vec::new())
+    }
+
+    fn get_expiring_authorizations(&self) -> Result<Vec<Transaction>, TransactionError> {
+        Ok(Vec::new())
+    }
+
+    fn get_record_count(&self) -> Result<u64, TransactionError> {
+        Ok(0)
+    }
+
+    fn set_record_count(&mut self, _count: u64) -> Result<(), TransactionError> {
+        Ok(())
+    }
+
+    fn get_fee(&self, parent: &TxId) -> Result<Transaction, TransactionError> {
+        Err(TransactionError::NotFound { id: *parent })
+    }
+
+    fn create_fee(&mut self, _fee: &Transaction) -> Result<Transaction, TransactionError> {
+        Err(anyhow::anyhow!("storage unavailable").into())
+    }
+
+    fn update_fee(&mut self, _fee: &Transaction) -> Result<Transaction, TransactionError> {
+        Err(anyhow::anyhow!("storage unavailable").into())
+    }
 }
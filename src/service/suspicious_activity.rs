@@ -0,0 +1,163 @@
+use crate::domain::client_id::ClientId;
+use crate::domain::tx_id::TxId;
+use crate::domain::tx_type::TxType;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrossClientAttempt {
+    pub tx_type: TxType,
+    pub tx: TxId,
+    pub client: ClientId,
+    pub owner: ClientId,
+}
+
+pub trait SuspiciousActivityReporter {
+    fn report_cross_client_attempt(&mut self, attempt: &CrossClientAttempt);
+}
+
+pub struct NoopReporter;
+
+impl SuspiciousActivityReporter for NoopReporter {
+    fn report_cross_client_attempt(&mut self, _attempt: &CrossClientAttempt) {}
+}
+
+#[derive(Clone)]
+pub struct SharedReporter {
+    inner: Arc<Mutex<Box<dyn SuspiciousActivityReporter + Send>>>,
+}
+
+impl SharedReporter {
+    pub fn new(reporter: Box<dyn SuspiciousActivityReporter + Send>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(reporter)),
+        }
+    }
+}
+
+impl SuspiciousActivityReporter for SharedReporter {
+    fn report_cross_client_attempt(&mut self, attempt: &CrossClientAttempt) {
+        match self.inner.lock() {
+            Ok(mut reporter) => reporter.report_cross_client_attempt(attempt),
+            Err(_) => eprintln!("Error reporting suspicious activity: reporter lock poisoned"),
+        }
+    }
+}
+
+pub struct CsvReporter<W: Write> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> CsvReporter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header_written: false,
+        }
+    }
+
+    fn write(&mut self, attempt: &CrossClientAttempt) -> std::io::Result<()> {
+        if !self.header_written {
+            writeln!(self.writer, "type,client,tx,owner")?;
+            self.header_written = true;
+        }
+        writeln!(
+            self.writer,
+            "{},{},{},{}",
+            attempt.tx_type, attempt.client, attempt.tx, attempt.owner
+        )?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> SuspiciousActivityReporter for CsvReporter<W> {
+    fn report_cross_client_attempt(&mut self, attempt: &CrossClientAttempt) {
+        if let Err(err) = self.write(attempt) {
+            eprintln!("Error reporting suspicious activity: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn attempt() -> CrossClientAttempt {
+        CrossClientAttempt {
+            tx_type: TxType::Dispute,
+            tx: TxId::new(1),
+            client: ClientId::new(2),
+            owner: ClientId::new(1),
+        }
+    }
+
+    #[test]
+    fn test_csv_reporter_writes_header_once() {
+        let mut reporter = CsvReporter::new(Vec::new());
+        let attempt = attempt();
+
+        reporter.report_cross_client_attempt(&attempt);
+        reporter.report_cross_client_attempt(&attempt);
+
+        assert_eq!(
+            String::from_utf8(reporter.writer).unwrap(),
+            "type,client,tx,owner\ndispute,2,1,1\ndispute,2,1,1\n"
+        );
+    }
+
+    struct FullWriter;
+
+    impl Write for FullWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_csv_reporter_write_errors_do_not_stop_processing() {
+        let mut reporter = CsvReporter::new(FullWriter);
+
+        reporter.report_cross_client_attempt(&attempt());
+        reporter.report_cross_client_attempt(&attempt());
+
+        assert!(!reporter.header_written);
+    }
+
+    #[test]
+    fn test_shared_reporter_clones_write_to_the_same_reporter() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let reporter =
+            SharedReporter::new(Box::new(CsvReporter::new(SharedBuffer(output.clone()))));
+        let mut first = reporter.clone();
+        let mut second = reporter;
+
+        first.report_cross_client_attempt(&attempt());
+        second.report_cross_client_attempt(&CrossClientAttempt {
+            tx_type: TxType::Chargeback,
+            ..attempt()
+        });
+
+        assert_eq!(
+            String::from_utf8(output.lock().unwrap().clone()).unwrap(),
+            "type,client,tx,owner\ndispute,2,1,1\nchargeback,2,1,1\n"
+        );
+    }
+
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -1,14 +1,46 @@
+use crate::domain::amount::Amount;
 use crate::domain::client::Client;
 use crate::domain::client_id::ClientId;
+use crate::domain::client_status::ClientStatus;
+use crate::domain::currency::Currency;
+use crate::domain::dispute_policy::{DisputePolicy, WithdrawalDisputes};
+use crate::domain::event::Event;
+use crate::domain::fee_schedule::FeeSchedule;
+use crate::domain::hold_expiry::HoldExpiry;
 use crate::domain::input_record::InputRecord;
 use crate::domain::output_record::OutputRecord;
 use crate::domain::transaction::Transaction;
 use crate::domain::transaction_status::TransactionStatus;
 use crate::domain::tx_id::TxId;
 use crate::domain::tx_type::TxType;
-use crate::error::ClientError;
+use crate::error::{ClientError, TransactionError};
 use crate::repository::client_repository::ClientRepository;
 use crate::repository::transaction_repository::TransactionRepository;
+use crate::service::event_log::{EventLog, NoopEventLog};
+use crate::service::service_config::ServiceConfig;
+use crate::service::suspicious_activity::{
+    CrossClientAttempt, NoopReporter, SuspiciousActivityReporter,
+};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Events in the log have already passed every check, so replaying them must not fail on
+// policy decisions taken when they were recorded. Locks are replayed from `AccountLocked`,
+// operator changes from `StatusChanged`.
+const REPLAY_POLICY: DisputePolicy = DisputePolicy {
+    allow_redispute_after_resolve: true,
+    withdrawal_disputes: WithdrawalDisputes::Reverse,
+    allow_negative_available: true,
+    chargeback_locks_account: false,
+};
+
+// Fees are replayed from `FeeCharged`.
+const REPLAY_FEES: FeeSchedule = FeeSchedule {
+    house_account: None,
+    deposit: None,
+    withdrawal: None,
+};
 
 pub struct TransactionService<T, V>
 where
@@ -17,6 +49,31 @@ where
 {
     client_repository: T,
     transaction_repository: V,
+    config: ServiceConfig,
+    reporter: Box<dyn SuspiciousActivityReporter + Send>,
+    event_log: Box<dyn EventLog + Send>,
+    // Records processed so far, the clock of `HoldExpiry::after_records`.
+    records: u64,
+    // Open authorizations with a deadline. Both are loaded from the repository before the
+    // first record, so the holds of an earlier run over the same state expire too.
+    holds: HashMap<TxId, Hold>,
+    holds_loaded: bool,
+}
+
+struct Hold {
+    client: ClientId,
+    after_record: Option<u64>,
+    at: Option<u64>,
+}
+
+impl Hold {
+    fn of(transaction: &Transaction) -> Self {
+        Self {
+            client: transaction.client_id,
+            after_record: transaction.expires_after_record,
+            at: transaction.expires_at,
+        }
+    }
 }
 
 impl<T, V> TransactionService<T, V>
@@ -25,41 +82,465 @@ where
     V: TransactionRepository,
 {
     pub fn new(client_repository: T, transaction_repository: V) -> Self {
+        Self::with_config(
+            client_repository,
+            transaction_repository,
+            ServiceConfig::default(),
+        )
+    }
+
+    pub fn with_config(
+        client_repository: T,
+        transaction_repository: V,
+        config: ServiceConfig,
+    ) -> Self {
         Self {
             client_repository,
             transaction_repository,
+            config,
+            reporter: Box::new(NoopReporter),
+            event_log: Box::new(NoopEventLog),
+            records: 0,
+            holds: HashMap::new(),
+            holds_loaded: false,
         }
     }
 
+    pub fn set_reporter(&mut self, reporter: Box<dyn SuspiciousActivityReporter + Send>) {
+        self.reporter = reporter;
+    }
+
+    pub fn set_event_log(&mut self, event_log: Box<dyn EventLog + Send>) {
+        self.event_log = event_log;
+    }
+
     pub fn process_transaction(&mut self, record: &InputRecord) -> anyhow::Result<Client> {
-        match record.tx_type {
-            TxType::Dispute | TxType::Resolve | TxType::Chargeback => {
-                self.process_existing_transaction(record.tx_type, record.tx, record.client)
+        self.load_holds()?;
+        self.expire_holds()?;
+        let counted = self.counts_records();
+        let result = self.apply_record(record, counted);
+        self.records += 1;
+        // A rejected record's unit of work was rolled back, so it is counted in one of its own.
+        if result.is_err() && counted {
+            let records = self.records;
+            self.in_unit_of_work(|service| {
+                Ok(service.transaction_repository.set_record_count(records)?)
+            })?;
+        }
+
+        if result.is_ok() {
+            match record.tx_type {
+                TxType::Authorize => self.track_hold(record.tx)?,
+                TxType::Capture | TxType::Void => {
+                    self.holds.remove(&record.tx);
+                }
+                _ => {}
+            }
+        }
+        result
+    }
+
+    fn apply_record(&mut self, record: &InputRecord, counted: bool) -> anyhow::Result<Client> {
+        let policy = self.config.dispute_policy;
+        // Events are appended before the unit of work commits, so a failing log keeps the
+        // record from being applied.
+        let result = self.in_unit_of_work(|service| {
+            if let Some(amount) = record.amount {
+                amount.positive()?;
+            }
+            let mut events = Vec::new();
+            let client = match record.tx_type {
+                TxType::Dispute | TxType::Resolve | TxType::Chargeback => {
+                    service.process_existing_transaction(record, &policy, &mut events)?
+                }
+                TxType::Refund => service.refund(record, &mut events)?,
+                TxType::Reversal => service.reverse(record, &mut events)?,
+                TxType::Deposit
+                | TxType::Withdrawal
+                | TxType::Transfer
+                | TxType::Exchange
+                | TxType::Authorize => {
+                    let transaction =
+                        Transaction::from_record(record, service.config.default_currency)?;
+                    let transaction = service.with_deadline(service.price(transaction)?);
+                    let fees = service.config.fees.clone();
+                    service.process_new_transaction(transaction, &fees, &mut events)?
+                }
+                TxType::Capture | TxType::Void => {
+                    let status = if record.tx_type == TxType::Capture {
+                        TransactionStatus::Captured
+                    } else {
+                        TransactionStatus::Voided
+                    };
+                    service.settle_authorization(
+                        record.tx,
+                        record.client,
+                        status,
+                        record.amount,
+                        record.currency,
+                        &mut events,
+                    )?
+                }
+                TxType::Unlock | TxType::Freeze | TxType::Close => {
+                    service.change_status(record, &mut events)?
+                }
+                TxType::Fee => {
+                    return Err(TransactionError::InvalidTransaction { id: record.tx }.into());
+                }
+            };
+            if counted {
+                service
+                    .transaction_repository
+                    .set_record_count(service.records + 1)?;
             }
-            TxType::Deposit | TxType::Withdrawal => {
-                let transaction = Transaction::try_from(record)?;
-                self.process_new_transaction(transaction)
+            for event in &events {
+                service.event_log.append(event)?;
             }
+            Ok(client)
+        });
+
+        if let Err(err) = &result {
+            self.event_log.append(&Event::Rejected {
+                tx_type: record.tx_type,
+                client: record.client,
+                tx: record.tx,
+                reason: err.to_string(),
+            })?;
         }
+
+        result
+    }
+
+    pub fn apply_event(&mut self, event: &Event) -> anyhow::Result<()> {
+        self.in_unit_of_work(|service| {
+            let mut events = Vec::new();
+            match *event {
+                Event::Deposited {
+                    client,
+                    tx,
+                    amount,
+                    currency,
+                } => {
+                    let transaction = Transaction {
+                        currency,
+                        ..Transaction::new(tx, client, amount, TxType::Deposit)
+                    };
+                    service.process_new_transaction(transaction, &REPLAY_FEES, &mut events)?;
+                }
+                Event::Withdrawn {
+                    client,
+                    tx,
+                    amount,
+                    currency,
+                } => {
+                    let transaction = Transaction {
+                        currency,
+                        ..Transaction::new(tx, client, amount, TxType::Withdrawal)
+                    };
+                    service.process_new_transaction(transaction, &REPLAY_FEES, &mut events)?;
+                }
+                Event::Transferred {
+                    client,
+                    to,
+                    tx,
+                    amount,
+                    currency,
+                } => {
+                    let transaction = Transaction {
+                        currency,
+                        ..Transaction::transfer(tx, client, to, amount)
+                    };
+                    service.process_new_transaction(transaction, &REPLAY_FEES, &mut events)?;
+                }
+                Event::Exchanged {
+                    client,
+                    tx,
+                    amount,
+                    currency,
+                    to_amount,
+                    to_currency,
+                    rate,
+                } => {
+                    let transaction = Transaction {
+                        currency,
+                        destination_amount: Some(to_amount),
+                        rate: Some(rate),
+                        ..Transaction::exchange(tx, client, amount, to_currency)
+                    };
+                    service.process_new_transaction(transaction, &REPLAY_FEES, &mut events)?;
+                }
+                Event::Authorized {
+                    client,
+                    tx,
+                    amount,
+                    currency,
+                } => {
+                    let transaction = Transaction {
+                        currency,
+                        ..Transaction::authorization(tx, client, amount)
+                    };
+                    service.process_new_transaction(transaction, &REPLAY_FEES, &mut events)?;
+                }
+                Event::Captured { client, tx, amount } => {
+                    service.settle_authorization(
+                        tx,
+                        client,
+                        TransactionStatus::Captured,
+                        Some(amount),
+                        None,
+                        &mut events,
+                    )?;
+                }
+                Event::Voided {
+                    client,
+                    tx,
+                    expired,
+                    ..
+                } => {
+                    let status = if expired {
+                        TransactionStatus::Expired
+                    } else {
+                        TransactionStatus::Voided
+                    };
+                    service.settle_authorization(tx, client, status, None, None, &mut events)?;
+                }
+                Event::FundsHeld { client, tx, amount } => {
+                    let record = replayed_record(TxType::Dispute, client, tx, amount);
+                    service.process_existing_transaction(&record, &REPLAY_POLICY, &mut events)?;
+                }
+                Event::FundsReleased { client, tx, amount } => {
+                    let record = replayed_record(TxType::Resolve, client, tx, amount);
+                    service.process_existing_transaction(&record, &REPLAY_POLICY, &mut events)?;
+                }
+                Event::ChargedBack { client, tx, amount } => {
+                    let record = replayed_record(TxType::Chargeback, client, tx, amount);
+                    service.process_existing_transaction(&record, &REPLAY_POLICY, &mut events)?;
+                }
+                Event::Refunded { client, tx, amount } => {
+                    service.refund(
+                        &replayed_record(TxType::Refund, client, tx, amount),
+                        &mut events,
+                    )?;
+                }
+                Event::Reversed {
+                    client,
+                    tx,
+                    ref reason,
+                    ref operator,
+                    ..
+                } => {
+                    let record = InputRecord {
+                        amount: None,
+                        reason: Some(reason.clone()),
+                        operator: Some(operator.clone()),
+                        ..replayed_record(TxType::Reversal, client, tx, Amount::ZERO)
+                    };
+                    service.reverse(&record, &mut events)?;
+                }
+                Event::FeeCharged {
+                    client,
+                    to,
+                    parent,
+                    amount,
+                    ..
+                } => {
+                    let mut client = service.client_repository.get_client(&client)?;
+                    let parent = service.transaction_repository.get_transaction(&parent)?;
+                    let fee = Transaction::fee(&parent, to, amount);
+                    service.book_fee(&mut client, fee, &mut events)?;
+                    service.client_repository.update_client(&client)?;
+                }
+                // Given back again by the chargeback replayed right before.
+                Event::FeeRefunded { .. } => {}
+                Event::AccountLocked { client } => {
+                    let mut client = service.client_repository.get_client(&client)?;
+                    client.status = ClientStatus::Locked;
+                    service.client_repository.update_client(&client)?;
+                }
+                Event::StatusChanged { client, to, .. } => {
+                    let mut client = service.client_repository.get_client(&client)?;
+                    client.status = to;
+                    service.client_repository.update_client(&client)?;
+                }
+                Event::Rejected { .. } => {}
+            }
+            Ok(())
+        })
+    }
+
+    pub fn client_repository(&self) -> &T {
+        &self.client_repository
     }
 
-    pub fn get_all_clients(&self) -> impl Iterator<Item = OutputRecord> {
+    pub fn transaction_repository(&self) -> &V {
+        &self.transaction_repository
+    }
+
+    pub fn get_client(&self, id: &ClientId) -> anyhow::Result<Client, ClientError> {
+        self.client_repository.get_client(id)
+    }
+
+    pub fn get_transaction(&self, id: &TxId) -> anyhow::Result<Transaction, TransactionError> {
+        self.transaction_repository.get_transaction(id)
+    }
+
+    // Fees are kept under the ID of the transaction they were charged on.
+    pub fn get_fee(&self, parent: &TxId) -> anyhow::Result<Transaction, TransactionError> {
+        self.transaction_repository.get_fee(parent)
+    }
+
+    pub fn get_transactions_for_client(
+        &self,
+        client_id: &ClientId,
+        range: Range<usize>,
+    ) -> anyhow::Result<Vec<Transaction>, TransactionError> {
+        self.transaction_repository
+            .get_transactions_for_client(client_id, range)
+    }
+
+    pub fn default_currency(&self) -> Currency {
+        self.config.default_currency
+    }
+
+    pub fn house_account(&self) -> Option<ClientId> {
+        self.config.fees.house_account
+    }
+
+    pub fn get_all_clients(&self) -> anyhow::Result<impl Iterator<Item = OutputRecord>> {
+        let currency = self.config.default_currency;
+        Ok(self
+            .client_repository
+            .get_all_clients()?
+            .map(move |client| OutputRecord::new(&client, currency)))
+    }
+
+    pub fn get_all_balances(&self) -> anyhow::Result<impl Iterator<Item = OutputRecord>> {
+        let currency = self.config.default_currency;
+        Ok(self
+            .client_repository
+            .get_all_clients()?
+            .flat_map(move |client| OutputRecord::per_currency(&client, currency)))
+    }
+
+    pub fn get_consolidated_balances(
+        &self,
+        currency: Currency,
+    ) -> anyhow::Result<Vec<OutputRecord>> {
         self.client_repository
-            .get_all_clients()
-            .map(OutputRecord::from)
+            .get_all_clients()?
+            .map(|client| {
+                OutputRecord::consolidated(
+                    &client,
+                    currency,
+                    &self.config.rates,
+                    self.config.rounding,
+                )
+            })
+            .collect()
     }
 
-    fn process_new_transaction(&mut self, transaction: Transaction) -> anyhow::Result<Client> {
-        let client = match transaction.tx_type {
+    fn process_new_transaction(
+        &mut self,
+        transaction: Transaction,
+        fees: &FeeSchedule,
+        events: &mut Vec<Event>,
+    ) -> anyhow::Result<Client> {
+        match self.transaction_repository.get_transaction(&transaction.id) {
+            Ok(existing)
+                if self.config.idempotent_replays && existing.is_replay_of(&transaction) =>
+            {
+                return Ok(self.client_repository.get_client(&existing.client_id)?);
+            }
+            Ok(_) => return Err(TransactionError::Duplicate { id: transaction.id }.into()),
+            Err(TransactionError::NotFound { .. }) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let (mut client, event) = match transaction.tx_type {
             TxType::Deposit => {
                 let mut client = self.get_or_create_client(&transaction.client_id)?;
-                client.deposit(transaction.amount)?;
-                client
+                client.ensure_open()?;
+                client.deposit(transaction.currency, transaction.amount)?;
+                let event = Event::Deposited {
+                    client: client.id,
+                    tx: transaction.id,
+                    amount: transaction.amount,
+                    currency: transaction.currency,
+                };
+                (client, event)
             }
             TxType::Withdrawal => {
                 let mut client = self.client_repository.get_client(&transaction.client_id)?;
-                client.withdraw(transaction.amount)?;
-                client
+                client.ensure_can_debit()?;
+                client.withdraw(transaction.currency, transaction.amount)?;
+                let event = Event::Withdrawn {
+                    client: client.id,
+                    tx: transaction.id,
+                    amount: transaction.amount,
+                    currency: transaction.currency,
+                };
+                (client, event)
+            }
+            // Both accounts are checked before anything moves; the recipient must exist.
+            TxType::Transfer => {
+                let to = transaction
+                    .destination_id
+                    .ok_or(TransactionError::InvalidTransaction { id: transaction.id })?;
+                let mut client = self.client_repository.get_client(&transaction.client_id)?;
+                let mut recipient = self.client_repository.get_client(&to)?;
+                client.ensure_can_debit()?;
+                recipient.ensure_not_locked()?;
+                recipient.ensure_open()?;
+                client.transfer_out(transaction.currency, transaction.amount)?;
+                recipient.deposit(transaction.currency, transaction.amount)?;
+                self.client_repository.update_client(&recipient)?;
+                let event = Event::Transferred {
+                    client: client.id,
+                    to,
+                    tx: transaction.id,
+                    amount: transaction.amount,
+                    currency: transaction.currency,
+                };
+                (client, event)
+            }
+            TxType::Authorize => {
+                let mut client = self.client_repository.get_client(&transaction.client_id)?;
+                client.ensure_can_debit()?;
+                client.authorize(transaction.currency, transaction.amount)?;
+                let event = Event::Authorized {
+                    client: client.id,
+                    tx: transaction.id,
+                    amount: transaction.amount,
+                    currency: transaction.currency,
+                };
+                (client, event)
+            }
+            TxType::Exchange => {
+                let (Some(to_currency), Some(to_amount), Some(rate)) = (
+                    transaction.destination_currency,
+                    transaction.destination_amount,
+                    transaction.rate,
+                ) else {
+                    return Err(TransactionError::InvalidTransaction { id: transaction.id }.into());
+                };
+                let mut client = self.client_repository.get_client(&transaction.client_id)?;
+                client.ensure_can_debit()?;
+                client.exchange(
+                    transaction.currency,
+                    transaction.amount,
+                    to_currency,
+                    to_amount,
+                )?;
+                let event = Event::Exchanged {
+                    client: client.id,
+                    tx: transaction.id,
+                    amount: transaction.amount,
+                    currency: transaction.currency,
+                    to_amount,
+                    to_currency,
+                    rate,
+                };
+                (client, event)
             }
             _ => anyhow::bail!(
                 "Invalid transaction type for new transaction: {:?}",
@@ -67,59 +548,521 @@ where
             ),
         };
 
-        self.client_repository.update_client(&client)?;
+        events.push(event);
+        let fee = self.fee_for(&transaction, fees)?;
 
         self.transaction_repository
             .create_transaction(&transaction)?;
+        if let Some(fee) = fee {
+            self.book_fee(&mut client, fee, events)?;
+        }
+        self.client_repository.update_client(&client)?;
 
         Ok(client)
     }
 
+    // The house account pays no fees itself.
+    fn fee_for(
+        &self,
+        transaction: &Transaction,
+        fees: &FeeSchedule,
+    ) -> anyhow::Result<Option<Transaction>> {
+        let Some(house) = fees
+            .house_account
+            .filter(|house| *house != transaction.client_id)
+        else {
+            return Ok(None);
+        };
+        let amount = fees.fee(
+            transaction.tx_type,
+            transaction.amount,
+            self.config.rounding,
+        )?;
+        if amount == Amount::ZERO {
+            return Ok(None);
+        }
+        Ok(Some(Transaction::fee(transaction, house, amount)))
+    }
+
+    // The fee is taken from the client's available funds, after the deposit or on top of the
+    // withdrawal, and stored as a fee of the transaction it was charged on.
+    fn book_fee(
+        &mut self,
+        client: &mut Client,
+        fee: Transaction,
+        events: &mut Vec<Event>,
+    ) -> anyhow::Result<()> {
+        let house_id = fee
+            .destination_id
+            .ok_or(TransactionError::InvalidTransaction { id: fee.id })?;
+        let mut house = self.get_or_create_client(&house_id)?;
+        client.pay_fee(fee.currency, fee.amount)?;
+        house.deposit(fee.currency, fee.amount)?;
+
+        self.client_repository.update_client(&house)?;
+        self.transaction_repository.create_fee(&fee)?;
+        events.push(Event::FeeCharged {
+            client: client.id,
+            to: house_id,
+            parent: fee.id,
+            amount: fee.amount,
+            currency: fee.currency,
+        });
+        Ok(())
+    }
+
+    // A fee is given back once the transaction it was charged on is charged back in full.
+    fn refund_fee(&mut self, client: &mut Client, parent: TxId) -> anyhow::Result<Option<Event>> {
+        let mut fee = match self.transaction_repository.get_fee(&parent) {
+            Ok(fee) => fee,
+            Err(TransactionError::NotFound { .. }) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let house_id = fee
+            .destination_id
+            .ok_or(TransactionError::InvalidTransaction { id: parent })?;
+        let mut house = self.client_repository.get_client(&house_id)?;
+        house.return_fee(fee.currency, fee.amount)?;
+        client.deposit(fee.currency, fee.amount)?;
+        fee.refunded_amount = Some(fee.amount);
+
+        self.transaction_repository.update_fee(&fee)?;
+        self.client_repository.update_client(&house)?;
+        Ok(Some(Event::FeeRefunded {
+            client: client.id,
+            tx: parent,
+            amount: fee.amount,
+        }))
+    }
+
     fn process_existing_transaction(
         &mut self,
-        tx_type: TxType,
-        tx_id: TxId,
-        client_id: ClientId,
+        record: &InputRecord,
+        policy: &DisputePolicy,
+        events: &mut Vec<Event>,
     ) -> anyhow::Result<Client> {
+        let (tx_type, tx_id, client_id) = (record.tx_type, record.tx, record.client);
         let mut client = self.client_repository.get_client(&client_id)?;
+        let mut transaction = self.owned_transaction(tx_type, tx_id, client_id, record.currency)?;
 
-        match tx_type {
-            TxType::Dispute => {
-                let transaction = self.transaction_repository.get_transaction(&tx_id)?;
-                client.dispute(transaction.amount)?;
+        let amount = transaction.transition(tx_type, record.amount, policy)?;
+        client.ensure_open()?;
 
-                let mut updated_tx = transaction.clone();
-                updated_tx.status = TransactionStatus::Disputed;
-                self.transaction_repository
-                    .update_transaction(&updated_tx)?;
+        // A transfer is disputed by the sender, and the funds are held at the recipient. A
+        // chargeback moves them back to the sender and locks the recipient like a deposit's.
+        let mut recipient = match transaction.destination_id {
+            Some(to) => Some(self.client_repository.get_client(&to)?),
+            None => None,
+        };
+        if let Some(recipient) = &recipient {
+            recipient.ensure_open()?;
+        }
+        let was_locked = client.is_locked();
+        let recipient_was_locked = recipient.as_ref().is_some_and(Client::is_locked);
+        let currency = transaction.currency;
+        match (tx_type, transaction.tx_type, recipient.as_mut()) {
+            (TxType::Dispute, TxType::Deposit, _) => client.dispute(currency, amount, policy)?,
+            (TxType::Resolve, TxType::Deposit, _) => client.resolve(currency, amount)?,
+            (TxType::Chargeback, TxType::Deposit, _) => {
+                client.chargeback(currency, amount, policy)?
             }
-            TxType::Resolve => {
-                let transaction = self
-                    .transaction_repository
-                    .get_transaction_under_dispute(&tx_id)?;
-                client.resolve(transaction.amount)?;
+            (TxType::Dispute, TxType::Withdrawal, _) => {
+                client.dispute_withdrawal(currency, amount)?
             }
-            TxType::Chargeback => {
-                let transaction = self
-                    .transaction_repository
-                    .get_transaction_under_dispute(&tx_id)?;
-                client.chargeback(transaction.amount)?;
+            (TxType::Resolve, TxType::Withdrawal, _) => {
+                client.resolve_withdrawal(currency, amount)?
+            }
+            (TxType::Chargeback, TxType::Withdrawal, _) => {
+                client.chargeback_withdrawal(currency, amount, policy)?
+            }
+            (TxType::Dispute, TxType::Transfer, Some(recipient)) => {
+                recipient.dispute(currency, amount, policy)?
+            }
+            (TxType::Resolve, TxType::Transfer, Some(recipient)) => {
+                recipient.resolve(currency, amount)?
+            }
+            (TxType::Chargeback, TxType::Transfer, Some(recipient)) => {
+                recipient.chargeback(currency, amount, policy)?;
+                client.deposit(currency, amount)?;
             }
             _ => anyhow::bail!(
                 "Invalid transaction type for existing transaction: {:?}",
                 tx_type
             ),
         }
+        // Whatever was refunded before is out of reach of the chargeback.
+        let fee_refund = if tx_type == TxType::Chargeback
+            && transaction.charged_back()
+                == transaction.amount.checked_sub(transaction.refunded())?
+        {
+            self.refund_fee(&mut client, transaction.id)?
+        } else {
+            None
+        };
+
+        self.transaction_repository
+            .update_transaction(&transaction)?;
+        self.client_repository.update_client(&client)?;
+        if let Some(recipient) = &recipient {
+            self.client_repository.update_client(recipient)?;
+        }
+
+        events.push(match tx_type {
+            TxType::Dispute => Event::FundsHeld {
+                client: client_id,
+                tx: tx_id,
+                amount,
+            },
+            TxType::Resolve => Event::FundsReleased {
+                client: client_id,
+                tx: tx_id,
+                amount,
+            },
+            _ => Event::ChargedBack {
+                client: client_id,
+                tx: tx_id,
+                amount,
+            },
+        });
+        if client.is_locked() && !was_locked {
+            events.push(Event::AccountLocked { client: client_id });
+        }
+        if let Some(recipient) = recipient.filter(|r| r.is_locked() && !recipient_was_locked) {
+            events.push(Event::AccountLocked {
+                client: recipient.id,
+            });
+        }
+        events.extend(fee_refund);
+
+        Ok(client)
+    }
+
+    // Disputes, captures, voids, refunds and reversals reference a transaction of the client
+    // they are sent for, and may name the currency it is in.
+    fn owned_transaction(
+        &mut self,
+        tx_type: TxType,
+        tx_id: TxId,
+        client_id: ClientId,
+        currency: Option<Currency>,
+    ) -> anyhow::Result<Transaction> {
+        let transaction = self.transaction_repository.get_transaction(&tx_id)?;
+
+        if transaction.client_id != client_id {
+            self.reporter
+                .report_cross_client_attempt(&CrossClientAttempt {
+                    tx_type,
+                    tx: tx_id,
+                    client: client_id,
+                    owner: transaction.client_id,
+                });
+            return Err(TransactionError::ClientMismatch {
+                id: tx_id,
+                client: client_id,
+            }
+            .into());
+        }
+        if let Some(currency) = currency.filter(|currency| *currency != transaction.currency) {
+            return Err(TransactionError::CurrencyMismatch {
+                id: tx_id,
+                currency,
+            }
+            .into());
+        }
+
+        Ok(transaction)
+    }
+
+    // A capture withdraws up to the authorized amount and releases the rest; a void or an
+    // expiry releases all of it. Expiries are applied whatever the account status.
+    fn settle_authorization(
+        &mut self,
+        tx_id: TxId,
+        client_id: ClientId,
+        status: TransactionStatus,
+        amount: Option<Amount>,
+        currency: Option<Currency>,
+        events: &mut Vec<Event>,
+    ) -> anyhow::Result<Client> {
+        let tx_type = match status {
+            TransactionStatus::Captured => TxType::Capture,
+            _ => TxType::Void,
+        };
+        let mut client = self.client_repository.get_client(&client_id)?;
+        let mut transaction = self.owned_transaction(tx_type, tx_id, client_id, currency)?;
+        transaction.settle(status)?;
+
+        let authorized = transaction.amount;
+        let event = match status {
+            TransactionStatus::Captured => {
+                let captured = amount.unwrap_or(authorized);
+                if captured > authorized {
+                    return Err(TransactionError::CaptureExceedsAuthorization { id: tx_id }.into());
+                }
+                client.ensure_can_debit()?;
+                client.capture(transaction.currency, authorized, captured)?;
+                transaction.captured_amount = Some(captured);
+                Event::Captured {
+                    client: client_id,
+                    tx: tx_id,
+                    amount: captured,
+                }
+            }
+            _ => {
+                if amount.is_some() {
+                    return Err(TransactionError::InvalidTransaction { id: tx_id }.into());
+                }
+                if status == TransactionStatus::Voided {
+                    client.ensure_open()?;
+                }
+                client.void(transaction.currency, authorized)?;
+                Event::Voided {
+                    client: client_id,
+                    tx: tx_id,
+                    amount: authorized,
+                    expired: status == TransactionStatus::Expired,
+                }
+            }
+        };
+
+        self.transaction_repository
+            .update_transaction(&transaction)?;
+        self.client_repository.update_client(&client)?;
+        events.push(event);
+        Ok(client)
+    }
+
+    // The record count of the deadline starts with the record after the authorization.
+    fn with_deadline(&self, transaction: Transaction) -> Transaction {
+        let expiry = self.config.hold_expiry;
+        if transaction.tx_type != TxType::Authorize || expiry == HoldExpiry::default() {
+            return transaction;
+        }
+        Transaction {
+            expires_after_record: expiry.after_records.map(|n| self.records + 1 + n),
+            expires_at: expiry.after_seconds.map(|n| now() + n),
+            ..transaction
+        }
+    }
+
+    // The record count is only stored while something expires after a number of records.
+    fn counts_records(&self) -> bool {
+        self.config.hold_expiry.after_records.is_some()
+            || self.holds.values().any(|hold| hold.after_record.is_some())
+    }
+
+    // The deadline is read back from the stored authorization, which an idempotent replay
+    // leaves as it was.
+    fn track_hold(&mut self, tx: TxId) -> anyhow::Result<()> {
+        if self.config.hold_expiry == HoldExpiry::default() {
+            return Ok(());
+        }
+        let transaction = self.transaction_repository.get_transaction(&tx)?;
+        if transaction.is_expiring() {
+            self.holds.insert(tx, Hold::of(&transaction));
+        }
+        Ok(())
+    }
+
+    fn load_holds(&mut self) -> anyhow::Result<()> {
+        if self.holds_loaded {
+            return Ok(());
+        }
+        self.records = self.transaction_repository.get_record_count()?;
+        for transaction in self.transaction_repository.get_expiring_authorizations()? {
+            self.holds.insert(transaction.id, Hold::of(&transaction));
+        }
+        self.holds_loaded = true;
+        Ok(())
+    }
+
+    // Each expiry is its own unit of work, logged like a void.
+    fn expire_holds(&mut self) -> anyhow::Result<()> {
+        if self.holds.is_empty() {
+            return Ok(());
+        }
+        let (records, now) = (self.records, now());
+        let mut due: Vec<TxId> = self
+            .holds
+            .iter()
+            .filter(|(_, hold)| {
+                hold.after_record.is_some_and(|r| r <= records) || hold.at.is_some_and(|t| t <= now)
+            })
+            .map(|(tx, _)| *tx)
+            .collect();
+        due.sort_by_key(TxId::get);
+
+        for tx in due {
+            let Some(hold) = self.holds.remove(&tx) else {
+                continue;
+            };
+            self.in_unit_of_work(|service| {
+                let mut events = Vec::new();
+                service.settle_authorization(
+                    tx,
+                    hold.client,
+                    TransactionStatus::Expired,
+                    None,
+                    None,
+                    &mut events,
+                )?;
+                for event in &events {
+                    service.event_log.append(event)?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    // Refunds give back a deposit without a dispute, so they never lock the account.
+    fn refund(&mut self, record: &InputRecord, events: &mut Vec<Event>) -> anyhow::Result<Client> {
+        let mut client = self.client_repository.get_client(&record.client)?;
+        let mut transaction =
+            self.owned_transaction(record.tx_type, record.tx, record.client, record.currency)?;
+        let amount = transaction.refund(record.amount)?;
+        client.ensure_can_debit()?;
+        client.refund(transaction.currency, amount)?;
+
+        self.transaction_repository
+            .update_transaction(&transaction)?;
+        self.client_repository.update_client(&client)?;
+        events.push(Event::Refunded {
+            client: client.id,
+            tx: record.tx,
+            amount,
+        });
+        Ok(client)
+    }
+
+    // Reversals are made by operators, who give a reason like for status changes. Reversing a
+    // deposit takes back what wasn't refunded; reversing a withdrawal credits it back.
+    fn reverse(&mut self, record: &InputRecord, events: &mut Vec<Event>) -> anyhow::Result<Client> {
+        let (None, None, Some(reason), Some(operator)) =
+            (record.amount, record.to, &record.reason, &record.operator)
+        else {
+            return Err(TransactionError::InvalidTransaction { id: record.tx }.into());
+        };
+        let mut client = self.client_repository.get_client(&record.client)?;
+        let mut transaction =
+            self.owned_transaction(record.tx_type, record.tx, record.client, record.currency)?;
+        let amount = transaction.reverse()?;
+        client.ensure_open()?;
+        match transaction.tx_type {
+            TxType::Deposit => client.reverse_deposit(transaction.currency, amount)?,
+            _ => client.deposit(transaction.currency, amount)?,
+        }
+
+        self.transaction_repository
+            .update_transaction(&transaction)?;
+        self.client_repository.update_client(&client)?;
+        events.push(Event::Reversed {
+            client: client.id,
+            tx: record.tx,
+            amount,
+            reason: reason.clone(),
+            operator: operator.clone(),
+        });
+        Ok(client)
+    }
 
+    // Admin records only change the account status; they are not stored as transactions, and
+    // their `tx` is just a reference for the audit trail.
+    fn change_status(
+        &mut self,
+        record: &InputRecord,
+        events: &mut Vec<Event>,
+    ) -> anyhow::Result<Client> {
+        let (Some(to), None, None, Some(reason), Some(operator)) = (
+            record.tx_type.target_status(),
+            record.amount,
+            record.to,
+            &record.reason,
+            &record.operator,
+        ) else {
+            return Err(TransactionError::InvalidTransaction { id: record.tx }.into());
+        };
+        let mut client = self.client_repository.get_client(&record.client)?;
+        let from = client.status;
+        client.change_status(to)?;
         self.client_repository.update_client(&client)?;
+
+        events.push(Event::StatusChanged {
+            client: client.id,
+            tx: record.tx,
+            from,
+            to,
+            reason: reason.clone(),
+            operator: operator.clone(),
+        });
         Ok(client)
     }
 
+    // Exchanges are priced at the rate in effect when they are processed. The rate and the
+    // converted amount are stored with the transaction, so replays don't need the table.
+    fn price(&self, transaction: Transaction) -> anyhow::Result<Transaction> {
+        let Some(to) = transaction.destination_currency else {
+            return Ok(transaction);
+        };
+        let from = transaction.currency;
+        let rate =
+            self.config
+                .rates
+                .rate(from, to, now())
+                .ok_or(TransactionError::RateNotFound {
+                    id: transaction.id,
+                    from,
+                    to,
+                })?;
+        let destination_amount = rate.convert(transaction.amount, self.config.rounding)?;
+        if destination_amount == Amount::ZERO {
+            return Err(TransactionError::ExchangeRoundsToZero { id: transaction.id }.into());
+        }
+        Ok(Transaction {
+            destination_amount: Some(destination_amount),
+            rate: Some(rate),
+            ..transaction
+        })
+    }
+
+    fn in_unit_of_work<R>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> anyhow::Result<R>,
+    ) -> anyhow::Result<R> {
+        self.client_repository.begin()?;
+        if let Err(err) = self.transaction_repository.begin() {
+            self.client_repository.rollback()?;
+            return Err(err);
+        }
+
+        // Units are closed in reverse order so that nested savepoints on a shared
+        // connection are released from the innermost one.
+        match f(self) {
+            Ok(value) => {
+                if let Err(err) = self.transaction_repository.commit() {
+                    self.transaction_repository.rollback()?;
+                    self.client_repository.rollback()?;
+                    return Err(err);
+                }
+                if let Err(err) = self.client_repository.commit() {
+                    self.client_repository.rollback()?;
+                    return Err(err);
+                }
+                Ok(value)
+            }
+            Err(err) => {
+                self.transaction_repository.rollback()?;
+                self.client_repository.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
     fn get_or_create_client(&mut self, id: &ClientId) -> anyhow::Result<Client, ClientError> {
         match self.client_repository.get_client(id) {
             Ok(client) => Ok(client),
             Err(ClientError::NotFound { .. }) => {
-                let client = Client::new(id.clone());
+                let client = Client::new(*id);
                 self.client_repository.create_client(&client)
             }
             Err(err) => Err(err),
@@ -127,35 +1070,102 @@ where
     }
 }
 
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+// Logged disputes, resolves and chargebacks carry the amount they applied to.
+fn replayed_record(tx_type: TxType, client: ClientId, tx: TxId, amount: Amount) -> InputRecord {
+    InputRecord {
+        tx_type,
+        client,
+        tx,
+        amount: Some(amount),
+        to: None,
+        currency: None,
+        to_currency: None,
+        reason: None,
+        operator: None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::Amount;
     use crate::domain::ClientStatus;
+    use crate::domain::rate::RateTable;
+    use crate::domain::rounding::Rounding;
+    use crate::domain::transaction_status::TransactionStatus;
+    use crate::error::error_code::error_code;
+    use crate::repository::client_repository::ClientRepositoryImpl;
+    use crate::repository::snapshot::{Checkpoint, Snapshot};
+    use crate::repository::sqlite_store::SqliteStore;
+    use crate::repository::transaction_repository::TransactionRepositoryImpl;
     use crate::service::stubs::*;
+    use std::sync::{Arc, Mutex};
+
+    fn record(tx_type: TxType, client: u16, tx: u32, amount: Option<&str>) -> InputRecord {
+        InputRecord {
+            tx_type,
+            client: ClientId::new(client),
+            tx: TxId::new(tx),
+            amount: amount.map(|a| a.parse().unwrap()),
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
+        }
+    }
+
+    fn transfer(client: u16, to: u16, tx: u32, amount: &str) -> InputRecord {
+        InputRecord {
+            to: Some(ClientId::new(to)),
+            ..record(TxType::Transfer, client, tx, Some(amount))
+        }
+    }
+
+    fn in_memory_service(
+        config: ServiceConfig,
+    ) -> TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl> {
+        TransactionService::with_config(
+            ClientRepositoryImpl::new(),
+            TransactionRepositoryImpl::new(),
+            config,
+        )
+    }
 
     #[test]
     fn test_process_deposit_transaction() {
         let client_id = ClientId::try_from("1".to_string()).unwrap();
         let tx_id = TxId::try_from("100".to_string()).unwrap();
-        let amount = 5.0;
+        let amount: Amount = "5".parse().unwrap();
 
         let client_repo = TestClientRepository::new();
         let transaction_repo = TestTransactionRepository {};
         let mut service = TransactionService::new(client_repo, transaction_repo);
 
         let input_record = InputRecord {
-            client: client_id.clone(),
+            client: client_id,
             tx: tx_id,
             tx_type: TxType::Deposit,
             amount: Some(amount),
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
         };
 
         let client = service.process_transaction(&input_record).unwrap();
 
         assert_eq!(client.id, client_id);
-        assert_eq!(client.available.get(), amount);
-        assert_eq!(client.total.get(), amount);
-        assert_eq!(client.held.get(), 0.0);
+        assert_eq!(client.balance(Currency::EUR).available.get(), amount);
+        assert_eq!(client.balance(Currency::EUR).total.get(), amount);
+        assert_eq!(client.balance(Currency::EUR).held.get(), Amount::ZERO);
         assert_eq!(client.status, ClientStatus::Active);
     }
 
@@ -163,11 +1173,13 @@ mod tests {
     fn test_process_withdrawal_transaction() {
         let client_id = ClientId::try_from("1".to_string()).unwrap();
         let tx_id = TxId::try_from("100".to_string()).unwrap();
-        let initial_amount = 10.0;
-        let withdrawal_amount = 5.0;
+        let initial_amount: Amount = "10".parse().unwrap();
+        let withdrawal_amount: Amount = "5".parse().unwrap();
 
-        let mut initial_client = Client::new(client_id.clone());
-        initial_client.deposit(initial_amount).unwrap();
+        let mut initial_client = Client::new(client_id);
+        initial_client
+            .deposit(Currency::EUR, initial_amount)
+            .unwrap();
 
         let client_repo = TestClientRepository::with_client(initial_client);
         let transaction_repo = TestTransactionRepository {};
@@ -178,14 +1190,22 @@ mod tests {
             tx: tx_id,
             tx_type: TxType::Withdrawal,
             amount: Some(withdrawal_amount),
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
         };
 
         let client = service.process_transaction(&input_record).unwrap();
 
         assert_eq!(client.id, client_id);
-        assert_eq!(client.available.get(), 5.0);
-        assert_eq!(client.total.get(), 5.0);
-        assert_eq!(client.held.get(), 0.0);
+        assert_eq!(
+            client.balance(Currency::EUR).available.get(),
+            withdrawal_amount
+        );
+        assert_eq!(client.balance(Currency::EUR).total.get(), withdrawal_amount);
+        assert_eq!(client.balance(Currency::EUR).held.get(), Amount::ZERO);
         assert_eq!(client.status, ClientStatus::Active);
     }
 
@@ -193,7 +1213,7 @@ mod tests {
     fn test_process_withdrawal_transaction_without_client() {
         let client_id = ClientId::try_from("1".to_string()).unwrap();
         let tx_id = TxId::try_from("100".to_string()).unwrap();
-        let withdrawal_amount = 5.0;
+        let withdrawal_amount: Amount = "5".parse().unwrap();
 
         let client_repo = TestClientRepository::new();
         let transaction_repo = TestTransactionRepository {};
@@ -204,6 +1224,11 @@ mod tests {
             tx: tx_id,
             tx_type: TxType::Withdrawal,
             amount: Some(withdrawal_amount),
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
         };
 
         let err = service.process_transaction(&input_record).unwrap_err();
@@ -214,17 +1239,14 @@ mod tests {
     fn test_process_dispute_transaction() {
         let client_id = ClientId::try_from("1".to_string()).unwrap();
         let tx_id = TxId::try_from("100".to_string()).unwrap();
-        let amount = 10.0;
+        let amount: Amount = "10".parse().unwrap();
 
-        let mut initial_client = Client::new(client_id.clone());
-        initial_client.deposit(amount).unwrap();
+        let mut initial_client = Client::new(client_id);
+        initial_client.deposit(Currency::EUR, amount).unwrap();
 
         let original_tx = Transaction {
-            id: tx_id,
-            client_id: client_id,
-            tx_type: TxType::Deposit,
-            amount: amount,
             status: TransactionStatus::Confirmed,
+            ..Transaction::new(tx_id, client_id, amount, TxType::Deposit)
         };
 
         let transaction_repo = DisputeTransactionRepository {
@@ -240,14 +1262,19 @@ mod tests {
             tx: tx_id,
             tx_type: TxType::Dispute,
             amount: None,
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
         };
 
         let client = service.process_transaction(&dispute_record).unwrap();
 
         assert_eq!(client.id, client_id);
-        assert_eq!(client.available.get(), 0.0);
-        assert_eq!(client.total.get(), amount);
-        assert_eq!(client.held.get(), amount);
+        assert_eq!(client.balance(Currency::EUR).available.get(), Amount::ZERO);
+        assert_eq!(client.balance(Currency::EUR).total.get(), amount);
+        assert_eq!(client.balance(Currency::EUR).held.get(), amount);
         assert_eq!(client.status, ClientStatus::Active);
     }
 
@@ -255,18 +1282,17 @@ mod tests {
     fn test_process_chargeback_transaction() {
         let client_id = ClientId::try_from("1".to_string()).unwrap();
         let tx_id = TxId::try_from("100".to_string()).unwrap();
-        let amount = 10.0;
+        let amount: Amount = "10".parse().unwrap();
 
-        let mut initial_client = Client::new(client_id.clone());
-        initial_client.deposit(amount).unwrap();
-        initial_client.dispute(amount).unwrap();
+        let mut initial_client = Client::new(client_id);
+        initial_client.deposit(Currency::EUR, amount).unwrap();
+        initial_client
+            .dispute(Currency::EUR, amount, &DisputePolicy::default())
+            .unwrap();
 
         let disputed_tx = Transaction {
-            id: tx_id.clone(),
-            client_id: client_id.clone(),
-            tx_type: TxType::Deposit,
-            amount: amount,
             status: TransactionStatus::Disputed,
+            ..Transaction::new(tx_id, client_id, amount, TxType::Deposit)
         };
 
         let transaction_repo = ChargebackTransactionRepository {
@@ -278,18 +1304,23 @@ mod tests {
         );
 
         let chargeback_record = InputRecord {
-            client: client_id.clone(),
-            tx: tx_id.clone(),
+            client: client_id,
+            tx: tx_id,
             tx_type: TxType::Chargeback,
             amount: None,
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
         };
 
         let client = service.process_transaction(&chargeback_record).unwrap();
 
         assert_eq!(client.id, client_id);
-        assert_eq!(client.available.get(), 0.0);
-        assert_eq!(client.total.get(), 0.0);
-        assert_eq!(client.held.get(), 0.0);
+        assert_eq!(client.balance(Currency::EUR).available.get(), Amount::ZERO);
+        assert_eq!(client.balance(Currency::EUR).total.get(), Amount::ZERO);
+        assert_eq!(client.balance(Currency::EUR).held.get(), Amount::ZERO);
         assert_eq!(client.status, ClientStatus::Locked);
     }
 
@@ -303,13 +1334,1971 @@ mod tests {
         let mut service = TransactionService::new(client_repo, transaction_repo);
 
         let dispute_record = InputRecord {
-            client: client_id.clone(),
+            client: client_id,
             tx: tx_id,
             tx_type: TxType::Dispute,
             amount: None,
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
         };
 
         let err = service.process_transaction(&dispute_record).unwrap_err();
         assert!(err.is::<ClientError>());
     }
+
+    #[test]
+    fn test_failed_transaction_rolls_back_client_update() {
+        let client_id = ClientId::try_from("1".to_string()).unwrap();
+        let tx_id = TxId::try_from("100".to_string()).unwrap();
+        let amount: Amount = "10".parse().unwrap();
+
+        let mut initial_client = Client::new(client_id);
+        initial_client.deposit(Currency::EUR, amount).unwrap();
+
+        let client_repo = TestClientRepository::with_client(initial_client);
+        let transaction_repo = FailingTransactionRepository {};
+        let mut service = TransactionService::new(client_repo, transaction_repo);
+
+        let input_record = InputRecord {
+            client: client_id,
+            tx: tx_id,
+            tx_type: TxType::Deposit,
+            amount: Some(amount),
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
+        };
+
+        assert!(service.process_transaction(&input_record).is_err());
+
+        let clients: Vec<OutputRecord> = service.get_all_clients().unwrap().collect();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].available.get(), amount);
+        assert_eq!(clients[0].total.get(), amount);
+    }
+
+    #[test]
+    fn test_failed_deposit_does_not_create_client() {
+        let client_id = ClientId::try_from("1".to_string()).unwrap();
+        let tx_id = TxId::try_from("100".to_string()).unwrap();
+
+        let client_repo = TestClientRepository::new();
+        let transaction_repo = FailingTransactionRepository {};
+        let mut service = TransactionService::new(client_repo, transaction_repo);
+
+        let input_record = InputRecord {
+            client: client_id,
+            tx: tx_id,
+            tx_type: TxType::Deposit,
+            amount: Some("10".parse().unwrap()),
+            to: None,
+            currency: None,
+            to_currency: None,
+            reason: None,
+            operator: None,
+        };
+
+        assert!(service.process_transaction(&input_record).is_err());
+        assert_eq!(service.get_all_clients().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_resolve_twice_is_rejected() {
+        let mut service = in_memory_service(ServiceConfig::default());
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Resolve, 1, 1, None))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Resolve, 1, 1, None))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::AlreadyResolved { .. })
+        ));
+    }
+
+    #[test]
+    fn test_chargeback_after_resolve_is_rejected() {
+        let mut service = in_memory_service(ServiceConfig::default());
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Resolve, 1, 1, None))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Chargeback, 1, 1, None))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::AlreadyResolved { .. })
+        ));
+        let client = service.get_all_clients().unwrap().next().unwrap();
+        assert!(!client.locked);
+        assert_eq!(client.total.get(), "10".parse().unwrap());
+    }
+
+    #[test]
+    fn test_repeated_chargeback_is_rejected() {
+        let mut service = in_memory_service(ServiceConfig::default());
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 2, Some("5")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Chargeback, 1, 1, None))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Chargeback, 1, 1, None))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::AlreadyChargedBack { .. })
+        ));
+        let client = service.get_all_clients().unwrap().next().unwrap();
+        assert_eq!(client.held.get(), Amount::ZERO);
+        assert_eq!(client.total.get(), "5".parse().unwrap());
+    }
+
+    #[test]
+    fn test_partial_disputes_of_a_deposit() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(ServiceConfig::default());
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 1, Some("3")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 1, Some("2")))
+            .unwrap();
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 1, 1, Some("6")))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::DisputeExceedsAmount { .. })
+        ));
+        service
+            .process_transaction(&record(TxType::Resolve, 1, 1, Some("1")))
+            .unwrap();
+        let err = service
+            .process_transaction(&record(TxType::Chargeback, 1, 1, Some("5")))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::ExceedsDisputedAmount { .. })
+        ));
+        let client = service
+            .process_transaction(&record(TxType::Chargeback, 1, 1, Some("4")))
+            .unwrap();
+
+        let balance = client.balance(Currency::EUR);
+        assert_eq!(balance.available.get(), "6".parse().unwrap());
+        assert_eq!(balance.held.get(), Amount::ZERO);
+        assert_eq!(balance.total.get(), "6".parse().unwrap());
+        assert!(client.is_locked());
+        let transaction = service.get_transaction(&TxId::new(1)).unwrap();
+        assert_eq!(transaction.status, TransactionStatus::ChargedBack);
+        assert_eq!(transaction.charged_back(), "4".parse().unwrap());
+
+        let mut replayed = in_memory_service(ServiceConfig::default());
+        for event in events.lock().unwrap().iter() {
+            replayed.apply_event(event).unwrap();
+        }
+        let client = replayed.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(
+            client.balance(Currency::EUR).total.get(),
+            "6".parse().unwrap()
+        );
+        let transaction = replayed.get_transaction(&TxId::new(1)).unwrap();
+        assert_eq!(transaction.charged_back(), "4".parse().unwrap());
+    }
+
+    #[test]
+    fn test_undisputed_part_can_be_disputed_after_resolve() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("100")),
+            record(TxType::Dispute, 1, 1, Some("40")),
+            record(TxType::Resolve, 1, 1, None),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        let client = service
+            .process_transaction(&record(TxType::Dispute, 1, 1, Some("60")))
+            .unwrap();
+
+        let balance = client.balance(Currency::EUR);
+        assert_eq!(balance.available.get(), "40".parse().unwrap());
+        assert_eq!(balance.held.get(), "60".parse().unwrap());
+        service
+            .process_transaction(&record(TxType::Resolve, 1, 1, None))
+            .unwrap();
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::AlreadyResolved { .. })
+        ));
+    }
+
+    #[test]
+    fn test_redispute_after_resolve_when_allowed() {
+        let mut service = in_memory_service(ServiceConfig {
+            dispute_policy: DisputePolicy {
+                allow_redispute_after_resolve: true,
+                ..DisputePolicy::default()
+            },
+            ..ServiceConfig::default()
+        });
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Resolve, 1, 1, None))
+            .unwrap();
+        let client = service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap();
+
+        assert_eq!(
+            client.balance(Currency::EUR).held.get(),
+            "10".parse().unwrap()
+        );
+        assert_eq!(client.balance(Currency::EUR).available.get(), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_duplicate_deposit_is_rejected() {
+        let mut service = in_memory_service(ServiceConfig::default());
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::Duplicate { .. })
+        ));
+        let client = service.get_all_clients().unwrap().next().unwrap();
+        assert_eq!(client.total.get(), "10".parse().unwrap());
+    }
+
+    #[test]
+    fn test_duplicate_withdrawal_id_is_rejected() {
+        let mut service = in_memory_service(ServiceConfig::default());
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Withdrawal, 1, 1, Some("5")))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::Duplicate { .. })
+        ));
+    }
+
+    #[test]
+    fn test_negative_and_zero_amounts_are_rejected() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+
+        for record in [
+            record(TxType::Withdrawal, 1, 2, Some("-100")),
+            record(TxType::Deposit, 2, 3, Some("-5")),
+            record(TxType::Deposit, 1, 4, Some("0")),
+            record(TxType::Dispute, 1, 1, Some("-1")),
+            transfer(1, 2, 5, "-1"),
+        ] {
+            let err = service.process_transaction(&record).unwrap_err();
+            assert_eq!(error_code(&err), "non_positive_amount", "{record:?}");
+        }
+
+        let clients: Vec<_> = service.get_all_clients().unwrap().collect();
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].available.get(), "10".parse().unwrap());
+        assert_eq!(clients[0].held.get(), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_idempotent_replay_is_a_no_op() {
+        let mut service = in_memory_service(ServiceConfig {
+            idempotent_replays: true,
+            ..ServiceConfig::default()
+        });
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        let client = service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10.0")))
+            .unwrap();
+
+        assert_eq!(
+            client.balance(Currency::EUR).total.get(),
+            "10".parse().unwrap()
+        );
+        assert_eq!(
+            client.balance(Currency::EUR).available.get(),
+            "10".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_idempotent_replay_with_different_content_is_rejected() {
+        let mut service = in_memory_service(ServiceConfig {
+            idempotent_replays: true,
+            ..ServiceConfig::default()
+        });
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("11")))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::Duplicate { .. })
+        ));
+    }
+
+    struct RecordingReporter {
+        attempts: Arc<Mutex<Vec<CrossClientAttempt>>>,
+    }
+
+    impl SuspiciousActivityReporter for RecordingReporter {
+        fn report_cross_client_attempt(&mut self, attempt: &CrossClientAttempt) {
+            self.attempts.lock().unwrap().push(*attempt);
+        }
+    }
+
+    #[test]
+    fn test_dispute_family_requires_transaction_owner() {
+        for tx_type in [TxType::Dispute, TxType::Resolve, TxType::Chargeback] {
+            let attempts = Arc::new(Mutex::new(Vec::new()));
+            let mut service = in_memory_service(ServiceConfig::default());
+            service.set_reporter(Box::new(RecordingReporter {
+                attempts: attempts.clone(),
+            }));
+
+            service
+                .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+                .unwrap();
+            service
+                .process_transaction(&record(TxType::Deposit, 2, 2, Some("10")))
+                .unwrap();
+            if tx_type != TxType::Dispute {
+                service
+                    .process_transaction(&record(TxType::Dispute, 1, 1, None))
+                    .unwrap();
+            }
+
+            let err = service
+                .process_transaction(&record(tx_type, 2, 1, None))
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<TransactionError>(),
+                Some(TransactionError::ClientMismatch { .. })
+            ));
+            assert_eq!(
+                *attempts.lock().unwrap(),
+                vec![CrossClientAttempt {
+                    tx_type,
+                    tx: TxId::new(1),
+                    client: ClientId::new(2),
+                    owner: ClientId::new(1),
+                }]
+            );
+
+            let clients: Vec<OutputRecord> = service.get_all_clients().unwrap().collect();
+            for client in clients {
+                assert_eq!(client.total.get(), "10".parse().unwrap());
+                assert!(!client.locked);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cross_client_attempt_leaves_the_transaction_to_its_owner() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Deposit, 2, 2, Some("10")))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 2, 1, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::ClientMismatch { .. })
+        ));
+        // A client without an account can't reference anything either.
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 3, 1, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::NotFound { .. })
+        ));
+        assert!(service.get_client(&ClientId::new(3)).is_err());
+
+        let client = service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap();
+        assert_eq!(
+            client.balance(Currency::EUR).held.get(),
+            "10".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unknown_transaction_is_not_reported_as_cross_client() {
+        let attempts = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(ServiceConfig::default());
+        service.set_reporter(Box::new(RecordingReporter {
+            attempts: attempts.clone(),
+        }));
+        service
+            .process_transaction(&record(TxType::Deposit, 2, 1, Some("10")))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 2, 5, None))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::NotFound { .. })
+        ));
+        assert!(attempts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_denied_by_default() {
+        let mut service = in_memory_service(ServiceConfig::default());
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Withdrawal, 1, 2, Some("4")))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 1, 2, None))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::DisputeNotAllowed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_withdrawal_dispute_reversed_on_chargeback() {
+        let mut service = in_memory_service(ServiceConfig {
+            dispute_policy: DisputePolicy {
+                withdrawal_disputes: WithdrawalDisputes::Reverse,
+                ..DisputePolicy::default()
+            },
+            ..ServiceConfig::default()
+        });
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Withdrawal, 1, 2, Some("4")))
+            .unwrap();
+        let client = service
+            .process_transaction(&record(TxType::Dispute, 1, 2, None))
+            .unwrap();
+
+        assert_eq!(
+            client.balance(Currency::EUR).available.get(),
+            "6".parse().unwrap()
+        );
+        assert_eq!(
+            client.balance(Currency::EUR).held.get(),
+            "4".parse().unwrap()
+        );
+        assert_eq!(
+            client.balance(Currency::EUR).total.get(),
+            "10".parse().unwrap()
+        );
+
+        let client = service
+            .process_transaction(&record(TxType::Chargeback, 1, 2, None))
+            .unwrap();
+
+        assert_eq!(
+            client.balance(Currency::EUR).available.get(),
+            "10".parse().unwrap()
+        );
+        assert_eq!(client.balance(Currency::EUR).held.get(), Amount::ZERO);
+        assert_eq!(
+            client.balance(Currency::EUR).total.get(),
+            "10".parse().unwrap()
+        );
+        assert!(client.is_locked());
+    }
+
+    struct RecordingEventLog {
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    impl EventLog for RecordingEventLog {
+        fn append(&mut self, event: &Event) -> anyhow::Result<()> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_accepted_and_rejected_records_are_logged() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(ServiceConfig::default());
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Withdrawal, 1, 2, Some("20")))
+            .unwrap_err();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Chargeback, 1, 1, None))
+            .unwrap();
+
+        let client = ClientId::new(1);
+        let amount: Amount = "10".parse().unwrap();
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                Event::Deposited {
+                    client,
+                    tx: TxId::new(1),
+                    amount,
+                    currency: Currency::EUR,
+                },
+                Event::Rejected {
+                    tx_type: TxType::Withdrawal,
+                    client,
+                    tx: TxId::new(2),
+                    reason: "error updating funds for client 1 with tx type withdraw".to_string(),
+                },
+                Event::FundsHeld {
+                    client,
+                    tx: TxId::new(1),
+                    amount,
+                },
+                Event::ChargedBack {
+                    client,
+                    tx: TxId::new(1),
+                    amount,
+                },
+                Event::AccountLocked { client },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failing_event_log_rolls_back_record() {
+        struct FailingEventLog;
+
+        impl EventLog for FailingEventLog {
+            fn append(&mut self, _event: &Event) -> anyhow::Result<()> {
+                anyhow::bail!("disk full")
+            }
+        }
+
+        let mut service = in_memory_service(ServiceConfig::default());
+        service.set_event_log(Box::new(FailingEventLog));
+
+        assert!(
+            service
+                .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+                .is_err()
+        );
+        assert!(service.get_client(&ClientId::new(1)).is_err());
+        assert!(service.get_transaction(&TxId::new(1)).is_err());
+    }
+
+    #[test]
+    fn test_replaying_the_log_rebuilds_state() {
+        let config = ServiceConfig {
+            dispute_policy: DisputePolicy {
+                allow_redispute_after_resolve: true,
+                withdrawal_disputes: WithdrawalDisputes::Reverse,
+                ..DisputePolicy::default()
+            },
+            ..ServiceConfig::default()
+        };
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(config);
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+
+        let records = [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Withdrawal, 1, 2, Some("4")),
+            record(TxType::Dispute, 1, 1, None),
+            record(TxType::Resolve, 1, 1, None),
+            record(TxType::Dispute, 1, 1, None),
+            record(TxType::Deposit, 2, 3, Some("5")),
+            transfer(1, 2, 6, "1"),
+            record(TxType::Dispute, 1, 6, None),
+            record(TxType::Withdrawal, 2, 4, Some("2")),
+            record(TxType::Dispute, 2, 4, None),
+            record(TxType::Chargeback, 2, 4, None),
+            record(TxType::Withdrawal, 3, 5, Some("1")),
+        ];
+        for r in &records {
+            let _ = service.process_transaction(r);
+        }
+
+        let mut replayed = in_memory_service(ServiceConfig::default());
+        for event in events.lock().unwrap().iter() {
+            replayed.apply_event(event).unwrap();
+        }
+
+        for id in [1, 2] {
+            let expected = service.get_client(&ClientId::new(id)).unwrap();
+            let actual = replayed.get_client(&ClientId::new(id)).unwrap();
+            assert_eq!(
+                expected.balance(Currency::EUR).available.get(),
+                actual.balance(Currency::EUR).available.get()
+            );
+            assert_eq!(
+                expected.balance(Currency::EUR).held.get(),
+                actual.balance(Currency::EUR).held.get()
+            );
+            assert_eq!(
+                expected.balance(Currency::EUR).total.get(),
+                actual.balance(Currency::EUR).total.get()
+            );
+            assert_eq!(expected.status, actual.status);
+        }
+        assert!(replayed.get_client(&ClientId::new(3)).is_err());
+        for id in [1, 2, 3, 4, 6] {
+            let expected = service.get_transaction(&TxId::new(id)).unwrap();
+            let actual = replayed.get_transaction(&TxId::new(id)).unwrap();
+            assert_eq!(expected.status, actual.status);
+        }
+    }
+
+    #[test]
+    fn test_transfer_moves_funds_between_clients() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Deposit, 2, 2, Some("1")))
+            .unwrap();
+
+        let sender = service
+            .process_transaction(&transfer(1, 2, 3, "4"))
+            .unwrap();
+
+        assert_eq!(
+            sender.balance(Currency::EUR).available.get(),
+            "6".parse().unwrap()
+        );
+        assert_eq!(
+            sender.balance(Currency::EUR).total.get(),
+            "6".parse().unwrap()
+        );
+        let recipient = service.get_client(&ClientId::new(2)).unwrap();
+        assert_eq!(
+            recipient.balance(Currency::EUR).available.get(),
+            "5".parse().unwrap()
+        );
+        assert_eq!(
+            recipient.balance(Currency::EUR).total.get(),
+            "5".parse().unwrap()
+        );
+        for client in [1, 2] {
+            let history = service
+                .get_transactions_for_client(&ClientId::new(client), 0..10)
+                .unwrap();
+            assert_eq!(history.last().unwrap().id, TxId::new(3));
+        }
+
+        let err = service
+            .process_transaction(&transfer(1, 2, 4, "7"))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::FundsUpdateError { .. })
+        ));
+        let err = service
+            .process_transaction(&transfer(1, 9, 5, "1"))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::NotFound { .. })
+        ));
+        assert_eq!(
+            service
+                .get_client(&ClientId::new(1))
+                .unwrap()
+                .balance(Currency::EUR)
+                .total
+                .get(),
+            "6".parse().unwrap()
+        );
+        assert!(service.get_transaction(&TxId::new(5)).is_err());
+    }
+
+    #[test]
+    fn test_transfer_rejected_when_either_account_is_locked() {
+        for locked in [1, 2] {
+            let mut service = in_memory_service(ServiceConfig::default());
+            service
+                .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+                .unwrap();
+            service
+                .process_transaction(&record(TxType::Deposit, 2, 2, Some("10")))
+                .unwrap();
+            service
+                .process_transaction(&record(TxType::Deposit, locked, 3, Some("1")))
+                .unwrap();
+            service
+                .process_transaction(&record(TxType::Dispute, locked, 3, None))
+                .unwrap();
+            service
+                .process_transaction(&record(TxType::Chargeback, locked, 3, None))
+                .unwrap();
+
+            let err = service
+                .process_transaction(&transfer(1, 2, 4, "1"))
+                .unwrap_err();
+
+            assert!(matches!(
+                err.downcast_ref::<ClientError>(),
+                Some(ClientError::Locked { id }) if id.get() == locked
+            ));
+            for client in [1, 2] {
+                assert_eq!(
+                    service
+                        .get_client(&ClientId::new(client))
+                        .unwrap()
+                        .balance(Currency::EUR)
+                        .total
+                        .get(),
+                    "10".parse().unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_transfer_dispute_holds_and_reverses_at_recipient() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(ServiceConfig::default());
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Deposit, 2, 3, Some("1")))
+            .unwrap();
+        service
+            .process_transaction(&transfer(1, 2, 2, "4"))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 2, 2, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::ClientMismatch { .. })
+        ));
+
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 2, None))
+            .unwrap();
+        let recipient = service.get_client(&ClientId::new(2)).unwrap();
+        assert_eq!(
+            recipient.balance(Currency::EUR).available.get(),
+            "1".parse().unwrap()
+        );
+        assert_eq!(
+            recipient.balance(Currency::EUR).held.get(),
+            "4".parse().unwrap()
+        );
+        assert_eq!(
+            recipient.balance(Currency::EUR).total.get(),
+            "5".parse().unwrap()
+        );
+
+        let sender = service
+            .process_transaction(&record(TxType::Chargeback, 1, 2, None))
+            .unwrap();
+
+        assert_eq!(
+            sender.balance(Currency::EUR).available.get(),
+            "10".parse().unwrap()
+        );
+        assert_eq!(
+            sender.balance(Currency::EUR).total.get(),
+            "10".parse().unwrap()
+        );
+        assert!(!sender.is_locked());
+        let recipient = service.get_client(&ClientId::new(2)).unwrap();
+        assert_eq!(recipient.balance(Currency::EUR).held.get(), Amount::ZERO);
+        assert_eq!(
+            recipient.balance(Currency::EUR).total.get(),
+            "1".parse().unwrap()
+        );
+        assert!(recipient.is_locked());
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&Event::AccountLocked {
+                client: ClientId::new(2)
+            })
+        );
+    }
+
+    fn in_currency(record: InputRecord, currency: &str) -> InputRecord {
+        InputRecord {
+            currency: Some(currency.parse().unwrap()),
+            ..record
+        }
+    }
+
+    #[test]
+    fn test_balances_are_kept_per_currency() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        let usd: Currency = "USD".parse().unwrap();
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&in_currency(
+                record(TxType::Deposit, 1, 2, Some("3")),
+                "usd",
+            ))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&in_currency(
+                record(TxType::Withdrawal, 1, 3, Some("5")),
+                "USD",
+            ))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::FundsUpdateError { .. })
+        ));
+
+        let client = service
+            .process_transaction(&record(TxType::Withdrawal, 1, 4, Some("5")))
+            .unwrap();
+        assert_eq!(
+            client.balance(Currency::EUR).total.get(),
+            "5".parse().unwrap()
+        );
+        assert_eq!(client.balance(usd).total.get(), "3".parse().unwrap());
+
+        let rows: Vec<_> = service
+            .get_all_balances()
+            .unwrap()
+            .map(|row| (row.currency, row.total.get()))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some(Currency::EUR), "5".parse().unwrap()),
+                (Some(usd), "3".parse().unwrap())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dispute_in_another_currency_is_rejected() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        service
+            .process_transaction(&in_currency(
+                record(TxType::Deposit, 1, 1, Some("10")),
+                "GBP",
+            ))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&in_currency(record(TxType::Dispute, 1, 1, None), "EUR"))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::CurrencyMismatch { .. })
+        ));
+
+        // Without a currency the dispute applies to the transaction's own.
+        let client = service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap();
+        let gbp = client.balance("GBP".parse().unwrap());
+        assert_eq!(gbp.held.get(), "10".parse().unwrap());
+        assert_eq!(client.balance(Currency::EUR).held.get(), Amount::ZERO);
+    }
+
+    #[test]
+    fn test_default_currency_applies_to_records_without_one() {
+        let usd: Currency = "USD".parse().unwrap();
+        let mut service = in_memory_service(ServiceConfig {
+            default_currency: usd,
+            ..ServiceConfig::default()
+        });
+
+        let client = service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("2")))
+            .unwrap();
+
+        assert_eq!(client.balance(usd).total.get(), "2".parse().unwrap());
+        assert_eq!(
+            service.get_transaction(&TxId::new(1)).unwrap().currency,
+            usd
+        );
+        let row = service.get_all_clients().unwrap().next().unwrap();
+        assert_eq!(row.total.get(), "2".parse().unwrap());
+    }
+
+    #[test]
+    fn test_replay_with_the_same_default_currency_gives_the_same_balances() {
+        let config = ServiceConfig {
+            default_currency: "USD".parse().unwrap(),
+            ..ServiceConfig::default()
+        };
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(config.clone());
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Withdrawal, 1, 2, Some("3")),
+            in_currency(record(TxType::Deposit, 2, 3, Some("1")), "EUR"),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        let mut replayed = in_memory_service(config);
+        for event in events.lock().unwrap().iter() {
+            replayed.apply_event(event).unwrap();
+        }
+        let totals = |service: &TransactionService<_, _>| {
+            let mut rows: Vec<(u16, String)> = service
+                .get_all_clients()
+                .unwrap()
+                .map(|row| (row.client.get(), row.total.get().to_string()))
+                .collect();
+            rows.sort();
+            rows
+        };
+        assert_eq!(
+            totals(&service),
+            vec![(1, "7.0000".to_string()), (2, "0.0000".to_string())]
+        );
+        assert_eq!(totals(&replayed), totals(&service));
+    }
+
+    fn exchange(client: u16, tx: u32, amount: &str, from: &str, to: &str) -> InputRecord {
+        InputRecord {
+            to_currency: Some(to.parse().unwrap()),
+            ..in_currency(record(TxType::Exchange, client, tx, Some(amount)), from)
+        }
+    }
+
+    fn exchange_service(
+        rounding: Rounding,
+    ) -> TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl> {
+        let rates = "\
+pair,rate,effective_from
+EUR/USD,1,
+EUR/USD,1.08335,1000000000
+EUR/USD,1.1,4102444800
+EUR/GBP,0.4,
+";
+        let mut service = in_memory_service(ServiceConfig {
+            rates: RateTable::from_csv(rates.as_bytes()).unwrap(),
+            rounding,
+            ..ServiceConfig::default()
+        });
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("100")))
+            .unwrap();
+        service
+    }
+
+    #[test]
+    fn test_exchange_uses_the_rate_in_effect() {
+        let usd: Currency = "USD".parse().unwrap();
+        let mut service = exchange_service(Rounding::HalfEven);
+
+        // 0.0007 * 1.08335 = 0.000758345
+        let client = service
+            .process_transaction(&exchange(1, 2, "0.0007", "EUR", "USD"))
+            .unwrap();
+        assert_eq!(client.balance(usd).total.get(), "0.0008".parse().unwrap());
+        let client = service
+            .process_transaction(&exchange(1, 10, "10", "EUR", "USD"))
+            .unwrap();
+        // The last rate only takes effect in 2100.
+        assert_eq!(client.balance(usd).total.get(), "10.8343".parse().unwrap());
+        assert_eq!(
+            client.balance(Currency::EUR).total.get(),
+            "89.9993".parse().unwrap()
+        );
+
+        let transaction = service.get_transaction(&TxId::new(2)).unwrap();
+        assert_eq!(
+            transaction.destination_amount,
+            Some("0.0008".parse().unwrap())
+        );
+        assert_eq!(transaction.rate, Some("1.08335".parse().unwrap()));
+
+        let mut service = exchange_service(Rounding::Down);
+        let client = service
+            .process_transaction(&exchange(1, 2, "0.0007", "EUR", "USD"))
+            .unwrap();
+        assert_eq!(client.balance(usd).total.get(), "0.0007".parse().unwrap());
+    }
+
+    #[test]
+    fn test_exchange_without_rate_is_rejected() {
+        let mut service = exchange_service(Rounding::HalfEven);
+
+        let err = service
+            .process_transaction(&exchange(1, 2, "1", "USD", "EUR"))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::RateNotFound { .. })
+        ));
+        assert!(service.get_transaction(&TxId::new(2)).is_err());
+    }
+
+    #[test]
+    fn test_exchange_rounding_to_zero_is_rejected() {
+        let mut service = exchange_service(Rounding::HalfEven);
+
+        // 0.0001 * 0.4 = 0.00004
+        let err = service
+            .process_transaction(&exchange(1, 2, "0.0001", "EUR", "GBP"))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::ExchangeRoundsToZero { .. })
+        ));
+        assert!(service.get_transaction(&TxId::new(2)).is_err());
+        let client = service.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(
+            client.balance(Currency::EUR).total.get(),
+            "100".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_exchange_rejected_on_locked_account() {
+        let mut service = exchange_service(Rounding::HalfEven);
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 2, Some("1")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 2, None))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Chargeback, 1, 2, None))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&exchange(1, 3, "1", "EUR", "USD"))
+            .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::Locked { .. })
+        ));
+    }
+
+    #[test]
+    fn test_exchange_replays_without_rates() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = exchange_service(Rounding::HalfEven);
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+        service
+            .process_transaction(&exchange(1, 2, "10", "EUR", "USD"))
+            .unwrap();
+
+        let mut replayed = in_memory_service(ServiceConfig::default());
+        replayed
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("100")))
+            .unwrap();
+        for event in events.lock().unwrap().iter() {
+            replayed.apply_event(event).unwrap();
+        }
+
+        let usd: Currency = "USD".parse().unwrap();
+        assert_eq!(
+            replayed
+                .get_client(&ClientId::new(1))
+                .unwrap()
+                .balance(usd)
+                .total
+                .get(),
+            "10.8335".parse().unwrap()
+        );
+    }
+
+    fn admin(tx_type: TxType, client: u16, tx: u32) -> InputRecord {
+        InputRecord {
+            reason: Some("ticket 42".to_string()),
+            operator: Some("alice".to_string()),
+            ..record(tx_type, client, tx, None)
+        }
+    }
+
+    #[test]
+    fn test_frozen_account_accepts_deposits_only() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Deposit, 2, 2, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&admin(TxType::Freeze, 1, 100))
+            .unwrap();
+
+        for rejected in [
+            record(TxType::Withdrawal, 1, 3, Some("1")),
+            transfer(1, 2, 4, "1"),
+        ] {
+            let err = service.process_transaction(&rejected).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<ClientError>(),
+                Some(ClientError::Frozen { .. })
+            ));
+        }
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 5, Some("1")))
+            .unwrap();
+        service
+            .process_transaction(&transfer(2, 1, 6, "1"))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 5, None))
+            .unwrap();
+
+        let client = service
+            .process_transaction(&admin(TxType::Unlock, 1, 101))
+            .unwrap();
+        assert_eq!(client.status, ClientStatus::Active);
+        service
+            .process_transaction(&record(TxType::Withdrawal, 1, 7, Some("1")))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_locked_account_cannot_be_debited() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        for record in [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Deposit, 1, 2, Some("5")),
+            record(TxType::Deposit, 2, 3, Some("10")),
+            record(TxType::Authorize, 1, 4, Some("2")),
+            record(TxType::Dispute, 1, 2, None),
+            record(TxType::Chargeback, 1, 2, None),
+        ] {
+            service.process_transaction(&record).unwrap();
+        }
+
+        for rejected in [
+            record(TxType::Withdrawal, 1, 5, Some("1")),
+            transfer(1, 2, 6, "1"),
+            record(TxType::Authorize, 1, 7, Some("1")),
+            capture(1, 4, None),
+            refund(1, 1, Some("1")),
+        ] {
+            let err = service.process_transaction(&rejected).unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<ClientError>(),
+                    Some(ClientError::Locked { .. })
+                ),
+                "{rejected:?}"
+            );
+        }
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 8, Some("1")))
+            .unwrap();
+        let client = service
+            .process_transaction(&record(TxType::Void, 1, 4, None))
+            .unwrap();
+        assert_eq!(eur(&client), amounts("11", "0", "11"));
+    }
+
+    #[test]
+    fn test_closed_account_accepts_nothing() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Deposit, 2, 2, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&admin(TxType::Close, 1, 100))
+            .unwrap();
+
+        for rejected in [
+            record(TxType::Deposit, 1, 3, Some("1")),
+            record(TxType::Withdrawal, 1, 4, Some("1")),
+            record(TxType::Dispute, 1, 1, None),
+            transfer(2, 1, 5, "1"),
+            admin(TxType::Unlock, 1, 101),
+        ] {
+            let err = service.process_transaction(&rejected).unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<ClientError>(),
+                    Some(ClientError::Closed { .. } | ClientError::InvalidStatusChange { .. })
+                ),
+                "{err}"
+            );
+        }
+        assert_eq!(
+            service
+                .get_client(&ClientId::new(1))
+                .unwrap()
+                .balance(Currency::EUR)
+                .total
+                .get(),
+            "10".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unlock_after_chargeback_is_logged_and_replayed() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(ServiceConfig::default());
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Dispute, 1, 1, None),
+            record(TxType::Chargeback, 1, 1, None),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        let without_operator = InputRecord {
+            operator: None,
+            ..admin(TxType::Unlock, 1, 100)
+        };
+        let err = service.process_transaction(&without_operator).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::InvalidTransaction { .. })
+        ));
+        let client = service
+            .process_transaction(&admin(TxType::Unlock, 1, 100))
+            .unwrap();
+        assert!(!client.is_locked());
+
+        assert_eq!(
+            events.lock().unwrap().last(),
+            Some(&Event::StatusChanged {
+                client: ClientId::new(1),
+                tx: TxId::new(100),
+                from: ClientStatus::Locked,
+                to: ClientStatus::Active,
+                reason: "ticket 42".to_string(),
+                operator: "alice".to_string(),
+            })
+        );
+        let mut replayed = in_memory_service(ServiceConfig::default());
+        for event in events.lock().unwrap().iter() {
+            replayed.apply_event(event).unwrap();
+        }
+        assert_eq!(
+            replayed.get_client(&ClientId::new(1)).unwrap().status,
+            ClientStatus::Active
+        );
+    }
+
+    fn capture(client: u16, tx: u32, amount: Option<&str>) -> InputRecord {
+        record(TxType::Capture, client, tx, amount)
+    }
+
+    fn authorized_service(
+        hold_expiry: HoldExpiry,
+    ) -> TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl> {
+        let mut service = in_memory_service(ServiceConfig {
+            hold_expiry,
+            ..ServiceConfig::default()
+        });
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Authorize, 1, 2, Some("4")))
+            .unwrap();
+        service
+    }
+
+    fn eur(client: &Client) -> (Amount, Amount, Amount) {
+        let balance = client.balance(Currency::EUR);
+        (
+            balance.available.get(),
+            balance.held.get(),
+            balance.total.get(),
+        )
+    }
+
+    fn amounts(available: &str, held: &str, total: &str) -> (Amount, Amount, Amount) {
+        (
+            available.parse().unwrap(),
+            held.parse().unwrap(),
+            total.parse().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_partial_capture_releases_the_rest() {
+        let mut service = authorized_service(HoldExpiry::default());
+
+        let err = service
+            .process_transaction(&capture(1, 2, Some("5")))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::CaptureExceedsAuthorization { .. })
+        ));
+        let client = service
+            .process_transaction(&capture(1, 2, Some("1.5")))
+            .unwrap();
+
+        assert_eq!(eur(&client), amounts("8.5", "0", "8.5"));
+        let authorization = service.get_transaction(&TxId::new(2)).unwrap();
+        assert_eq!(authorization.status, TransactionStatus::Captured);
+        assert_eq!(authorization.captured_amount, Some("1.5".parse().unwrap()));
+        let err = service
+            .process_transaction(&record(TxType::Void, 1, 2, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::AuthorizationClosed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_void_releases_the_hold() {
+        let mut service = authorized_service(HoldExpiry::default());
+
+        let err = service
+            .process_transaction(&record(TxType::Void, 1, 1, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::NotAnAuthorization { .. })
+        ));
+        let client = service
+            .process_transaction(&record(TxType::Void, 1, 2, None))
+            .unwrap();
+
+        assert_eq!(eur(&client), amounts("10", "0", "10"));
+        assert_eq!(
+            service.get_transaction(&TxId::new(2)).unwrap().status,
+            TransactionStatus::Voided
+        );
+    }
+
+    #[test]
+    fn test_hold_expires_after_records() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = authorized_service(HoldExpiry {
+            after_records: Some(2),
+            after_seconds: None,
+        });
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+
+        service
+            .process_transaction(&record(TxType::Deposit, 2, 3, Some("1")))
+            .unwrap();
+        let client = service
+            .process_transaction(&record(TxType::Deposit, 2, 4, Some("1")))
+            .unwrap();
+        assert_eq!(client.id, ClientId::new(2));
+        assert_eq!(
+            service.get_transaction(&TxId::new(2)).unwrap().status,
+            TransactionStatus::Authorized
+        );
+
+        let client = service
+            .process_transaction(&record(TxType::Deposit, 1, 5, Some("1")))
+            .unwrap();
+
+        assert_eq!(eur(&client), amounts("11", "0", "11"));
+        assert_eq!(
+            service.get_transaction(&TxId::new(2)).unwrap().status,
+            TransactionStatus::Expired
+        );
+        assert!(events.lock().unwrap().contains(&Event::Voided {
+            client: ClientId::new(1),
+            tx: TxId::new(2),
+            amount: "4".parse().unwrap(),
+            expired: true,
+        }));
+        let err = service
+            .process_transaction(&capture(1, 2, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::AuthorizationClosed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_hold_expires_after_seconds() {
+        let mut service = authorized_service(HoldExpiry {
+            after_records: None,
+            after_seconds: Some(0),
+        });
+
+        service
+            .process_transaction(&record(TxType::Deposit, 2, 3, Some("1")))
+            .unwrap();
+
+        assert_eq!(
+            service.get_transaction(&TxId::new(2)).unwrap().status,
+            TransactionStatus::Expired
+        );
+    }
+
+    #[test]
+    fn test_record_count_is_only_stored_for_hold_expiry() {
+        let records = [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Withdrawal, 1, 2, Some("20")),
+            record(TxType::Deposit, 1, 3, Some("1")),
+        ];
+        let stored_count = |hold_expiry| {
+            let store = SqliteStore::open_in_memory().unwrap();
+            let mut service = TransactionService::with_config(
+                store.client_repository(),
+                store.transaction_repository(),
+                ServiceConfig {
+                    hold_expiry,
+                    ..ServiceConfig::default()
+                },
+            );
+            for r in &records {
+                let _ = service.process_transaction(r);
+            }
+            store.transaction_repository().get_record_count().unwrap()
+        };
+
+        assert_eq!(stored_count(HoldExpiry::default()), 0);
+        // Rejected records count too.
+        let after_records = HoldExpiry {
+            after_records: Some(2),
+            after_seconds: None,
+        };
+        assert_eq!(stored_count(after_records), 3);
+    }
+
+    #[test]
+    fn test_holds_expire_after_resume_and_restart() {
+        let config = ServiceConfig {
+            hold_expiry: HoldExpiry {
+                after_records: Some(3),
+                after_seconds: None,
+            },
+            ..ServiceConfig::default()
+        };
+        let first = [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Authorize, 1, 2, Some("4")),
+        ];
+        let rest = [
+            record(TxType::Deposit, 2, 3, Some("1")),
+            record(TxType::Deposit, 2, 4, Some("1")),
+            record(TxType::Deposit, 2, 5, Some("1")),
+            capture(1, 2, None),
+        ];
+        fn finish<T: ClientRepository, V: TransactionRepository>(
+            service: &mut TransactionService<T, V>,
+            rest: &[InputRecord],
+        ) -> (Amount, Amount, Amount) {
+            let rejected: Vec<bool> = rest
+                .iter()
+                .map(|r| service.process_transaction(r).is_err())
+                .collect();
+            assert_eq!(rejected, [false, false, false, true]);
+            eur(&service.get_client(&ClientId::new(1)).unwrap())
+        }
+
+        let mut uninterrupted = in_memory_service(config.clone());
+        for r in &first {
+            uninterrupted.process_transaction(r).unwrap();
+        }
+        let expected = finish(&mut uninterrupted, &rest);
+        assert_eq!(expected, amounts("10", "0", "10"));
+
+        let mut service = in_memory_service(config.clone());
+        for r in &first {
+            service.process_transaction(r).unwrap();
+        }
+        let checkpoint = Checkpoint {
+            byte: 0,
+            line: 3,
+            record: 2,
+        };
+        let snapshot = Snapshot::capture(
+            checkpoint,
+            service.client_repository(),
+            service.transaction_repository(),
+        )
+        .unwrap();
+        let snapshot: Snapshot =
+            serde_json::from_str(&serde_json::to_string(&snapshot).unwrap()).unwrap();
+        let (clients, transactions) = snapshot.restore();
+        let mut resumed = TransactionService::with_config(clients, transactions, config.clone());
+        assert_eq!(finish(&mut resumed, &rest), expected);
+
+        let store = SqliteStore::open_in_memory().unwrap();
+        let mut service = TransactionService::with_config(
+            store.client_repository(),
+            store.transaction_repository(),
+            config.clone(),
+        );
+        for r in &first {
+            service.process_transaction(r).unwrap();
+        }
+        drop(service);
+        let mut restarted = TransactionService::with_config(
+            store.client_repository(),
+            store.transaction_repository(),
+            config,
+        );
+        assert_eq!(finish(&mut restarted, &rest), expected);
+    }
+
+    #[test]
+    fn test_authorizations_are_replayed() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(ServiceConfig {
+            hold_expiry: HoldExpiry {
+                after_records: Some(1),
+                after_seconds: None,
+            },
+            ..ServiceConfig::default()
+        });
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Authorize, 1, 2, Some("4")),
+            capture(1, 2, Some("3")),
+            record(TxType::Authorize, 1, 3, Some("2")),
+            record(TxType::Deposit, 1, 4, Some("1")),
+            record(TxType::Authorize, 1, 5, Some("1")),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        let mut replayed = in_memory_service(ServiceConfig::default());
+        for event in events.lock().unwrap().iter() {
+            replayed.apply_event(event).unwrap();
+        }
+
+        let client = replayed.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(eur(&client), amounts("7", "1", "8"));
+        for (id, status) in [
+            (2, TransactionStatus::Captured),
+            (3, TransactionStatus::Expired),
+            (5, TransactionStatus::Authorized),
+        ] {
+            assert_eq!(
+                replayed.get_transaction(&TxId::new(id)).unwrap().status,
+                status
+            );
+        }
+    }
+
+    fn refund(client: u16, tx: u32, amount: Option<&str>) -> InputRecord {
+        record(TxType::Refund, client, tx, amount)
+    }
+
+    #[test]
+    fn test_refunds_up_to_the_deposit_without_locking() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Withdrawal, 1, 2, Some("1")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Dispute, 1, 1, Some("2")))
+            .unwrap();
+
+        let client = service
+            .process_transaction(&refund(1, 1, Some("3")))
+            .unwrap();
+        assert_eq!(eur(&client), amounts("4", "2", "6"));
+        let err = service
+            .process_transaction(&refund(1, 1, Some("6")))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::RefundExceedsAmount { .. })
+        ));
+        let err = service
+            .process_transaction(&refund(1, 2, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::NotRefundable { .. })
+        ));
+        // The rest of the deposit can't be disputed any more.
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 1, 1, Some("6")))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::DisputeExceedsAmount { .. })
+        ));
+        service
+            .process_transaction(&record(TxType::Resolve, 1, 1, None))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 3, Some("1")))
+            .unwrap();
+        let client = service.process_transaction(&refund(1, 1, None)).unwrap();
+
+        assert_eq!(eur(&client), amounts("0", "0", "0"));
+        assert!(!client.is_locked());
+        let transaction = service.get_transaction(&TxId::new(1)).unwrap();
+        assert_eq!(transaction.refunded(), "10".parse().unwrap());
+    }
+
+    #[test]
+    fn test_reversals_need_an_operator() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("10")))
+            .unwrap();
+        service
+            .process_transaction(&record(TxType::Withdrawal, 1, 2, Some("4")))
+            .unwrap();
+
+        let err = service
+            .process_transaction(&record(TxType::Reversal, 1, 2, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::InvalidTransaction { .. })
+        ));
+        let client = service
+            .process_transaction(&admin(TxType::Reversal, 1, 2))
+            .unwrap();
+        assert_eq!(eur(&client), amounts("10", "0", "10"));
+
+        service
+            .process_transaction(&refund(1, 1, Some("3")))
+            .unwrap();
+        let client = service
+            .process_transaction(&admin(TxType::Reversal, 1, 1))
+            .unwrap();
+        assert_eq!(eur(&client), amounts("0", "0", "0"));
+        assert!(!client.is_locked());
+
+        for tx in [1, 2] {
+            let err = service
+                .process_transaction(&admin(TxType::Reversal, 1, tx))
+                .unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<TransactionError>(),
+                Some(TransactionError::AlreadyReversed { .. })
+            ));
+        }
+        let err = service
+            .process_transaction(&record(TxType::Dispute, 1, 1, None))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TransactionError>(),
+            Some(TransactionError::AlreadyReversed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_reversal_takes_what_was_not_charged_back() {
+        let mut service = in_memory_service(ServiceConfig::default());
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Dispute, 1, 1, Some("4")),
+            record(TxType::Chargeback, 1, 1, None),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        let client = service
+            .process_transaction(&admin(TxType::Reversal, 1, 1))
+            .unwrap();
+        assert_eq!(eur(&client), amounts("0", "0", "0"));
+        let deposit = service.get_transaction(&TxId::new(1)).unwrap();
+        assert_eq!(deposit.status, TransactionStatus::Reversed);
+    }
+
+    #[test]
+    fn test_refunds_and_reversals_are_replayed() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = in_memory_service(ServiceConfig::default());
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            refund(1, 1, Some("2.5")),
+            record(TxType::Withdrawal, 1, 2, Some("3")),
+            admin(TxType::Reversal, 1, 2),
+            refund(1, 1, Some("1")),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        let mut replayed = in_memory_service(ServiceConfig::default());
+        for event in events.lock().unwrap().iter() {
+            replayed.apply_event(event).unwrap();
+        }
+
+        let client = replayed.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(eur(&client), amounts("6.5", "0", "6.5"));
+        let deposit = replayed.get_transaction(&TxId::new(1)).unwrap();
+        assert_eq!(deposit.refunded(), "3.5".parse().unwrap());
+        let withdrawal = replayed.get_transaction(&TxId::new(2)).unwrap();
+        assert_eq!(withdrawal.status, TransactionStatus::Reversed);
+    }
+
+    fn fee_service() -> TransactionService<ClientRepositoryImpl, TransactionRepositoryImpl> {
+        in_memory_service(
+            ServiceConfig::from_toml(
+                r#"
+                [fees]
+                house_account = "900"
+                [fees.deposit]
+                flat = "1"
+                [fees.withdrawal]
+                percent = "1"
+                min = "0.5"
+                "#,
+            )
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_fees_are_paid_into_the_house_account() {
+        let mut service = fee_service();
+
+        let client = service
+            .process_transaction(&record(TxType::Deposit, 1, 1, Some("100")))
+            .unwrap();
+        assert_eq!(eur(&client), amounts("99", "0", "99"));
+        let client = service
+            .process_transaction(&record(TxType::Withdrawal, 1, 2, Some("10")))
+            .unwrap();
+        assert_eq!(eur(&client), amounts("88.5", "0", "88.5"));
+        let err = service
+            .process_transaction(&record(TxType::Withdrawal, 1, 3, Some("88")))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ClientError>(),
+            Some(ClientError::FundsUpdateError { .. })
+        ));
+
+        let house = service.get_client(&ClientId::new(900)).unwrap();
+        assert_eq!(eur(&house), amounts("1.5", "0", "1.5"));
+        let client = service.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(eur(&client), amounts("88.5", "0", "88.5"));
+        let fee = service.get_fee(&TxId::new(1)).unwrap();
+        assert_eq!(fee.tx_type, TxType::Fee);
+        assert_eq!(fee.client_id, ClientId::new(1));
+        assert_eq!(fee.destination_id, Some(ClientId::new(900)));
+        assert_eq!(fee.amount, "1".parse().unwrap());
+
+        // The house account's own deposits are free.
+        let house = service
+            .process_transaction(&record(TxType::Deposit, 900, 4, Some("1")))
+            .unwrap();
+        assert_eq!(eur(&house), amounts("2.5", "0", "2.5"));
+    }
+
+    #[test]
+    fn test_fee_is_refunded_on_chargeback() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut service = fee_service();
+        service.set_event_log(Box::new(RecordingEventLog {
+            events: events.clone(),
+        }));
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Deposit, 1, 2, Some("10")),
+            record(TxType::Dispute, 1, 1, Some("4")),
+            record(TxType::Chargeback, 1, 1, None),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+        // Only a chargeback of the whole deposit gives the fee back.
+        let house = service.get_client(&ClientId::new(900)).unwrap();
+        assert_eq!(eur(&house), amounts("2", "0", "2"));
+
+        for r in [
+            record(TxType::Dispute, 1, 1, None),
+            record(TxType::Chargeback, 1, 1, None),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        let mut replayed = in_memory_service(ServiceConfig::default());
+        for event in events.lock().unwrap().iter() {
+            replayed.apply_event(event).unwrap();
+        }
+        for service in [&service, &replayed] {
+            let client = service.get_client(&ClientId::new(1)).unwrap();
+            assert_eq!(eur(&client), amounts("9", "0", "9"));
+            assert!(client.is_locked());
+            let house = service.get_client(&ClientId::new(900)).unwrap();
+            assert_eq!(eur(&house), amounts("1", "0", "1"));
+            let fee = service.get_fee(&TxId::new(1)).unwrap();
+            assert_eq!(fee.refunded(), fee.amount);
+        }
+    }
+
+    #[test]
+    fn test_fee_is_refunded_on_chargeback_of_what_was_not_refunded() {
+        let mut service = fee_service();
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("10")),
+            record(TxType::Deposit, 1, 2, Some("10")),
+            record(TxType::Refund, 1, 1, Some("4")),
+            record(TxType::Dispute, 1, 1, None),
+            record(TxType::Chargeback, 1, 1, None),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        let client = service.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(eur(&client), amounts("9", "0", "9"));
+        let house = service.get_client(&ClientId::new(900)).unwrap();
+        assert_eq!(eur(&house), amounts("1", "0", "1"));
+        let fee = service.get_fee(&TxId::new(1)).unwrap();
+        assert_eq!(fee.refunded(), fee.amount);
+    }
+
+    #[test]
+    fn test_fees_leave_every_transaction_id_to_the_input() {
+        let mut service = fee_service();
+        for r in [
+            record(TxType::Deposit, 1, 1, Some("100")),
+            record(TxType::Withdrawal, 1, 2, Some("10")),
+        ] {
+            service.process_transaction(&r).unwrap();
+        }
+
+        for (tx, amount) in [(u32::MAX, "10"), (u32::MAX - 1, "5")] {
+            service
+                .process_transaction(&record(TxType::Deposit, 1, tx, Some(amount)))
+                .unwrap();
+        }
+
+        let client = service.get_client(&ClientId::new(1)).unwrap();
+        assert_eq!(eur(&client), amounts("101.5", "0", "101.5"));
+        let fee = service.get_fee(&TxId::new(u32::MAX)).unwrap();
+        assert_eq!(fee.amount, "1".parse().unwrap());
+        assert_eq!(
+            service
+                .get_transaction(&TxId::new(u32::MAX))
+                .unwrap()
+                .tx_type,
+            TxType::Deposit
+        );
+    }
 }